@@ -1,10 +1,25 @@
+use crate::dates::DateParser;
+use crate::document::{TaskDocument, COMMENT_PREFIX};
+use crate::fileio::{self, DEFAULT_BACKUPS};
+use crate::patch::Patch;
+use crate::query::TaskQuery;
+use crate::recurrence::{Frequency, Recurrence, RecurrenceEnd};
+use crate::sort::TaskSort;
+use crate::storage::{self, Storage};
+use crate::timelog::{format_duration, tracked_time, ReportGroup, TimeReport};
+use crate::todo::normalize_tag;
+use crate::todo::CategoryNode;
 use crate::todo::DueDate;
+use crate::todo::DueMode;
 use crate::todo::NewTask;
 use crate::todo::Priority;
+use crate::todo::RemoveSubtasks;
+use crate::todo::Status;
 use crate::todo::Task;
 use crate::todo::TaskUpdate;
 use crate::todo::TodoError;
 use crate::todo::TodoList;
+use crate::workspace::Workspace;
 use chrono::NaiveDate;
 use crossterm::{
     cursor::MoveUp,
@@ -12,21 +27,75 @@ use crossterm::{
     terminal::{Clear, ClearType},
     ExecutableCommand,
 };
-use std::collections::HashSet;
-use std::fs::{File, OpenOptions};
-use std::io::{self, stdout, BufRead, BufReader, Write};
-use std::path::Path;
+use std::collections::{BTreeSet, HashSet};
+use std::env;
+use std::fmt;
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, BufRead, BufReader, Write};
+use std::path::{Path, PathBuf};
+use std::process;
+use std::time::{Duration, Instant};
 
-// DEFAULT_CATEGORY is the default category for new tasks
-pub const DEFAULT_CATEGORY: &str = "General";
+pub use crate::todo::DEFAULT_CATEGORY;
 
 // FILENAME_HISTORY is the name of the file that stores the history of filenames
 const FILENAME_HISTORY: &str = ".todo_filenames.txt";
 
+// EDITOR_VARS are the environment variables naming the editor `edit` opens, in order of
+// preference, with FALLBACK_EDITOR used when neither is set
+const EDITOR_VARS: [&str; 2] = ["VISUAL", "EDITOR"];
+const FALLBACK_EDITOR: &str = "vi";
+
+// ERROR_MARKER starts the comment lines `edit` adds to point out parse errors
+const ERROR_MARKER: &str = "# ERROR: ";
+
 const AFFIRMATIVE_RESPONSES: [&str; 9] = [
     "y", "yes", "yeah", "yep", "ok", "sure", "true", "accept", "aff",
 ];
 
+// outln! and out! write to the shell's console as println! and print! write to stdout,
+// panicking if it can't be written to
+macro_rules! outln {
+    ($console:expr) => {
+        writeln!($console).expect("failed to write to the console")
+    };
+    ($console:expr, $($arg:tt)*) => {
+        writeln!($console, $($arg)*).expect("failed to write to the console")
+    };
+}
+
+macro_rules! out {
+    ($console:expr, $($arg:tt)*) => {
+        write!($console, $($arg)*).expect("failed to write to the console")
+    };
+}
+
+// Console is where the shell reads commands and answers from and writes its output to:
+// stdin and stdout when run from a terminal, which is when it is interactive
+struct Console<'a> {
+    input: &'a mut dyn BufRead,
+    output: &'a mut dyn Write,
+    interactive: bool,
+}
+
+impl Console<'_> {
+    // read_line reads a line of input into `line` as BufRead::read_line does, returning 0 at
+    // the end of the input
+    fn read_line(&mut self, line: &mut String) -> io::Result<usize> {
+        self.input.read_line(line)
+    }
+}
+
+impl Write for Console<'_> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.output.write(buf)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.output.flush()
+    }
+}
+
 struct FilenameTracker {
     filenames: HashSet<String>,
     tracking_enabled: bool,
@@ -68,305 +137,1710 @@ impl FilenameTracker {
     }
 }
 
-pub fn run_cli(todo_list: &mut TodoList) -> io::Result<()> {
+// Autosave says when the shell saves the list to its file without being asked
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum Autosave {
+    Off,
+    // OnChange saves after every command that changes the list
+    OnChange,
+    // Every saves a changed list once this long has passed since it was last saved. It is
+    // checked between commands, so nothing is saved while the shell waits for input
+    Every(Duration),
+}
+
+impl fmt::Display for Autosave {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Autosave::Off => write!(f, "off"),
+            Autosave::OnChange => write!(f, "after every change"),
+            Autosave::Every(interval) => write!(f, "every {} minutes", interval.as_secs() / 60),
+        }
+    }
+}
+
+// Session is the shell's state around the list: where it was loaded from or last saved to,
+// whether it has changed since, and when to save it without being asked
+struct Session {
+    // storage keeps any list file locked while the shell has it open
+    storage: Option<Box<dyn Storage>>,
+    // workspace holds the named lists, when the shell was started with one
+    workspace: Option<Workspace>,
+    dirty: bool,
+    autosave: Autosave,
+    last_saved: Instant,
+}
+
+impl Session {
+    fn new(storage: Option<Box<dyn Storage>>, workspace: Option<Workspace>) -> Self {
+        Session {
+            storage,
+            workspace,
+            dirty: false,
+            autosave: Autosave::Off,
+            last_saved: Instant::now(),
+        }
+    }
+
+    // autosave_due says whether the list has changes that autosave should write now
+    fn autosave_due(&self) -> bool {
+        self.dirty
+            && self.storage.is_some()
+            && match self.autosave {
+                Autosave::Off => false,
+                Autosave::OnChange => true,
+                Autosave::Every(interval) => self.last_saved.elapsed() >= interval,
+            }
+    }
+}
+
+// run_cli runs the interactive shell on `todo_list`, saving it to `storage` when there is one.
+// With a workspace, `todo_list` is its current list and the other lists can be used too. The
+// shell reads commands from `input` and writes to `output`, normally stdin and stdout; when
+// `interactive`, they are a terminal and saved files can be picked with the arrow keys
+pub fn run_cli(
+    todo_list: &mut TodoList,
+    storage: Option<Box<dyn Storage>>,
+    workspace: Option<Workspace>,
+    input: &mut dyn BufRead,
+    output: &mut dyn Write,
+    interactive: bool,
+) -> io::Result<()> {
+    let console = &mut Console {
+        input,
+        output,
+        interactive,
+    };
     let mut filename_tracker = FilenameTracker::new()?;
+    let mut session = Session::new(storage, workspace);
 
-    println!("Welcome to the Todo List CLI!");
-    print!("Would you like to enable filename tracking? (y/n): ");
-    io::stdout().flush()?;
+    outln!(console, "Welcome to the Todo List CLI!");
+    match (&session.workspace, &session.storage) {
+        (Some(workspace), _) => outln!(
+            console,
+            "Opened list {} in workspace {}.",
+            workspace.current(),
+            workspace.dir().display()
+        ),
+        (None, Some(storage)) => outln!(console, "Opened {}.", storage.describe()),
+        (None, None) => {}
+    }
+    out!(
+        console,
+        "Would you like to enable filename tracking? (y/n): "
+    );
+    console.flush()?;
     let mut response = String::new();
-    io::stdin().read_line(&mut response)?;
+    console.read_line(&mut response)?;
 
     if AFFIRMATIVE_RESPONSES.contains(&response.trim().to_lowercase().as_str()) {
         filename_tracker.enable_tracking();
-        println!("Filename tracking enabled.");
+        outln!(console, "Filename tracking enabled.");
     } else {
-        println!("Filename tracking disabled. You can enable it later by using the 'enable_tracking' command.");
+        outln!(console, "Filename tracking disabled. You can enable it later by using the 'enable_tracking' command.");
     }
 
     loop {
-        print!("Enter command (add/remove/list/get/update/categories/save/load/enable_tracking/quit): ");
-        io::stdout().flush()?;
+        out!(console, "Enter command (add/remove/list/tree/actionable/get/update/edit/notes/checklist/move/block/unblock/start/complete/reopen/track/stop/report/recur/overdue/today/upcoming/categories/rename_category/merge_categories/delete_category/tags/rename_tag/undo/redo/history/lists/new_list/switch_list/rename_list/delete_list/move_to_list/copy_to_list/save/save_as/load/autosave/enable_tracking/quit): ");
+        console.flush()?;
 
         let mut command = String::new();
-        io::stdin().read_line(&mut command)?;
+        if console.read_line(&mut command)? == 0 {
+            // Input ended, as when commands are piped in, so there is no one to ask
+            outln!(console);
+            save_on_exit(console, todo_list, &mut filename_tracker, &mut session)?;
+            quit(console);
+            break;
+        }
         let command = command.trim();
+        let changes = todo_list.changes();
 
         match command {
-            "add" => add_task(todo_list),
-            "remove" => remove_task(todo_list),
-            "list" => list_tasks(todo_list),
-            "get" => get_task(todo_list),
-            "update" => update_task(todo_list),
-            "categories" => list_categories(todo_list),
-            "save" => save_list(todo_list, &mut filename_tracker)?,
-            "load" => match load_list(&filename_tracker) {
-                Ok(loaded_list) => *todo_list = loaded_list,
-                Err(e) => println!("Error loading list: {}. Continuing with current list.", e),
+            "add" => add_task(console, todo_list),
+            "remove" => remove_task(console, todo_list),
+            "list" => list_tasks(console, todo_list),
+            "get" => get_task(console, todo_list),
+            "tree" => show_tree(console, todo_list),
+            "actionable" => list_actionable(console, todo_list),
+            "block" => change_dependency(console, todo_list, true),
+            "unblock" => change_dependency(console, todo_list, false),
+            "move" => move_task(console, todo_list),
+            "update" => update_task(console, todo_list),
+            "overdue" | "today" | "upcoming" => list_due_tasks(console, todo_list, command),
+            "recur" => set_recurrence(console, todo_list),
+            "start" => change_status(console, todo_list, Status::InProgress),
+            "complete" => change_status(console, todo_list, Status::Completed),
+            "reopen" => change_status(console, todo_list, Status::NotStarted),
+            "categories" => list_categories(console, todo_list),
+            "rename_category" | "merge_categories" | "delete_category" => {
+                change_category(console, todo_list, command)
+            }
+            "edit" => edit_task(console, todo_list),
+            "track" => start_timer(console, todo_list),
+            "stop" => stop_timer(console, todo_list),
+            "report" => time_report(console, todo_list),
+            "notes" => edit_notes(console, todo_list),
+            "checklist" => edit_checklist(console, todo_list),
+            "tags" => list_tags(console, todo_list),
+            "rename_tag" => rename_tag(console, todo_list),
+            "undo" => match todo_list.undo() {
+                Ok(change) => outln!(console, "Undid {}.", change),
+                Err(e) => outln!(console, "{}", e),
+            },
+            "redo" => match todo_list.redo() {
+                Ok(change) => outln!(console, "Redid {}.", change),
+                Err(e) => outln!(console, "{}", e),
             },
+            "history" => show_history(console, todo_list),
+            "lists" => show_lists(console, &mut session),
+            "new_list" => create_list(console, &mut session),
+            "switch_list" => switch_list(console, todo_list, &mut filename_tracker, &mut session)?,
+            "rename_list" => rename_list(console, &mut session),
+            "delete_list" => delete_list(console, &mut session),
+            "move_to_list" => transfer_task(console, todo_list, &mut session, true),
+            "copy_to_list" => transfer_task(console, todo_list, &mut session, false),
+            "save" => save_list(console, todo_list, &mut filename_tracker, &mut session)?,
+            "save_as" => save_list_as(console, todo_list, &mut filename_tracker, &mut session)?,
+            "load" => {
+                if confirm_discard(console, todo_list, &mut filename_tracker, &mut session)? {
+                    match load_list(console, &filename_tracker, &mut session) {
+                        Ok(loaded_list) => *todo_list = loaded_list,
+                        Err(e) => {
+                            outln!(
+                                console,
+                                "Error loading list: {}. Continuing with current list.",
+                                e
+                            )
+                        }
+                    }
+                }
+            }
+            "autosave" => set_autosave(console, &mut session),
             "enable_tracking" => {
                 filename_tracker.enable_tracking();
-                println!("Filename tracking enabled.");
+                outln!(console, "Filename tracking enabled.");
             }
             "quit" => {
-                quit();
+                if !confirm_discard(console, todo_list, &mut filename_tracker, &mut session)? {
+                    continue;
+                }
+                if let Some((id, _)) = todo_list.running_timer() {
+                    outln!(console,
+                        "The timer on task id {} is still running; it keeps running in saved lists.",
+                        id
+                    );
+                }
+                quit(console);
                 break;
             }
-            _ => println!("Unknown command."),
+            _ => outln!(console, "Unknown command."),
+        }
+
+        if !matches!(
+            command,
+            "save" | "save_as" | "load" | "switch_list" | "move_to_list"
+        ) && todo_list.changes() != changes
+        {
+            session.dirty = true;
+        }
+        if session.autosave_due() {
+            save_to_storage(console, todo_list, &mut filename_tracker, &mut session)?;
         }
     }
     Ok(())
 }
 
-fn add_task(todo_list: &mut TodoList) {
-    print!("Enter task: ");
-    io::stdout().flush().unwrap();
-    let mut description = String::new();
-    io::stdin().read_line(&mut description).unwrap();
+// confirm_discard asks what to do with unsaved changes before they would be lost, saving
+// them if asked to. It returns false when the user would rather go back to the list
+fn confirm_discard(
+    console: &mut Console,
+    todo_list: &TodoList,
+    filename_tracker: &mut FilenameTracker,
+    session: &mut Session,
+) -> io::Result<bool> {
+    if !session.dirty {
+        return Ok(true);
+    }
+    out!(
+        console,
+        "The list has unsaved changes. Save them first? (y/n, leave blank to go back): "
+    );
+    console.flush()?;
+    let mut answer = String::new();
+    console.read_line(&mut answer)?;
+    let answer = answer.trim().to_lowercase();
+    if answer.is_empty() {
+        return Ok(false);
+    }
+    if AFFIRMATIVE_RESPONSES.contains(&answer.as_str()) {
+        save_list(console, todo_list, filename_tracker, session)?;
+        return Ok(!session.dirty);
+    }
+    Ok(true)
+}
 
-    let description = description.trim().to_string();
+// save_on_exit saves unsaved changes to the current file when the shell ends without a
+// chance to ask
+fn save_on_exit(
+    console: &mut Console,
+    todo_list: &TodoList,
+    filename_tracker: &mut FilenameTracker,
+    session: &mut Session,
+) -> io::Result<()> {
+    if !session.dirty {
+        return Ok(());
+    }
+    if session.storage.is_none() {
+        outln!(
+            console,
+            "The list was never saved to a file, so its changes are lost."
+        );
+        return Ok(());
+    }
+    save_to_storage(console, todo_list, filename_tracker, session).map(|_| ())
+}
 
-    print!("Enter due date (YYYY-MM-DD, leave blank for no date): ");
-    io::stdout().flush().unwrap();
-    let mut date_string = String::new();
+fn set_autosave(console: &mut Console, session: &mut Session) {
+    out!(
+        console,
+        "Autosave (off, change, or a number of minutes; currently {}): ",
+        session.autosave
+    );
+    console.flush().unwrap();
+    let mut answer = String::new();
+    console.read_line(&mut answer).unwrap();
+    session.autosave = match answer.trim().to_lowercase().as_str() {
+        "" => return,
+        "off" | "no" | "n" => Autosave::Off,
+        "change" | "changes" | "on" | "y" => Autosave::OnChange,
+        minutes => match minutes.parse::<u64>() {
+            Ok(minutes) if minutes > 0 => Autosave::Every(Duration::from_secs(minutes * 60)),
+            _ => {
+                outln!(console, "Invalid autosave setting.");
+                return;
+            }
+        },
+    };
+    match &session.storage {
+        Some(storage) => outln!(
+            console,
+            "Autosave is {}, saving to {}.",
+            session.autosave,
+            storage.describe()
+        ),
+        None if session.autosave != Autosave::Off => outln!(
+            console,
+            "Autosave is {}; it starts once the list is saved to a file.",
+            session.autosave
+        ),
+        None => outln!(console, "Autosave is off."),
+    }
+}
 
-    io::stdin().read_line(&mut date_string).unwrap();
-    let date_string = date_string.trim();
+// open_workspace returns the shell's workspace, explaining how to start one when there is none
+fn open_workspace<'a>(
+    console: &mut Console,
+    session: &'a mut Session,
+) -> Option<&'a mut Workspace> {
+    if session.workspace.is_none() {
+        outln!(
+            console,
+            "No workspace is open; start the shell with --workspace <dir> to use named lists."
+        );
+    }
+    session.workspace.as_mut()
+}
+
+fn read_list_name(console: &mut Console, prompt: &str) -> String {
+    out!(console, "{}", prompt);
+    console.flush().unwrap();
+    let mut name = String::new();
+    console.read_line(&mut name).unwrap();
+    name.trim().to_string()
+}
+
+// show_lists lists the lists in the workspace, marking the current one
+fn show_lists(console: &mut Console, session: &mut Session) {
+    let Some(workspace) = open_workspace(console, session) else {
+        return;
+    };
+    match workspace.names() {
+        Ok(names) => {
+            for name in names {
+                let marker = if name == workspace.current() {
+                    "*"
+                } else {
+                    " "
+                };
+                outln!(console, "{} {}", marker, name);
+            }
+        }
+        Err(e) => outln!(console, "{}", e),
+    }
+}
+
+fn create_list(console: &mut Console, session: &mut Session) {
+    let Some(workspace) = open_workspace(console, session) else {
+        return;
+    };
+    let name = read_list_name(console, "Enter name of the new list: ");
+    match workspace.create(&name) {
+        Ok(()) => outln!(console, "List {} created.", name),
+        Err(e) => outln!(console, "{}", e),
+    }
+}
+
+// switch_list makes another list in the workspace the current one, once any unsaved changes
+// to this one are saved or dropped
+fn switch_list(
+    console: &mut Console,
+    todo_list: &mut TodoList,
+    filename_tracker: &mut FilenameTracker,
+    session: &mut Session,
+) -> io::Result<()> {
+    let Some(workspace) = open_workspace(console, session) else {
+        return Ok(());
+    };
+    let name = read_list_name(console, "Enter list to switch to: ");
+    if name == workspace.current() {
+        outln!(console, "List {} is already the current list.", name);
+        return Ok(());
+    }
+    if !workspace.contains(&name) {
+        outln!(console, "{}", TodoError::ListNotFound(name));
+        return Ok(());
+    }
+    if !confirm_discard(console, todo_list, filename_tracker, session)? {
+        return Ok(());
+    }
+    let Some(workspace) = session.workspace.as_mut() else {
+        return Ok(());
+    };
+    match workspace.switch(&name) {
+        Ok((storage, list)) => {
+            *todo_list = list;
+            session.storage = Some(storage);
+            session.dirty = false;
+            session.last_saved = Instant::now();
+            outln!(console, "Switched to list {}.", name);
+        }
+        Err(e) => outln!(console, "{}", e),
+    }
+    Ok(())
+}
 
-    let due_date = if date_string.is_empty() {
-        DueDate::None
+// rename_list renames a list. The current list's file is closed while it is renamed and
+// reopened under the new name, keeping any unsaved changes
+fn rename_list(console: &mut Console, session: &mut Session) {
+    let Some(workspace) = open_workspace(console, session) else {
+        return;
+    };
+    let name = read_list_name(
+        console,
+        "Enter list to rename (leave blank for the current list): ",
+    );
+    let name = if name.is_empty() {
+        workspace.current().to_string()
     } else {
-        match NaiveDate::parse_from_str(date_string, "%Y-%m-%d") {
-            Ok(date) => DueDate::On(date),
-            Err(_) => {
-                println!("Invalid date format. Setting due date to none.");
-                DueDate::None
+        name
+    };
+    let new_name = read_list_name(console, "Enter new list name: ");
+    let is_current = name == workspace.current();
+    if is_current {
+        session.storage = None;
+    }
+
+    let Some(workspace) = session.workspace.as_mut() else {
+        return;
+    };
+    match workspace.rename(&name, &new_name) {
+        Ok(()) => outln!(console, "List {} renamed to {}.", name, new_name),
+        Err(e) => outln!(console, "{}", e),
+    }
+    if is_current {
+        let current = workspace.current().to_string();
+        match workspace.switch(&current) {
+            Ok((storage, _)) => session.storage = Some(storage),
+            Err(e) => outln!(console, "The list could not be reopened: {}", e),
+        }
+    }
+}
+
+fn delete_list(console: &mut Console, session: &mut Session) {
+    let Some(workspace) = open_workspace(console, session) else {
+        return;
+    };
+    let name = read_list_name(console, "Enter list to delete: ");
+    if !workspace.contains(&name) {
+        outln!(console, "{}", TodoError::ListNotFound(name));
+        return;
+    }
+    let answer = read_list_name(
+        console,
+        &format!("Delete list {} and all of its tasks? (y/n): ", name),
+    );
+    if !AFFIRMATIVE_RESPONSES.contains(&answer.to_lowercase().as_str()) {
+        return;
+    }
+    match workspace.delete(&name) {
+        Ok(()) => outln!(console, "List {} deleted.", name),
+        Err(e) => outln!(console, "{}", e),
+    }
+}
+
+// transfer_task moves or copies a task and its subtasks to another list in the workspace,
+// which is saved straight away. A move leaves its removal from this list unsaved, like any
+// other change
+fn transfer_task(
+    console: &mut Console,
+    todo_list: &mut TodoList,
+    session: &mut Session,
+    remove: bool,
+) {
+    if open_workspace(console, session).is_none() {
+        return;
+    }
+    let Some(id) = read_task_id(console) else {
+        return;
+    };
+    let to = read_list_name(console, "Enter list to send the task to: ");
+    let (Some(workspace), Some(storage)) = (&session.workspace, session.storage.as_mut()) else {
+        return;
+    };
+    let result = if remove {
+        workspace.move_task(todo_list, storage.as_mut(), id, &to)
+    } else {
+        workspace.copy_task(todo_list, id, &to)
+    };
+    match result {
+        Ok(ids) => {
+            // Moving saves the current list along with the list the task went to
+            if remove {
+                session.dirty = false;
+                session.last_saved = Instant::now();
             }
+            let ids: Vec<String> = ids.iter().map(u32::to_string).collect();
+            outln!(
+                console,
+                "Task id {} {} to list {} as task ids {}.",
+                id,
+                if remove { "moved" } else { "copied" },
+                to,
+                ids.join(", ")
+            );
         }
+        Err(e) => outln!(console, "{}", e),
+    }
+}
+
+fn add_task(console: &mut Console, todo_list: &mut TodoList) {
+    out!(console, "Enter task (add tags with +tag): ");
+    console.flush().unwrap();
+    let mut description = String::new();
+    console.read_line(&mut description).unwrap();
+
+    let (description, tags, _) = split_tags(&description, &BTreeSet::new());
+
+    let due_date = match read_date(
+        console,
+        "Enter due date (e.g. 2026-11-01, tomorrow, next fri, in 3 days, leave blank for no date): ",
+        false,
+    ) {
+        DateInput::Date(date) => read_due_mode(console, "use on").unwrap_or_default().due(date),
+        DateInput::Blank | DateInput::Clear => DueDate::None,
     };
 
-    print!("Enter category (leave blank for general): ");
-    io::stdout().flush().unwrap();
+    out!(
+        console,
+        "Enter category, e.g. Work/ClientA (leave blank for general): "
+    );
+    console.flush().unwrap();
     let mut category = String::new();
-    io::stdin().read_line(&mut category).unwrap();
+    console.read_line(&mut category).unwrap();
     let category_string = category.trim().to_string();
     if category_string.is_empty() {
         category = DEFAULT_CATEGORY.to_string();
     } else {
         category = category_string;
     }
-    let id = todo_list.add_task(NewTask {
+
+    out!(
+        console,
+        "Enter priority (low/medium/high, leave blank for low): "
+    );
+    console.flush().unwrap();
+    let mut priority_string = String::new();
+    console.read_line(&mut priority_string).unwrap();
+    let priority_string = priority_string.trim();
+    let priority = if priority_string.is_empty() {
+        Priority::Low
+    } else {
+        match priority_string.parse() {
+            Ok(priority) => priority,
+            Err(_) => {
+                outln!(console, "Invalid priority. Setting priority to low.");
+                Priority::Low
+            }
+        }
+    };
+
+    out!(
+        console,
+        "Enter parent task ID (leave blank for a top-level task): "
+    );
+    console.flush().unwrap();
+    let mut parent = String::new();
+    console.read_line(&mut parent).unwrap();
+    let parent: Option<u32> = parent.trim().parse().ok();
+
+    let new_task = NewTask {
         description,
         due_date,
         category,
-        priority: Priority::Low,
-    });
-    println!("Task added with ID {}.", id);
+        priority,
+    };
+    let id = match parent {
+        Some(parent) => match todo_list.add_subtask(parent, new_task) {
+            Ok(id) => id,
+            Err(e) => {
+                outln!(console, "{}", e);
+                return;
+            }
+        },
+        None => todo_list.add_task(new_task),
+    };
+    for tag in tags {
+        if let Err(e) = todo_list.add_tag(id, &tag) {
+            outln!(console, "{}", e);
+        }
+    }
+    outln!(console, "Task added with ID {}.", id);
 }
 
-fn remove_task(todo_list: &mut TodoList) {
-    print!("Enter task ID: ");
-    io::stdout().flush().unwrap();
+// split_tags pulls `+tag` words out of a description, along with `-tag` words naming one of
+// the task's `tags` to remove, and returns the remaining text. Any other word, such as `-5`,
+// stays in the text
+fn split_tags(input: &str, tags: &BTreeSet<String>) -> (String, Vec<String>, Vec<String>) {
+    let mut words = Vec::new();
+    let mut added = Vec::new();
+    let mut removed = Vec::new();
+    for word in input.split_whitespace() {
+        let removes_tag = |tag: &str| normalize_tag(tag).is_ok_and(|tag| tags.contains(&tag));
+        match (word.strip_prefix('+'), word.strip_prefix('-')) {
+            (Some(tag), _) if !tag.is_empty() => added.push(tag.to_string()),
+            (_, Some(tag)) if removes_tag(tag) => removed.push(tag.to_string()),
+            _ => words.push(word),
+        }
+    }
+    (words.join(" "), added, removed)
+}
+
+fn remove_task(console: &mut Console, todo_list: &mut TodoList) {
+    out!(console, "Enter task ID: ");
+    console.flush().unwrap();
     let mut id = String::new();
-    io::stdin().read_line(&mut id).unwrap();
+    console.read_line(&mut id).unwrap();
     let id: u32 = id.trim().parse().unwrap();
-    if todo_list.remove_task(id).is_ok() {
-        println!("Task removed.");
+
+    let mut subtasks = RemoveSubtasks::Promote;
+    let subtask_count = todo_list.progress(id).total;
+    if subtask_count > 0 {
+        out!(
+            console,
+            "Task has {} subtasks. Remove them too? (y/n, n moves them up a level): ",
+            subtask_count
+        );
+        console.flush().unwrap();
+        let mut response = String::new();
+        console.read_line(&mut response).unwrap();
+        if AFFIRMATIVE_RESPONSES.contains(&response.trim().to_lowercase().as_str()) {
+            subtasks = RemoveSubtasks::Cascade;
+        }
+    }
+
+    let dependents = todo_list.removal_dependents(id, subtasks);
+    if !dependents.is_empty() {
+        let ids: Vec<String> = dependents.iter().map(|task| task.id.to_string()).collect();
+        outln!(
+            console,
+            "Warning: tasks {} are blocked by the tasks being removed and will no longer be.",
+            ids.join(", ")
+        );
+    }
+
+    match todo_list.remove_task_with(id, subtasks) {
+        Ok(removed) if removed.len() > 1 => outln!(console, "Removed {} tasks.", removed.len()),
+        Ok(_) => outln!(console, "Task removed."),
+        Err(_) => outln!(console, "Task not found."),
+    }
+}
+
+// change_dependency links or unlinks a task and a task blocking it
+fn change_dependency(console: &mut Console, todo_list: &mut TodoList, block: bool) {
+    let Some(id) = read_task_id(console) else {
+        return;
+    };
+    out!(console, "Enter ID of the blocking task: ");
+    console.flush().unwrap();
+    let mut blocker = String::new();
+    console.read_line(&mut blocker).unwrap();
+    let Ok(blocker) = blocker.trim().parse() else {
+        outln!(console, "Invalid task ID.");
+        return;
+    };
+
+    let result = if block {
+        todo_list.add_dependency(id, blocker)
     } else {
-        println!("Task not found.");
+        todo_list.remove_dependency(id, blocker)
+    };
+    match result {
+        Ok(()) if block => outln!(
+            console,
+            "Task id {} is now blocked by task id {}.",
+            id,
+            blocker
+        ),
+        Ok(()) => outln!(
+            console,
+            "Task id {} is no longer blocked by task id {}.",
+            id,
+            blocker
+        ),
+        Err(e) => outln!(console, "{}", e),
     }
 }
 
-fn list_tasks(todo_list: &TodoList) {
-    print!("Enter category to list (leave blank for all): ");
-    io::stdout().flush().unwrap();
-    let mut category = String::new();
-    io::stdin().read_line(&mut category).unwrap();
-    let category = category.trim();
-    let category = if category.is_empty() {
+// list_actionable prints the unfinished tasks that are not waiting on any other task
+fn list_actionable(console: &mut Console, todo_list: &TodoList) {
+    let tasks = todo_list.actionable_tasks();
+    if tasks.is_empty() {
+        outln!(console, "No tasks.");
+    }
+    for task in tasks {
+        print_task_details(console, todo_list, task);
+    }
+}
+
+// show_tree prints every task with its subtasks indented beneath it
+fn show_tree(console: &mut Console, todo_list: &TodoList) {
+    for (depth, task) in todo_list.task_tree() {
+        outln!(
+            console,
+            "{}{}",
+            "    ".repeat(depth),
+            format_tree_line(todo_list, task)
+        );
+    }
+}
+
+// format_tree_line renders a task as a checkbox line with its subtask progress
+pub fn format_tree_line(todo_list: &TodoList, task: &Task) -> String {
+    let check = match task.status {
+        Status::Completed => "[x]",
+        Status::InProgress => "[~]",
+        Status::NotStarted => "[ ]",
+    };
+    let progress = todo_list.progress(task.id);
+    if progress.total > 0 {
+        format!(
+            "{} {} {} ({}/{})",
+            check, task.id, task.description, progress.completed, progress.total
+        )
+    } else {
+        format!("{} {} {}", check, task.id, task.description)
+    }
+}
+
+// move_task moves a task and its subtasks under another task
+fn move_task(console: &mut Console, todo_list: &mut TodoList) {
+    let Some(id) = read_task_id(console) else {
+        return;
+    };
+    out!(
+        console,
+        "Enter new parent task ID (leave blank to make it a top-level task): "
+    );
+    console.flush().unwrap();
+    let mut parent = String::new();
+    console.read_line(&mut parent).unwrap();
+    let parent = parent.trim();
+    let parent = if parent.is_empty() {
         None
     } else {
-        Some(category)
+        match parent.parse() {
+            Ok(parent) => Some(parent),
+            Err(_) => {
+                outln!(console, "Invalid task ID.");
+                return;
+            }
+        }
     };
 
-    let tasks = todo_list.list_tasks(category);
+    match todo_list.move_task(id, parent) {
+        Ok(()) => outln!(console, "Task id {} moved.", id),
+        Err(e) => outln!(console, "{}", e),
+    }
+}
+
+fn list_tasks(console: &mut Console, todo_list: &TodoList) {
+    out!(console, "Enter filter (e.g. cat:Work +urgent prio>=medium due<2026-11-01 status:open \"invoice\", leave blank for all): ");
+    console.flush().unwrap();
+    let mut filter = String::new();
+    console.read_line(&mut filter).unwrap();
+
+    let query = match TaskQuery::parse(&filter) {
+        Ok(query) => query,
+        Err(e) => {
+            outln!(console, "{}", e);
+            return;
+        }
+    };
+
+    out!(
+        console,
+        "Sort by (e.g. priority:desc,due,id, leave blank for priority then due date): "
+    );
+    console.flush().unwrap();
+    let mut sort = String::new();
+    console.read_line(&mut sort).unwrap();
+    let sort = if sort.trim().is_empty() {
+        TaskSort::default()
+    } else {
+        match sort.parse() {
+            Ok(sort) => sort,
+            Err(e) => {
+                outln!(console, "{}", e);
+                return;
+            }
+        }
+    };
+
+    let tasks = todo_list.query_sorted(&query, &sort);
     for task in tasks {
-        print_task_details(task)
+        print_task_details(console, todo_list, task)
     }
 }
 
-fn update_task(todo_list: &mut TodoList) {
-    print!("Enter task ID: ");
-    io::stdout().flush().unwrap();
+fn update_task(console: &mut Console, todo_list: &mut TodoList) {
+    out!(console, "Enter task ID: ");
+    console.flush().unwrap();
     let mut id = String::new();
-    io::stdin().read_line(&mut id).unwrap();
+    console.read_line(&mut id).unwrap();
     let id: u32 = id.trim().parse().unwrap();
 
     if let Some(task) = todo_list.get_task(id) {
-        print_task_details(task)
+        print_task_details(console, todo_list, task)
     } else {
-        println!("Task not found.");
+        outln!(console, "Task not found.");
         return;
     }
 
-    print!("Enter new description (leave blank to keep current): ");
-    io::stdout().flush().unwrap();
+    out!(console, "Enter new description, +tag to add a tag, -tag to remove one (leave blank to keep current): ");
+    console.flush().unwrap();
     let mut description = String::new();
-    io::stdin().read_line(&mut description).unwrap();
-    let description = if description.trim().is_empty() {
-        None
+    console.read_line(&mut description).unwrap();
+    let tags = todo_list
+        .get_task(id)
+        .map(|task| task.tags.clone())
+        .unwrap_or_default();
+    let (description, added_tags, removed_tags) = split_tags(&description, &tags);
+    let description = if description.is_empty() {
+        Patch::Keep
     } else {
-        Some(description.trim().to_string())
+        Patch::Set(description)
     };
 
-    print!("Enter new due date (YYYY-MM-DD, leave blank to keep current): ");
-    io::stdout().flush().unwrap();
-    let mut date_string = String::new();
-    io::stdin().read_line(&mut date_string).unwrap();
-    let date_string = date_string.trim();
-    let due_date = if !date_string.is_empty() {
-        match NaiveDate::parse_from_str(date_string, "%Y-%m-%d") {
-            Ok(date) => Some(date),
-            Err(_) => {
-                println!("Invalid date format. Task will be updated without a due date.");
-                None
-            }
-        }
-    } else {
-        None
+    let has_due_date = todo_list
+        .get_task(id)
+        .is_some_and(|task| task.due_date.date().is_some());
+    let (due_date, due_mode) = match read_date(
+        console,
+        "Enter new due date (e.g. 2026-11-01, next fri, 2w, 'none' to clear, leave blank to keep current): ",
+        true,
+    ) {
+        DateInput::Date(date) => (Patch::Set(date), read_due_mode(console, "keep current").into()),
+        DateInput::Clear => (Patch::Clear, Patch::Keep),
+        DateInput::Blank if has_due_date => (Patch::Keep, read_due_mode(console, "keep current").into()),
+        DateInput::Blank => (Patch::Keep, Patch::Keep),
     };
 
-    print!("Enter new category (leave blank to keep current): ");
-    io::stdout().flush().unwrap();
+    out!(
+        console,
+        "Enter new category ('none' to move it to {}, leave blank to keep current): ",
+        DEFAULT_CATEGORY
+    );
+    console.flush().unwrap();
     let mut category = String::new();
-    io::stdin().read_line(&mut category).unwrap();
-    let category_string = category.trim().to_string();
-    let category = if category_string.is_empty() {
-        None
-    } else {
-        Some(category_string)
+    console.read_line(&mut category).unwrap();
+    let category = match category.trim() {
+        "" => Patch::Keep,
+        none if none.eq_ignore_ascii_case("none") => Patch::Clear,
+        category => Patch::Set(category.to_string()),
+    };
+
+    let priority = loop {
+        out!(console, "Enter new priority (low/medium/high, 'none' to reset to low, leave blank to keep current): ");
+        console.flush().unwrap();
+        let mut priority = String::new();
+        console.read_line(&mut priority).unwrap();
+        match priority.trim() {
+            "" => break Patch::Keep,
+            none if none.eq_ignore_ascii_case("none") => break Patch::Clear,
+            priority => match priority.parse() {
+                Ok(priority) => break Patch::Set(priority),
+                Err(e) => outln!(console, "{}. Please try again.", e),
+            },
+        }
     };
 
     let task_update = TaskUpdate {
         description,
         due_date,
+        due_mode,
         category,
+        priority,
     };
 
     if todo_list.update_task(id, task_update).is_ok() {
-        println!("Task id {} updated.", id);
+        for tag in &added_tags {
+            if let Err(e) = todo_list.add_tag(id, tag) {
+                outln!(console, "{}", e);
+            }
+        }
+        for tag in &removed_tags {
+            if let Err(e) = todo_list.remove_tag(id, tag) {
+                outln!(console, "{}", e);
+            }
+        }
+        outln!(console, "Task id {} updated.", id);
+    }
+}
+
+// DateInput is the answer to a date prompt
+enum DateInput {
+    Blank,
+    Clear,
+    Date(NaiveDate),
+}
+
+// read_date prompts until the input is blank or resolves to a date, echoing the resolved
+// date back so relative input like "next fri" can be checked
+fn read_date(console: &mut Console, prompt: &str, allow_clear: bool) -> DateInput {
+    let parser = DateParser::from_env();
+    loop {
+        out!(console, "{}", prompt);
+        console.flush().unwrap();
+        let mut date_string = String::new();
+        console.read_line(&mut date_string).unwrap();
+        let date_string = date_string.trim();
+
+        if date_string.is_empty() {
+            return DateInput::Blank;
+        }
+        if allow_clear && date_string.eq_ignore_ascii_case("none") {
+            return DateInput::Clear;
+        }
+        match parser.parse(date_string, chrono::Local::now().date_naive()) {
+            Ok(date) => {
+                outln!(
+                    console,
+                    "Due date resolved to {}.",
+                    date.format("%A %Y-%m-%d")
+                );
+                return DateInput::Date(date);
+            }
+            Err(e) => outln!(console, "{}. Please try again.", e),
+        }
+    }
+}
+
+// read_due_mode asks whether a task is due on a date or no later than it, returning None
+// when the answer is left blank
+fn read_due_mode(console: &mut Console, blank: &str) -> Option<DueMode> {
+    loop {
+        out!(
+            console,
+            "Due on that date, or no later than it? (on/by, leave blank to {}): ",
+            blank
+        );
+        console.flush().unwrap();
+        let mut mode = String::new();
+        console.read_line(&mut mode).unwrap();
+        if mode.trim().is_empty() {
+            return None;
+        }
+        match mode.parse() {
+            Ok(mode) => return Some(mode),
+            Err(e) => outln!(console, "{}. Please try again.", e),
+        }
+    }
+}
+
+// list_due_tasks prints the tasks that are overdue, due today, or due within a number of days
+fn list_due_tasks(console: &mut Console, todo_list: &TodoList, view: &str) {
+    let today = chrono::Local::now().date_naive();
+    let tasks = match view {
+        "overdue" => todo_list.overdue_tasks(today),
+        "today" => todo_list.due_today_tasks(today),
+        _ => {
+            out!(
+                console,
+                "Enter number of days to look ahead (leave blank for 7): "
+            );
+            console.flush().unwrap();
+            let mut days = String::new();
+            console.read_line(&mut days).unwrap();
+            let days = days.trim().parse().unwrap_or(7);
+            todo_list.due_within_days(today, days)
+        }
+    };
+
+    if tasks.is_empty() {
+        outln!(console, "No tasks.");
+    }
+    for task in tasks {
+        print_task_details(console, todo_list, task);
+    }
+}
+
+// change_status moves a task to the given status
+fn change_status(console: &mut Console, todo_list: &mut TodoList, status: Status) {
+    let Some(id) = read_task_id(console) else {
+        return;
+    };
+
+    let result = match status {
+        Status::NotStarted => todo_list.reopen_task(id).map(|()| None),
+        Status::InProgress => todo_list.start_task(id).map(|()| None),
+        Status::Completed => todo_list.complete_task(id),
+    };
+    match result {
+        Ok(next_id) => {
+            outln!(console, "Task id {} is now {}.", id, status);
+            if let Some(next_id) = next_id {
+                outln!(console, "Next occurrence added with ID {}.", next_id);
+            }
+        }
+        Err(e) => outln!(console, "{}", e),
+    }
+}
+
+// set_recurrence makes a task repeat, or stops it repeating
+fn set_recurrence(console: &mut Console, todo_list: &mut TodoList) {
+    let Some(id) = read_task_id(console) else {
+        return;
+    };
+    if todo_list.get_task(id).is_none() {
+        outln!(console, "Task not found.");
+        return;
+    }
+
+    out!(
+        console,
+        "Repeat (daily, weekly [mon,thu], monthly <day>, every <n> days, none): "
+    );
+    console.flush().unwrap();
+    let mut frequency = String::new();
+    console.read_line(&mut frequency).unwrap();
+    let frequency = frequency.trim();
+    if frequency.is_empty() || frequency.eq_ignore_ascii_case("none") {
+        if todo_list.set_recurrence(id, None).is_ok() {
+            outln!(console, "Task id {} no longer repeats.", id);
+        }
+        return;
+    }
+    let frequency: Frequency = match frequency.parse() {
+        Ok(frequency) => frequency,
+        Err(e) => {
+            outln!(console, "{}", e);
+            return;
+        }
+    };
+
+    out!(
+        console,
+        "Stop after how many occurrences? (leave blank to use an end date): "
+    );
+    console.flush().unwrap();
+    let mut count = String::new();
+    console.read_line(&mut count).unwrap();
+    let end = match count.trim().parse() {
+        Ok(count) => RecurrenceEnd::Count(count),
+        Err(_) => match read_date(
+            console,
+            "Repeat until (leave blank to repeat forever): ",
+            false,
+        ) {
+            DateInput::Date(date) => RecurrenceEnd::Until(date),
+            DateInput::Blank | DateInput::Clear => RecurrenceEnd::Never,
+        },
+    };
+
+    let recurrence = Recurrence { frequency, end };
+    outln!(console, "Task id {} repeats {}.", id, recurrence);
+    let _ = todo_list.set_recurrence(id, Some(recurrence));
+}
+
+// read_task_id prompts for a task ID, reporting input that is not a number
+fn read_task_id(console: &mut Console) -> Option<u32> {
+    out!(console, "Enter task ID: ");
+    console.flush().unwrap();
+    let mut id = String::new();
+    console.read_line(&mut id).unwrap();
+    match id.trim().parse() {
+        Ok(id) => Some(id),
+        Err(_) => {
+            outln!(console, "Invalid task ID.");
+            None
+        }
+    }
+}
+
+fn list_categories(console: &mut Console, todo_list: &TodoList) {
+    let lines = format_category_tree(&todo_list.category_tree());
+    if lines.is_empty() {
+        outln!(console, "No categories.");
+    }
+    for line in lines {
+        outln!(console, "{}", line);
+    }
+}
+
+// format_category_tree renders the category tree with subcategories indented beneath their
+// parents and the number of tasks under each category, subcategories included
+pub fn format_category_tree(nodes: &[CategoryNode]) -> Vec<String> {
+    let mut lines = Vec::new();
+    push_category_lines(nodes, 0, &mut lines);
+    lines
+}
+
+fn push_category_lines(nodes: &[CategoryNode], depth: usize, lines: &mut Vec<String>) {
+    for node in nodes {
+        lines.push(format!(
+            "{}{} ({})",
+            "    ".repeat(depth),
+            node.name,
+            node.total
+        ));
+        push_category_lines(&node.children, depth + 1, lines);
+    }
+}
+
+// change_category renames, merges or deletes a category across every task in it
+fn change_category(console: &mut Console, todo_list: &mut TodoList, action: &str) {
+    out!(console, "Enter category: ");
+    console.flush().unwrap();
+    let mut category = String::new();
+    console.read_line(&mut category).unwrap();
+
+    let result = match action {
+        "delete_category" => todo_list.delete_category(&category),
+        _ => {
+            if action == "merge_categories" {
+                out!(console, "Enter category to merge it into: ");
+            } else {
+                out!(console, "Enter new category name: ");
+            }
+            console.flush().unwrap();
+            let mut target = String::new();
+            console.read_line(&mut target).unwrap();
+            if action == "merge_categories" {
+                todo_list.merge_categories(&category, &target)
+            } else {
+                todo_list.rename_category(&category, &target)
+            }
+        }
+    };
+    match result {
+        Ok(count) => outln!(console, "Moved {} tasks.", count),
+        Err(e) => outln!(console, "{}", e),
+    }
+}
+
+// edit_notes replaces a task's notes with lines read up to a line holding a single '.'
+fn edit_notes(console: &mut Console, todo_list: &mut TodoList) {
+    let id = match read_task_id(console) {
+        Some(id) => id,
+        None => return,
+    };
+    match todo_list.get_task(id) {
+        Some(task) if !task.notes.is_empty() => outln!(console, "Current notes:\n{}", task.notes),
+        Some(_) => {}
+        None => {
+            outln!(console, "Task not found.");
+            return;
+        }
+    }
+
+    outln!(
+        console,
+        "Enter notes, finishing with a line containing only '.' (enter '.' alone to clear):"
+    );
+    let mut notes = Vec::new();
+    loop {
+        let mut line = String::new();
+        // The end of the input finishes the notes as a '.' line does
+        if console.read_line(&mut line).unwrap_or(0) == 0 || line.trim() == "." {
+            break;
+        }
+        notes.push(line.trim_end_matches(['\r', '\n']).to_string());
+    }
+    match todo_list.set_notes(id, &notes.join("\n")) {
+        Ok(()) => outln!(console, "Notes saved."),
+        Err(e) => outln!(console, "{}", e),
+    }
+}
+
+// edit_checklist shows a task's checklist and toggles, adds or removes items until a blank line
+fn edit_checklist(console: &mut Console, todo_list: &mut TodoList) {
+    let id = match read_task_id(console) {
+        Some(id) => id,
+        None => return,
+    };
+    loop {
+        let Some(task) = todo_list.get_task(id) else {
+            outln!(console, "Task not found.");
+            return;
+        };
+        print_checklist(console, task);
+
+        out!(console, "Enter an item number to check or uncheck it, 'add <text>', 'rm <number>', or leave blank to finish: ");
+        console.flush().unwrap();
+        let mut input = String::new();
+        console.read_line(&mut input).unwrap();
+        let input = input.trim();
+
+        let result = if input.is_empty() {
+            return;
+        } else if let Some(text) = input.strip_prefix("add ") {
+            todo_list.add_checklist_item(id, text).map(|_| ())
+        } else if let Some(number) = input.strip_prefix("rm ") {
+            match number.trim().parse() {
+                Ok(number) => todo_list.remove_checklist_item(id, number).map(|_| ()),
+                Err(_) => {
+                    outln!(console, "Invalid item number.");
+                    continue;
+                }
+            }
+        } else {
+            match input.parse() {
+                Ok(number) => todo_list.toggle_checklist_item(id, number).map(|_| ()),
+                Err(_) => {
+                    outln!(console, "Invalid item number.");
+                    continue;
+                }
+            }
+        };
+        if let Err(e) = result {
+            outln!(console, "{}", e);
+        }
+    }
+}
+
+fn print_checklist(console: &mut Console, task: &Task) {
+    match task.checklist_percent() {
+        Some(percent) => outln!(console, "Checklist ({}% complete):", percent),
+        None => outln!(console, "Checklist is empty."),
+    }
+    for (index, item) in task.checklist.iter().enumerate() {
+        let mark = if item.checked { "[x]" } else { "[ ]" };
+        outln!(console, "  {}. {} {}", index + 1, mark, item.text);
+    }
+}
+
+fn edit_task(console: &mut Console, todo_list: &mut TodoList) {
+    let id = match read_task_id(console) {
+        Some(id) => id,
+        None => return,
+    };
+    match edit_task_in_editor(todo_list, id, &default_editor()) {
+        Ok(true) => outln!(console, "Task id {} updated.", id),
+        Ok(false) => outln!(console, "No changes made."),
+        Err(e) => outln!(console, "{}", e),
+    }
+}
+
+// edit_task_in_editor opens a task as a TaskDocument in `editor` and applies the saved
+// document. A document that does not parse is reopened with the error noted above the
+// offending line; saving an empty document cancels. Returns whether the task changed
+pub fn edit_task_in_editor(
+    todo_list: &mut TodoList,
+    id: u32,
+    editor: &str,
+) -> Result<bool, TodoError> {
+    let task = todo_list.get_task(id).ok_or(TodoError::TaskNotFound)?;
+    let original = TaskDocument::from_task(task);
+    let file = TempFile::create(&format!("todo-task-{}", id))?;
+    let mut text = format!(
+        "{} Editing task {}. Lines starting with '{}' are ignored outside the notes.\n\
+         {} Leave a field empty to clear it, or delete everything to cancel.\n{}",
+        COMMENT_PREFIX, id, COMMENT_PREFIX, COMMENT_PREFIX, original
+    );
+    // annotation is the error line added to the document last time, which is dropped from
+    // the edited document. Lines the user wrote are kept, whatever they look like
+    let mut annotation: Option<String> = None;
+
+    loop {
+        fs::write(&file.path, &text)?;
+        run_editor(editor, &file.path)?;
+        let edited = fs::read_to_string(&file.path)?;
+        let mut lines: Vec<&str> = edited.lines().collect();
+        if let Some(annotation) = annotation.take() {
+            if let Some(at) = lines.iter().position(|line| *line == annotation) {
+                lines.remove(at);
+            }
+        }
+        text = lines.join("\n");
+        let blank = text.lines().all(|line| {
+            let line = line.trim();
+            line.is_empty() || line.starts_with(COMMENT_PREFIX)
+        });
+        if blank {
+            return Ok(false);
+        }
+        match TaskDocument::parse(&text) {
+            Ok(document) if document == original => return Ok(false),
+            Ok(document) => return document.apply(todo_list, id).map(|_| true),
+            Err(TodoError::InvalidDocument { line, message }) => {
+                let mut lines: Vec<&str> = text.lines().collect();
+                let error = format!("{}{}", ERROR_MARKER, message);
+                let at = line.map_or(0, |line| line.saturating_sub(1).min(lines.len()));
+                lines.insert(at, &error);
+                text = lines.join("\n");
+                annotation = Some(error);
+            }
+            Err(e) => return Err(e),
+        }
+    }
+}
+
+// TempFile is a new file in the temporary directory that is removed when dropped. Its name
+// ends in a suffix that is hard to guess, and creating it fails rather than open a file
+// that is already there
+struct TempFile {
+    path: PathBuf,
+}
+
+impl TempFile {
+    fn create(prefix: &str) -> io::Result<TempFile> {
+        let mut attempt: u32 = 0;
+        loop {
+            let nanos = std::time::SystemTime::now()
+                .duration_since(std::time::UNIX_EPOCH)
+                .map_or(0, |elapsed| elapsed.subsec_nanos());
+            let path = env::temp_dir().join(format!(
+                "{}-{}-{:08x}{:04x}.txt",
+                prefix,
+                process::id(),
+                nanos,
+                attempt
+            ));
+            match OpenOptions::new().write(true).create_new(true).open(&path) {
+                Ok(_) => return Ok(TempFile { path }),
+                Err(e) if e.kind() == io::ErrorKind::AlreadyExists && attempt < 100 => attempt += 1,
+                Err(e) => return Err(e),
+            }
+        }
+    }
+}
+
+impl Drop for TempFile {
+    fn drop(&mut self) {
+        let _ = fs::remove_file(&self.path);
+    }
+}
+
+// default_editor is the editor named by $VISUAL or $EDITOR, or FALLBACK_EDITOR
+pub fn default_editor() -> String {
+    EDITOR_VARS
+        .iter()
+        .filter_map(|var| env::var(var).ok())
+        .find(|editor| !editor.trim().is_empty())
+        .unwrap_or_else(|| FALLBACK_EDITOR.to_string())
+}
+
+// run_editor opens a file in an editor and waits for it to close. The editor may come with
+// arguments, as in `code --wait`
+fn run_editor(editor: &str, path: &Path) -> io::Result<()> {
+    let mut words = editor.split_whitespace();
+    let program = words.next().unwrap_or(FALLBACK_EDITOR);
+
+    let status = process::Command::new(program)
+        .args(words)
+        .arg(path)
+        .status()?;
+    if !status.success() {
+        return Err(io::Error::other(format!(
+            "editor {} exited with {}",
+            program, status
+        )));
+    }
+    Ok(())
+}
+
+fn start_timer(console: &mut Console, todo_list: &mut TodoList) {
+    let id = match read_task_id(console) {
+        Some(id) => id,
+        None => return,
+    };
+    match todo_list.start_timer(id) {
+        Ok(()) => outln!(console, "Timer started on task id {}.", id),
+        Err(e) => outln!(console, "{}", e),
+    }
+}
+
+fn stop_timer(console: &mut Console, todo_list: &mut TodoList) {
+    match todo_list.stop_timer() {
+        Ok((id, duration)) => outln!(
+            console,
+            "Timer stopped on task id {} after {}.",
+            id,
+            format_duration(duration)
+        ),
+        Err(e) => outln!(console, "{}", e),
+    }
+}
+
+// time_report prints the time tracked by task, category or day over an optional date range,
+// or writes it to a CSV file
+fn time_report(console: &mut Console, todo_list: &TodoList) {
+    out!(
+        console,
+        "Group by (task/category/day, leave blank for task): "
+    );
+    console.flush().unwrap();
+    let mut group = String::new();
+    console.read_line(&mut group).unwrap();
+    let group = if group.trim().is_empty() {
+        ReportGroup::Task
+    } else {
+        match group.parse() {
+            Ok(group) => group,
+            Err(e) => {
+                outln!(console, "{}", e);
+                return;
+            }
+        }
+    };
+
+    let mut date = |prompt| match read_date(console, prompt, false) {
+        DateInput::Date(date) => Some(date),
+        DateInput::Blank | DateInput::Clear => None,
+    };
+    let from = date("From date (leave blank for the beginning): ");
+    let to = date("To date (leave blank for today): ");
+    let report = TimeReport::new(todo_list, group, from, to, chrono::Utc::now());
+
+    out!(
+        console,
+        "Export to CSV file (leave blank to print the report): "
+    );
+    console.flush().unwrap();
+    let mut filename = String::new();
+    console.read_line(&mut filename).unwrap();
+    let filename = filename.trim();
+    if filename.is_empty() {
+        outln!(console, "{}", report);
+    } else {
+        match fs::write(filename, report.to_csv()) {
+            Ok(()) => outln!(console, "Report written to {}.", filename),
+            Err(e) => outln!(console, "Error writing report: {}", e),
+        }
     }
 }
 
-fn list_categories(todo_list: &TodoList) {
-    let categories = todo_list.get_categories();
-    for category in categories {
-        println!("{}", category);
+fn list_tags(console: &mut Console, todo_list: &TodoList) {
+    let counts = todo_list.tag_counts();
+    if counts.is_empty() {
+        outln!(console, "No tags.");
+    }
+    for (tag, count) in counts {
+        outln!(console, "+{} ({})", tag, count);
     }
 }
 
-fn get_task(todo_list: &TodoList) {
-    print!("Enter task ID: ");
-    io::stdout().flush().unwrap();
+fn rename_tag(console: &mut Console, todo_list: &mut TodoList) {
+    out!(console, "Enter tag to rename: ");
+    console.flush().unwrap();
+    let mut old = String::new();
+    console.read_line(&mut old).unwrap();
+
+    out!(console, "Enter new tag name: ");
+    console.flush().unwrap();
+    let mut new = String::new();
+    console.read_line(&mut new).unwrap();
+
+    match todo_list.rename_tag(&old, &new) {
+        Ok(count) => outln!(console, "Renamed tag on {} tasks.", count),
+        Err(e) => outln!(console, "{}", e),
+    }
+}
+
+// show_history lists the changes that can be undone and redone, then asks whether the
+// history should be kept in the save file
+fn show_history(console: &mut Console, todo_list: &mut TodoList) {
+    let history = todo_list.history();
+    outln!(
+        console,
+        "Undo (most recent first, keeping {}):",
+        history.limit()
+    );
+    for change in history.undo_changes() {
+        outln!(console, "  {}", change);
+    }
+    outln!(console, "Redo:");
+    for change in history.redo_changes() {
+        outln!(console, "  {}", change);
+    }
+
+    let current = if history.is_persisted() { "y" } else { "n" };
+    out!(
+        console,
+        "Keep the history in the save file? (y/n, leave blank to keep {}): ",
+        current
+    );
+    console.flush().unwrap();
+    let mut answer = String::new();
+    console.read_line(&mut answer).unwrap();
+    match answer.trim().to_lowercase().as_str() {
+        "y" => todo_list.history_mut().set_persisted(true),
+        "n" => todo_list.history_mut().set_persisted(false),
+        _ => {}
+    }
+}
+
+fn get_task(console: &mut Console, todo_list: &TodoList) {
+    out!(console, "Enter task ID: ");
+    console.flush().unwrap();
     let mut id = String::new();
-    io::stdin().read_line(&mut id).unwrap();
+    console.read_line(&mut id).unwrap();
     let id: u32 = id.trim().parse().unwrap();
 
     if let Some(task) = todo_list.get_task(id) {
-        print_task_details(task)
+        print_task_details(console, todo_list, task)
     } else {
-        println!("Task not found.");
+        outln!(console, "Task not found.");
     }
 }
 
-fn print_task_details(task: &Task) {
-    println!("Task details:");
-    println!("ID: {}", task.id);
-    println!("Description: {}", task.description);
+// format_tags renders a task's tags as space-separated `+tag` words
+pub fn format_tags(task: &Task) -> String {
+    let tags: Vec<String> = task.tags.iter().map(|tag| format!("+{}", tag)).collect();
+    tags.join(" ")
+}
+
+fn print_task_details(console: &mut Console, todo_list: &TodoList, task: &Task) {
+    outln!(console, "Task details:");
+    outln!(console, "ID: {}", task.id);
+    outln!(console, "Description: {}", task.description);
 
-    let due_date_str = match &task.due_date {
-        DueDate::On(d) | DueDate::Before(d) => d.format("%Y-%m-%d").to_string(),
-        DueDate::None => "None".to_string(),
-    };
-    println!("Due Date: {}", due_date_str);
+    let today = chrono::Local::now().date_naive();
+    if task.status != Status::Completed && task.due_date.is_overdue(today) {
+        outln!(console, "Due Date: {} (overdue)", task.due_date);
+    } else {
+        outln!(console, "Due Date: {}", task.due_date);
+    }
 
-    println!("Category: {}", task.category);
+    outln!(console, "Category: {}", task.category);
+    if !task.tags.is_empty() {
+        outln!(console, "Tags: {}", format_tags(task));
+    }
+    outln!(console, "Priority: {}", task.priority);
+    outln!(console, "Status: {}", task.status);
+    if let Some(parent) = task.parent {
+        outln!(console, "Parent: {}", parent);
+    }
+    let progress = todo_list.progress(task.id);
+    if progress.total > 0 {
+        outln!(console, "Progress: {}", progress);
+    }
+    let blockers = todo_list.open_blockers(task.id);
+    if !blockers.is_empty() {
+        let ids: Vec<String> = blockers.iter().map(|task| task.id.to_string()).collect();
+        outln!(console, "Blocked by: {}", ids.join(", "));
+    }
+    if let Some(created_at) = task.created_at {
+        outln!(
+            console,
+            "Created: {}",
+            created_at
+                .with_timezone(&chrono::Local)
+                .format("%Y-%m-%d %H:%M")
+        );
+    }
+    if let Some(recurrence) = &task.recurrence {
+        outln!(console, "Repeats: {}", recurrence);
+    }
+    if !task.time_entries.is_empty() {
+        let tracked = format_duration(tracked_time(task, chrono::Utc::now()));
+        match todo_list.running_timer() {
+            Some((id, started_at)) if id == task.id => outln!(
+                console,
+                "Time tracked: {} (timer running since {})",
+                tracked,
+                started_at.with_timezone(&chrono::Local).format("%H:%M")
+            ),
+            _ => outln!(console, "Time tracked: {}", tracked),
+        }
+    }
+    if let Some(completed_at) = task.completed_at {
+        outln!(
+            console,
+            "Completed: {}",
+            completed_at
+                .with_timezone(&chrono::Local)
+                .format("%Y-%m-%d %H:%M")
+        );
+    }
+    if !task.checklist.is_empty() {
+        print_checklist(console, task);
+    }
+    if !task.notes.is_empty() {
+        outln!(console, "Notes:");
+        for line in task.notes.lines() {
+            outln!(console, "  {}", line);
+        }
+    }
+}
+
+// save_list saves the list where it came from, or asks for a file when it has none yet
+fn save_list(
+    console: &mut Console,
+    todo_list: &TodoList,
+    filename_tracker: &mut FilenameTracker,
+    session: &mut Session,
+) -> io::Result<()> {
+    if session.storage.is_none() {
+        return save_list_as(console, todo_list, filename_tracker, session);
+    }
+    save_to_storage(console, todo_list, filename_tracker, session).map(|_| ())
 }
 
-// save_list to local file storage
-fn save_list(todo_list: &TodoList, filename_tracker: &mut FilenameTracker) -> io::Result<()> {
+// save_list_as saves the list to a file the user picks, which becomes the list's file
+fn save_list_as(
+    console: &mut Console,
+    todo_list: &TodoList,
+    filename_tracker: &mut FilenameTracker,
+    session: &mut Session,
+) -> io::Result<()> {
     let filenames: Vec<_> = filename_tracker.list().iter().cloned().collect();
 
-    if filename_tracker.tracking_enabled && !filenames.is_empty() {
-        match select_file(&filenames, "Select a file to save to:") {
-            Ok(Some(filename)) => return save_to_file(todo_list, &filename, filename_tracker),
+    if filename_tracker.tracking_enabled && !filenames.is_empty() && console.interactive {
+        match select_file(console, &filenames, "Select a file to save to:") {
+            Ok(Some(filename)) => {
+                return save_to_file(console, todo_list, &filename, filename_tracker, session)
+            }
             Ok(None) => {} // User chose to enter a new filename
             Err(e) => return Err(e),
         }
     }
 
     // If tracking is disabled, the list is empty, or user chose to enter a new filename
-    print!("Enter filename to save: ");
-    io::stdout().flush()?;
+    out!(console, "Enter filename to save: ");
+    console.flush()?;
     let mut filename = String::new();
-    io::stdin().read_line(&mut filename)?;
+    console.read_line(&mut filename)?;
     let filename = filename.trim();
 
-    save_to_file(todo_list, filename, filename_tracker)
+    save_to_file(console, todo_list, filename, filename_tracker, session)
 }
 
-// load_list from local file storage
-fn load_list(filename_tracker: &FilenameTracker) -> Result<TodoList, TodoError> {
+// load_list from local file storage, locking the file unless the list is already open
+fn load_list(
+    console: &mut Console,
+    filename_tracker: &FilenameTracker,
+    session: &mut Session,
+) -> Result<TodoList, TodoError> {
     let filenames: Vec<_> = filename_tracker.list().iter().cloned().collect();
 
-    if filename_tracker.tracking_enabled && !filenames.is_empty() {
-        match select_file(&filenames, "Select a file to load:") {
-            Ok(Some(filename)) => return TodoList::load_from_file(&filename),
+    if filename_tracker.tracking_enabled && !filenames.is_empty() && console.interactive {
+        match select_file(console, &filenames, "Select a file to load:") {
+            Ok(Some(filename)) => return load_from_file(&filename, session),
             Ok(None) => {} // User chose to enter a new filename
             Err(e) => return Err(TodoError::Io(e)),
         }
     }
 
     // If tracking is disabled, the list is empty, or user chose to enter a new filename
-    print!("Enter filename to load: ");
-    io::stdout().flush().unwrap();
+    out!(console, "Enter filename to load: ");
+    console.flush().unwrap();
     let mut filename = String::new();
-    io::stdin().read_line(&mut filename).unwrap();
+    console.read_line(&mut filename).unwrap();
     let filename = filename.trim();
+    if filename.is_empty() {
+        return Err(TodoError::Io(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            "no filename given",
+        )));
+    }
+
+    load_from_file(filename, session)
+}
 
-    TodoList::load_from_file(filename)
+// load_from_file loads a list and makes its file the session's list file
+fn load_from_file(filename: &str, session: &mut Session) -> Result<TodoList, TodoError> {
+    if !Path::new(filename).exists() {
+        return Err(TodoError::Io(io::Error::new(
+            io::ErrorKind::NotFound,
+            format!("{} does not exist", filename),
+        )));
+    }
+    let todo_list = match session.storage.as_mut() {
+        Some(storage) if is_open(storage.as_ref(), filename) => storage.load()?,
+        _ => {
+            let mut storage = storage::open_file(filename, false, DEFAULT_BACKUPS)?;
+            let todo_list = storage.load()?;
+            session.storage = Some(storage);
+            todo_list
+        }
+    };
+    session.dirty = false;
+    session.last_saved = Instant::now();
+    Ok(todo_list)
 }
 
-fn quit() {
-    println!("Goodbye!");
+// is_open reports whether `storage` keeps the list in `filename`
+fn is_open(storage: &dyn Storage, filename: &str) -> bool {
+    storage
+        .path()
+        .is_some_and(|path| fileio::same_file(path, filename))
 }
 
-// select_file prompts the user to select a file from a list of filenames
-fn select_file(filenames: &[String], prompt: &str) -> io::Result<Option<String>> {
+fn quit(console: &mut Console) {
+    outln!(console, "Goodbye!");
+}
+
+// select_file prompts the user to select a file from a list of filenames. It reads the arrow
+// keys from the terminal itself, so it is only used when the console is interactive
+fn select_file(
+    console: &mut Console,
+    filenames: &[String],
+    prompt: &str,
+) -> io::Result<Option<String>> {
     let mut selected = 0;
-    let mut stdout = stdout();
 
-    println!("{}", prompt);
+    outln!(console, "{}", prompt);
     for filename in filenames.iter() {
-        println!("  {}", filename);
+        outln!(console, "  {}", filename);
     }
-    println!("  Enter new filename");
+    outln!(console, "  Enter new filename");
 
     loop {
         // Move cursor to the start of the list
-        stdout.execute(MoveUp(filenames.len() as u16 + 1))?;
+        console.execute(MoveUp(filenames.len() as u16 + 1))?;
 
         // Redraw the list with the current selection
         for (i, filename) in filenames.iter().enumerate() {
-            stdout.execute(Clear(ClearType::CurrentLine))?;
+            console.execute(Clear(ClearType::CurrentLine))?;
             if i == selected {
-                println!("> {}", filename);
+                outln!(console, "> {}", filename);
             } else {
-                println!("  {}", filename);
+                outln!(console, "  {}", filename);
             }
         }
-        stdout.execute(Clear(ClearType::CurrentLine))?;
-        println!(
+        console.execute(Clear(ClearType::CurrentLine))?;
+        outln!(
+            console,
             "{}",
             if selected == filenames.len() {
                 "> Enter new filename"
@@ -375,50 +1849,46 @@ fn select_file(filenames: &[String], prompt: &str) -> io::Result<Option<String>>
             }
         );
 
-        stdout.flush()?;
+        console.flush()?;
 
         // Handle key events
         if let Event::Key(event) = event::read()? {
             match event.code {
                 KeyCode::Up => {
-                    if selected > 0 {
-                        selected -= 1;
-                    }
+                    selected = selected.saturating_sub(1);
                 }
-                KeyCode::Down => {
-                    if selected < filenames.len() {
-                        selected += 1;
-                    }
+                KeyCode::Down if selected < filenames.len() => {
+                    selected += 1;
                 }
                 KeyCode::Enter => {
                     // Clear the selection UI
-                    stdout.execute(MoveUp(filenames.len() as u16 + 1))?;
+                    console.execute(MoveUp(filenames.len() as u16 + 1))?;
                     for _ in 0..=filenames.len() {
-                        stdout.execute(Clear(ClearType::CurrentLine))?;
-                        println!();
+                        console.execute(Clear(ClearType::CurrentLine))?;
+                        outln!(console);
                     }
-                    stdout.execute(MoveUp(filenames.len() as u16 + 2))?;
-                    stdout.execute(Clear(ClearType::CurrentLine))?;
-                    println!("{}", prompt);
+                    console.execute(MoveUp(filenames.len() as u16 + 2))?;
+                    console.execute(Clear(ClearType::CurrentLine))?;
+                    outln!(console, "{}", prompt);
 
                     return if selected < filenames.len() {
-                        println!("Selected: {}", filenames[selected]);
+                        outln!(console, "Selected: {}", filenames[selected]);
                         Ok(Some(filenames[selected].clone()))
                     } else {
-                        println!("Enter new filename:");
+                        outln!(console, "Enter new filename:");
                         Ok(None)
                     };
                 }
                 KeyCode::Esc => {
                     // Clear the selection UI
-                    stdout.execute(MoveUp(filenames.len() as u16 + 1))?;
+                    console.execute(MoveUp(filenames.len() as u16 + 1))?;
                     for _ in 0..=filenames.len() {
-                        stdout.execute(Clear(ClearType::CurrentLine))?;
-                        println!();
+                        console.execute(Clear(ClearType::CurrentLine))?;
+                        outln!(console);
                     }
-                    stdout.execute(MoveUp(filenames.len() as u16 + 2))?;
-                    stdout.execute(Clear(ClearType::CurrentLine))?;
-                    println!("Operation cancelled.");
+                    console.execute(MoveUp(filenames.len() as u16 + 2))?;
+                    console.execute(Clear(ClearType::CurrentLine))?;
+                    outln!(console, "Operation cancelled.");
                     return Ok(None);
                 }
                 _ => {}
@@ -427,21 +1897,242 @@ fn select_file(filenames: &[String], prompt: &str) -> io::Result<Option<String>>
     }
 }
 
-// save_to_file saves the todo list to a file
+// save_to_file saves the todo list to a file, which becomes the session's list file
 fn save_to_file(
+    console: &mut Console,
     todo_list: &TodoList,
     filename: &str,
     filename_tracker: &mut FilenameTracker,
+    session: &mut Session,
 ) -> io::Result<()> {
-    match todo_list.save_to_file(filename) {
-        Ok(_) => {
-            println!("Todo list saved successfully to {}.", filename);
-            filename_tracker.add(filename)?;
-            Ok(())
+    let filename = filename.trim();
+    if filename.is_empty() {
+        outln!(console, "No filename given; the list was not saved.");
+        return Ok(());
+    }
+    if let Some(storage) = &session.storage {
+        if is_open(storage.as_ref(), filename) {
+            return save_to_storage(console, todo_list, filename_tracker, session).map(|_| ());
         }
+    }
+    let storage = match storage::open_file(filename, false, DEFAULT_BACKUPS) {
+        Ok(storage) => storage,
         Err(e) => {
-            println!("Failed to save todo list: {}", e);
-            Err(io::Error::new(io::ErrorKind::Other, e))
+            outln!(console, "{}", e);
+            return Ok(());
+        }
+    };
+    let previous = session.storage.replace(storage);
+    if !save_to_storage(console, todo_list, filename_tracker, session)? {
+        session.storage = previous;
+    }
+    Ok(())
+}
+
+// save_to_storage saves the todo list where the session keeps it, reporting whether it did. A
+// failed save is reported and leaves the changes unsaved rather than ending the shell
+fn save_to_storage(
+    console: &mut Console,
+    todo_list: &TodoList,
+    filename_tracker: &mut FilenameTracker,
+    session: &mut Session,
+) -> io::Result<bool> {
+    let Some(storage) = session.storage.as_mut() else {
+        return Ok(false);
+    };
+    match storage.save(todo_list) {
+        Ok(()) => {
+            session.dirty = false;
+            session.last_saved = Instant::now();
+            outln!(
+                console,
+                "Todo list saved successfully to {}.",
+                storage.describe()
+            );
+            if let Some(path) = storage.path() {
+                filename_tracker.add(&path.to_string_lossy())?;
+            }
+            Ok(true)
+        }
+        Err(e) => {
+            outln!(console, "Failed to save todo list: {}", e);
+            Ok(false)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::storage::Memory;
+
+    fn tracker() -> FilenameTracker {
+        FilenameTracker {
+            filenames: HashSet::new(),
+            tracking_enabled: false,
         }
     }
+
+    fn changed_list() -> TodoList {
+        let mut todo_list = TodoList::new();
+        todo_list.add_task(NewTask {
+            description: "Water plants".to_string(),
+            due_date: DueDate::None,
+            category: DEFAULT_CATEGORY.to_string(),
+            priority: Priority::Low,
+        });
+        todo_list
+    }
+
+    #[test]
+    fn test_autosave_due() {
+        let mut session = Session::new(Some(Box::new(Memory::default())), None);
+        session.autosave = Autosave::OnChange;
+        assert!(!session.autosave_due());
+        session.dirty = true;
+        assert!(session.autosave_due());
+
+        session.autosave = Autosave::Off;
+        assert!(!session.autosave_due());
+
+        session.autosave = Autosave::Every(Duration::from_secs(60));
+        assert!(!session.autosave_due());
+        session.last_saved = Instant::now() - Duration::from_secs(120);
+        assert!(session.autosave_due());
+
+        // There is nowhere to save a list that was never saved to a file
+        session.storage = None;
+        assert!(!session.autosave_due());
+    }
+
+    #[test]
+    fn test_save_on_exit() {
+        let todo_list = changed_list();
+        let mut input: &[u8] = b"";
+        let mut output = Vec::new();
+        let console = &mut Console {
+            input: &mut input,
+            output: &mut output,
+            interactive: false,
+        };
+        let mut session = Session::new(Some(Box::new(Memory::default())), None);
+        save_on_exit(console, &todo_list, &mut tracker(), &mut session).unwrap();
+        let storage = session.storage.as_mut().unwrap();
+        assert!(storage.load().unwrap().list_tasks(None).is_empty());
+
+        session.dirty = true;
+        save_on_exit(console, &todo_list, &mut tracker(), &mut session).unwrap();
+        assert!(!session.dirty);
+        let storage = session.storage.as_mut().unwrap();
+        assert_eq!(storage.load().unwrap(), todo_list);
+
+        let mut session = Session::new(None, None);
+        session.dirty = true;
+        save_on_exit(console, &todo_list, &mut tracker(), &mut session).unwrap();
+        assert!(session.dirty);
+        let output = String::from_utf8(output).unwrap();
+        assert!(output.contains("Todo list saved successfully to memory."));
+        assert!(output.contains("The list was never saved to a file, so its changes are lost."));
+    }
+
+    #[test]
+    fn test_shell_adds_saves_and_quits() {
+        // Tracking off, then an add answering description, due date, category, priority and
+        // parent, followed by save and quit
+        let script = "n\nadd\nSet thermostat to -5 +home\n\nHome\nhigh\n\nsave\nquit\n";
+        let mut todo_list = TodoList::new();
+        let mut output = Vec::new();
+        run_cli(
+            &mut todo_list,
+            Some(Box::new(Memory::default())),
+            None,
+            &mut script.as_bytes(),
+            &mut output,
+            false,
+        )
+        .unwrap();
+
+        let task = todo_list.get_task(1).unwrap();
+        assert_eq!(task.description, "Set thermostat to -5");
+        assert!(task.tags.contains("home"));
+        assert_eq!(task.category, "Home");
+        assert_eq!(task.priority, Priority::High);
+        let output = String::from_utf8(output).unwrap();
+        assert!(output.contains("Opened memory."));
+        assert!(output.contains("Task added with ID 1."));
+        assert!(output.contains("Todo list saved successfully to memory."));
+        assert!(!output.contains("unsaved changes"));
+        assert!(output.ends_with("Goodbye!\n"));
+    }
+
+    #[test]
+    fn test_notes_are_read_from_the_console() {
+        // Tracking off, an add, then notes for task 1 ending with '.', and quit without saving
+        let script = "n\nadd\nTask\n\n\n\n\nnotes\n1\nfirst line\n  second line\n.\nquit\nn\n";
+        let mut todo_list = TodoList::new();
+        let mut output = Vec::new();
+        run_cli(
+            &mut todo_list,
+            None,
+            None,
+            &mut script.as_bytes(),
+            &mut output,
+            false,
+        )
+        .unwrap();
+
+        assert_eq!(
+            todo_list.get_task(1).unwrap().notes,
+            "first line\n  second line"
+        );
+        let output = String::from_utf8(output).unwrap();
+        assert!(output.contains("Notes saved."));
+        assert!(output.ends_with("Goodbye!\n"));
+    }
+
+    #[test]
+    fn test_save_as_blank_filename_cancels() {
+        // Tracking off, an add, then save_as with a blank filename, and quit without saving
+        let script = "n\nadd\nTask\n\n\n\n\nsave_as\n  \nquit\nn\n";
+        let mut todo_list = TodoList::new();
+        let mut output = Vec::new();
+        run_cli(
+            &mut todo_list,
+            None,
+            None,
+            &mut script.as_bytes(),
+            &mut output,
+            false,
+        )
+        .unwrap();
+
+        let output = String::from_utf8(output).unwrap();
+        assert!(output.contains("No filename given; the list was not saved."));
+        assert!(output.contains("The list has unsaved changes."));
+        assert!(!Path::new(".tmp").exists() && !Path::new(".lock").exists());
+    }
+
+    #[test]
+    fn test_is_open_ignores_path_spelling() {
+        let storage = storage::JsonFile::new("todo.json", 0);
+        assert!(is_open(&storage, "todo.json"));
+        assert!(is_open(&storage, "./todo.json"));
+        assert!(!is_open(&storage, "other.json"));
+        assert!(!is_open(&Memory::default(), "todo.json"));
+    }
+
+    #[test]
+    fn test_split_tags_keeps_dashed_words() {
+        let (description, added, removed) =
+            split_tags("Set thermostat to -5 +home", &BTreeSet::new());
+        assert_eq!(description, "Set thermostat to -5");
+        assert_eq!(added, ["home"]);
+        assert!(removed.is_empty());
+
+        let tags = BTreeSet::from(["home".to_string()]);
+        let (description, added, removed) = split_tags("Cool to -5 -Home -work", &tags);
+        assert_eq!(description, "Cool to -5 -work");
+        assert!(added.is_empty());
+        assert_eq!(removed, ["Home"]);
+    }
 }
@@ -0,0 +1,778 @@
+use crate::cli::{self, DEFAULT_CATEGORY};
+use crate::dates::DateParser;
+use crate::fileio::DEFAULT_BACKUPS;
+use crate::journal::Journal;
+use crate::patch::Patch;
+use crate::query::TaskQuery;
+use crate::recurrence::{Recurrence, RecurrenceEnd};
+use crate::sort::TaskSort;
+use crate::storage::{self, Storage};
+use crate::timelog::{format_duration, ReportGroup, TimeReport};
+use crate::todo::{
+    DueDate, DueMode, NewTask, Priority, RemoveSubtasks, Status, Task, TaskUpdate, TodoError,
+    TodoList,
+};
+use crate::workspace::Workspace;
+use chrono::{DateTime, Local, NaiveDate, NaiveDateTime, TimeZone, Utc};
+use clap::{Parser, Subcommand};
+use std::io::{self, IsTerminal};
+use std::path::Path;
+
+// DEFAULT_FILE is the list file used by one-shot commands when --file is not given
+pub const DEFAULT_FILE: &str = "todo.json";
+
+// Args are the command line arguments of the todo binary
+#[derive(Parser, Debug)]
+#[command(name = "todo", about = "Manage a TODO list", version)]
+pub struct Args {
+    /// List file read and written by one-shot commands
+    #[arg(short, long, global = true, env = "TODO_FILE", default_value = DEFAULT_FILE)]
+    pub file: String,
+
+    /// Keep the list file as a journal of changes rather than a snapshot; existing journals
+    /// are used as journals without it
+    #[arg(long, global = true, env = "TODO_JOURNAL")]
+    pub journal: bool,
+
+    /// Number of earlier versions of the list file to keep as <file>.bak.1 onwards
+    #[arg(long, global = true, env = "TODO_BACKUPS", default_value_t = DEFAULT_BACKUPS)]
+    pub backups: usize,
+
+    /// Read the list as it was at this time (RFC 3339, YYYY-MM-DD HH:MM or a date, meaning the
+    /// end of that day) from a journal; the list cannot be changed
+    #[arg(long, global = true, value_parser = parse_timestamp)]
+    pub as_of: Option<DateTime<Utc>>,
+
+    /// Directory of named lists; commands and the shell work on its current list, or the one
+    /// given with --list
+    #[arg(long, global = true, env = "TODO_WORKSPACE")]
+    pub workspace: Option<String>,
+
+    /// Name of the list in the workspace to use
+    #[arg(long, global = true, env = "TODO_LIST", requires = "workspace")]
+    pub list: Option<String>,
+
+    #[command(subcommand)]
+    pub command: Option<Command>,
+}
+
+// Command is a single one-shot operation on the list, or the interactive shell
+#[derive(Subcommand, Debug)]
+pub enum Command {
+    /// Add a new task
+    Add {
+        description: String,
+        /// Due on this date (YYYY-MM-DD, tomorrow, next fri, in 3 days, 2w, ...)
+        #[arg(long, value_parser = parse_date)]
+        due: Option<NaiveDate>,
+        /// Due no later than this date
+        #[arg(long, value_parser = parse_date, conflicts_with = "due")]
+        due_by: Option<NaiveDate>,
+        #[arg(long)]
+        category: Option<String>,
+        /// low, medium or high
+        #[arg(long, default_value = "low")]
+        priority: Priority,
+        /// Add the task as a subtask of this task
+        #[arg(long)]
+        parent: Option<u32>,
+        /// Tag the task; repeat for several tags
+        #[arg(long = "tag")]
+        tags: Vec<String>,
+    },
+    /// List tasks, optionally filtered, e.g. `cat:Work prio>=medium status:open "invoice"`
+    List {
+        /// Filter terms; terms must all match unless separated by OR
+        query: Vec<String>,
+        /// Only tasks in this category or its subcategories
+        #[arg(long)]
+        category: Option<String>,
+        /// not_started, in_progress or completed
+        #[arg(long)]
+        status: Option<Status>,
+        /// Only tasks with every one of these tags; repeat for several tags
+        #[arg(long = "tag")]
+        tags: Vec<String>,
+        /// Only tasks with at least one of these tags; repeat for several tags
+        #[arg(long = "any-tag")]
+        any_tags: Vec<String>,
+        /// Sort keys, e.g. `priority:desc,due,id` (id, due, priority, category, created,
+        /// description; add `:desc` or a leading `-` to reverse)
+        #[arg(long, default_value_t = TaskSort::default(), allow_hyphen_values = true)]
+        sort: TaskSort,
+    },
+    /// Show a single task, followed by its checklist and notes
+    Get { id: u32 },
+    /// Remove a task; its subtasks move up a level unless --cascade is given
+    #[command(alias = "remove")]
+    Rm {
+        id: u32,
+        /// Remove the task's subtasks too
+        #[arg(long)]
+        cascade: bool,
+    },
+    /// Mark a task as blocked until another task is completed
+    Block {
+        id: u32,
+        #[arg(long)]
+        by: u32,
+    },
+    /// Remove a blocking link between two tasks
+    Unblock {
+        id: u32,
+        #[arg(long)]
+        by: u32,
+    },
+    /// List unfinished tasks whose blockers are all done
+    #[command(alias = "actionable")]
+    Next,
+    /// Show all tasks as a tree of subtasks with completion progress
+    Tree,
+    /// Move a task and its subtasks under another task, or to the top level
+    Move {
+        id: u32,
+        /// New parent task; leave out to make the task top-level
+        #[arg(long)]
+        parent: Option<u32>,
+    },
+    /// Update an existing task
+    Update {
+        id: u32,
+        #[arg(long)]
+        description: Option<String>,
+        /// Due on this date (YYYY-MM-DD, tomorrow, next fri, in 3 days, 2w, ...)
+        #[arg(long, value_parser = parse_date)]
+        due: Option<NaiveDate>,
+        /// Due no later than this date
+        #[arg(long, value_parser = parse_date, conflicts_with = "due")]
+        due_by: Option<NaiveDate>,
+        /// Remove the due date
+        #[arg(long, conflicts_with_all = ["due", "due_by"])]
+        no_due: bool,
+        /// Keep the due date but change whether it is a fixed day (on) or a deadline (by)
+        #[arg(long, conflicts_with_all = ["due", "due_by", "no_due"])]
+        due_mode: Option<DueMode>,
+        #[arg(long)]
+        category: Option<String>,
+        /// low, medium or high
+        #[arg(long)]
+        priority: Option<Priority>,
+        /// Apply a JSON Merge Patch, e.g. '{"due_date": null, "priority": "high"}', or read
+        /// one from standard input with '-'
+        #[arg(long, conflicts_with_all = ["description", "due", "due_by", "no_due", "due_mode", "category", "priority"])]
+        patch: Option<String>,
+        /// Add a tag; repeat for several tags
+        #[arg(long = "tag")]
+        tags: Vec<String>,
+        /// Remove a tag; repeat for several tags
+        #[arg(long = "untag")]
+        untags: Vec<String>,
+    },
+    /// List unfinished tasks whose due date has passed
+    Overdue,
+    /// List unfinished tasks due today
+    Today,
+    /// List unfinished tasks due in the next few days
+    Upcoming {
+        #[arg(long, default_value_t = 7)]
+        days: u32,
+    },
+    /// Mark a task as in progress
+    Start { id: u32 },
+    /// Mark a task as completed
+    #[command(alias = "complete")]
+    Done { id: u32 },
+    /// Move a completed or started task back to not started
+    Reopen { id: u32 },
+    /// Start timing work on a task; only one timer runs at a time
+    Track { id: u32 },
+    /// Stop the running timer
+    Stop,
+    /// Total the time tracked by task, category or day
+    Report {
+        /// task, category or day
+        #[arg(long, default_value = "task")]
+        by: ReportGroup,
+        /// Only time tracked on or after this date
+        #[arg(long, value_parser = parse_date)]
+        from: Option<NaiveDate>,
+        /// Only time tracked on or before this date
+        #[arg(long, value_parser = parse_date)]
+        to: Option<NaiveDate>,
+        /// Print CSV with the time in decimal hours
+        #[arg(long)]
+        csv: bool,
+    },
+    /// Make a task repeat: daily, "weekly mon,thu", "monthly 15", "every 3 days" or none
+    Recur {
+        id: u32,
+        frequency: String,
+        /// Stop repeating after this date
+        #[arg(long, value_parser = parse_date, conflicts_with = "count")]
+        until: Option<NaiveDate>,
+        /// Stop after this many occurrences, this one included
+        #[arg(long)]
+        count: Option<u32>,
+    },
+    /// List all categories
+    Categories {
+        /// Show subcategories indented beneath their parents, with task counts
+        #[arg(long)]
+        tree: bool,
+    },
+    /// Rename a category on every task in it
+    RenameCategory { old: String, new: String },
+    /// Move every task in one category into another
+    MergeCategories { from: String, into: String },
+    /// Delete a category, moving its tasks to the default category
+    DeleteCategory { category: String },
+    /// Edit a task as a text document in $VISUAL or $EDITOR
+    Edit { id: u32 },
+    /// Replace a task's notes with the given text, or with standard input when left out
+    Notes { id: u32, text: Option<String> },
+    /// Add an item to a task's checklist
+    AddItem { id: u32, text: String },
+    /// Check or uncheck a checklist item, numbered from 1
+    Toggle { id: u32, item: usize },
+    /// Remove a checklist item, numbered from 1
+    RmItem { id: u32, item: usize },
+    /// List all tags with the number of tasks carrying each
+    Tags,
+    /// Rename a tag on every task, merging it into the new tag if that is already used
+    RenameTag { old: String, new: String },
+    /// Undo the last add, remove or update kept in the list's history
+    Undo,
+    /// Redo the last undone change
+    Redo,
+    /// Show the changes that can be undone and redone, or configure the history
+    History {
+        /// Keep the history in the list file so undo works across commands
+        #[arg(long, conflicts_with = "no_persist")]
+        persist: bool,
+        /// Stop keeping the history in the list file
+        #[arg(long)]
+        no_persist: bool,
+        /// Number of changes to keep, for a persisted history
+        #[arg(long)]
+        limit: Option<usize>,
+    },
+    /// Rewrite a journal as a single snapshot of the list, dropping its history, or rebuild a
+    /// database file
+    Compact,
+    /// Copy the list into a new list file, e.g. a SQLite database named <name>.db
+    Migrate { to: String },
+    /// Start the interactive shell
+    Shell,
+}
+
+type Result<T> = std::result::Result<T, TodoError>;
+
+// run executes the parsed command line against the list file
+pub fn run(mut args: Args) -> Result<()> {
+    let workspace = match &args.workspace {
+        Some(dir) => {
+            let workspace = Workspace::open(dir, args.backups)?;
+            let name = args.list.as_deref().unwrap_or(workspace.current());
+            if !workspace.contains(name) {
+                return Err(TodoError::ListNotFound(name.to_string()));
+            }
+            args.file = workspace.path(name).to_string_lossy().into_owned();
+            Some(workspace)
+        }
+        None => None,
+    };
+
+    let command = match args.command {
+        None | Some(Command::Shell) if workspace.is_some() => {
+            let mut workspace = workspace.unwrap();
+            let name = args.list.unwrap_or_else(|| workspace.current().to_string());
+            let (storage, mut todo_list) = workspace.switch(&name)?;
+            cli::run_cli(
+                &mut todo_list,
+                Some(storage),
+                Some(workspace),
+                &mut io::stdin().lock(),
+                &mut io::stdout(),
+                io::stdin().is_terminal(),
+            )?;
+            return Ok(());
+        }
+        None | Some(Command::Shell) => {
+            // The shell opens the list file if there is one, and otherwise asks where to save
+            let (storage, mut todo_list) =
+                if args.journal || args.as_of.is_some() || Path::new(&args.file).exists() {
+                    let mut storage =
+                        open_storage(&args.file, args.journal, args.as_of, args.backups)?;
+                    let todo_list = storage.load()?;
+                    (Some(storage), todo_list)
+                } else {
+                    (None, TodoList::new())
+                };
+            cli::run_cli(
+                &mut todo_list,
+                storage,
+                None,
+                &mut io::stdin().lock(),
+                &mut io::stdout(),
+                io::stdin().is_terminal(),
+            )?;
+            return Ok(());
+        }
+        Some(command) => command,
+    };
+
+    // The list file stays locked until the command is done, so no other process changes
+    // the list between reading and saving it
+    let mut storage = open_storage(&args.file, args.journal, args.as_of, args.backups)?;
+
+    // Listing asks the storage for the matching tasks, which a database answers from its
+    // indexes without loading the whole list
+    if let Command::List {
+        query,
+        category,
+        status,
+        tags,
+        any_tags,
+        sort,
+    } = command
+    {
+        let mut query = TaskQuery::parse(&query_from_args(&query))?;
+        if let Some(category) = category {
+            query = query.and(TaskQuery::Category(category));
+        }
+        if let Some(status) = status {
+            query = query.and(TaskQuery::Status(status));
+        }
+        if !tags.is_empty() {
+            query = query.and(TaskQuery::all_tags(&tags)?);
+        }
+        if !any_tags.is_empty() {
+            query = query.and(TaskQuery::any_tag(&any_tags)?);
+        }
+        let tasks = storage.find_tasks(&query)?;
+        let mut tasks: Vec<&Task> = tasks.iter().collect();
+        sort.sort(&mut tasks);
+        print_tasks(tasks);
+        return Ok(());
+    }
+    let mut todo_list = storage.load()?;
+
+    match command {
+        Command::Add {
+            description,
+            due,
+            due_by,
+            category,
+            priority,
+            parent,
+            tags,
+        } => {
+            let has_due_date = due.is_some() || due_by.is_some();
+            let new_task = NewTask {
+                description,
+                due_date: due_date_from_args(due, due_by).unwrap_or(DueDate::None),
+                category: category.unwrap_or_else(|| DEFAULT_CATEGORY.to_string()),
+                priority,
+            };
+            let id = match parent {
+                Some(parent) => todo_list.add_subtask(parent, new_task)?,
+                None => todo_list.add_task(new_task),
+            };
+            for tag in &tags {
+                todo_list.add_tag(id, tag)?;
+            }
+            storage.save(&todo_list)?;
+            println!("{}", id);
+            if has_due_date {
+                report_due_date(&todo_list, id);
+            }
+        }
+        Command::Get { id } => {
+            let task = todo_list.get_task(id).ok_or(TodoError::TaskNotFound)?;
+            println!("{}", format_task_line(task));
+            for item in &task.checklist {
+                println!("\t[{}] {}", if item.checked { "x" } else { " " }, item.text);
+            }
+            for line in task.notes.lines() {
+                println!("\t{}", line);
+            }
+        }
+        Command::Rm { id, cascade } => {
+            let subtasks = if cascade {
+                RemoveSubtasks::Cascade
+            } else {
+                RemoveSubtasks::Promote
+            };
+            let dependents = todo_list.removal_dependents(id, subtasks);
+            if !dependents.is_empty() {
+                let ids: Vec<String> = dependents.iter().map(|task| task.id.to_string()).collect();
+                eprintln!(
+                    "warning: tasks {} were blocked by task {}{}",
+                    ids.join(", "),
+                    id,
+                    if cascade { " or its subtasks" } else { "" }
+                );
+            }
+            todo_list.remove_task_with(id, subtasks)?;
+            storage.save(&todo_list)?;
+        }
+        Command::Block { id, by } => {
+            todo_list.add_dependency(id, by)?;
+            storage.save(&todo_list)?;
+        }
+        Command::Unblock { id, by } => {
+            todo_list.remove_dependency(id, by)?;
+            storage.save(&todo_list)?;
+        }
+        Command::Next => print_tasks(todo_list.actionable_tasks()),
+        Command::Tree => {
+            for (depth, task) in todo_list.task_tree() {
+                println!(
+                    "{}{}",
+                    "    ".repeat(depth),
+                    cli::format_tree_line(&todo_list, task)
+                );
+            }
+        }
+        Command::Move { id, parent } => {
+            todo_list.move_task(id, parent)?;
+            storage.save(&todo_list)?;
+        }
+        Command::Update {
+            id,
+            description,
+            due,
+            due_by,
+            no_due,
+            due_mode,
+            category,
+            priority,
+            patch,
+            tags,
+            untags,
+        } => {
+            let mut update = match patch.as_deref() {
+                Some("-") => TaskUpdate::from_merge_patch(&io::read_to_string(io::stdin())?)?,
+                Some(patch) => TaskUpdate::from_merge_patch(patch)?,
+                None => TaskUpdate {
+                    description: description.into(),
+                    category: category.into(),
+                    priority: priority.into(),
+                    ..TaskUpdate::default()
+                },
+            };
+            let has_due_date = due.is_some() || due_by.is_some();
+            if no_due {
+                update = update.with_due_date(DueDate::None);
+            } else if let Some(due_date) = due_date_from_args(due, due_by) {
+                update = update.with_due_date(due_date);
+            }
+            if let Some(due_mode) = due_mode {
+                update.due_mode = Patch::Set(due_mode);
+            }
+            todo_list.update_task(id, update)?;
+            for tag in &tags {
+                todo_list.add_tag(id, tag)?;
+            }
+            for tag in &untags {
+                todo_list.remove_tag(id, tag)?;
+            }
+            storage.save(&todo_list)?;
+            if has_due_date {
+                report_due_date(&todo_list, id);
+            }
+        }
+        Command::Overdue => print_tasks(todo_list.overdue_tasks(today())),
+        Command::Today => print_tasks(todo_list.due_today_tasks(today())),
+        Command::Upcoming { days } => print_tasks(todo_list.due_within_days(today(), days)),
+        Command::Start { id } => {
+            todo_list.start_task(id)?;
+            storage.save(&todo_list)?;
+        }
+        Command::Done { id } => {
+            if let Some(next_id) = todo_list.complete_task(id)? {
+                println!("{}", next_id);
+            }
+            storage.save(&todo_list)?;
+        }
+        Command::Reopen { id } => {
+            todo_list.reopen_task(id)?;
+            storage.save(&todo_list)?;
+        }
+        Command::Track { id } => {
+            todo_list.start_timer(id)?;
+            storage.save(&todo_list)?;
+        }
+        Command::Stop => {
+            let (id, duration) = todo_list.stop_timer()?;
+            println!("{}\t{}", id, format_duration(duration));
+            storage.save(&todo_list)?;
+        }
+        Command::Report { by, from, to, csv } => {
+            let report = TimeReport::new(&todo_list, by, from, to, Utc::now());
+            if csv {
+                print!("{}", report.to_csv());
+            } else {
+                println!("{}", report);
+            }
+        }
+        Command::Recur {
+            id,
+            frequency,
+            until,
+            count,
+        } => {
+            let recurrence = if frequency.eq_ignore_ascii_case("none") {
+                None
+            } else {
+                let end = match (until, count) {
+                    (Some(until), _) => RecurrenceEnd::Until(until),
+                    (None, Some(count)) => RecurrenceEnd::Count(count),
+                    (None, None) => RecurrenceEnd::Never,
+                };
+                Some(Recurrence {
+                    frequency: frequency.parse()?,
+                    end,
+                })
+            };
+            todo_list.set_recurrence(id, recurrence)?;
+            storage.save(&todo_list)?;
+        }
+        Command::Categories { tree: false } => {
+            for category in todo_list.get_categories() {
+                println!("{}", category);
+            }
+        }
+        Command::Categories { tree: true } => {
+            for line in cli::format_category_tree(&todo_list.category_tree()) {
+                println!("{}", line);
+            }
+        }
+        Command::RenameCategory { old, new } => {
+            todo_list.rename_category(&old, &new)?;
+            storage.save(&todo_list)?;
+        }
+        Command::MergeCategories { from, into } => {
+            todo_list.merge_categories(&from, &into)?;
+            storage.save(&todo_list)?;
+        }
+        Command::DeleteCategory { category } => {
+            todo_list.delete_category(&category)?;
+            storage.save(&todo_list)?;
+        }
+        Command::Edit { id } => {
+            if cli::edit_task_in_editor(&mut todo_list, id, &cli::default_editor())? {
+                storage.save(&todo_list)?;
+            }
+        }
+        Command::Notes { id, text } => {
+            let notes = match text {
+                Some(text) => text,
+                None => io::read_to_string(io::stdin())?,
+            };
+            todo_list.set_notes(id, &notes)?;
+            storage.save(&todo_list)?;
+        }
+        Command::AddItem { id, text } => {
+            println!("{}", todo_list.add_checklist_item(id, &text)?);
+            storage.save(&todo_list)?;
+        }
+        Command::Toggle { id, item } => {
+            todo_list.toggle_checklist_item(id, item)?;
+            storage.save(&todo_list)?;
+        }
+        Command::RmItem { id, item } => {
+            todo_list.remove_checklist_item(id, item)?;
+            storage.save(&todo_list)?;
+        }
+        Command::Tags => {
+            for (tag, count) in todo_list.tag_counts() {
+                println!("{}\t{}", tag, count);
+            }
+        }
+        Command::RenameTag { old, new } => {
+            todo_list.rename_tag(&old, &new)?;
+            storage.save(&todo_list)?;
+        }
+        Command::Undo => {
+            println!("Undid {}", todo_list.undo()?);
+            storage.save(&todo_list)?;
+        }
+        Command::Redo => {
+            println!("Redid {}", todo_list.redo()?);
+            storage.save(&todo_list)?;
+        }
+        Command::History {
+            persist,
+            no_persist,
+            limit,
+        } => {
+            if persist || no_persist || limit.is_some() {
+                let history = todo_list.history_mut();
+                if persist || no_persist {
+                    history.set_persisted(persist);
+                }
+                // A limit on a history that is not saved would be lost when the command ends
+                if limit.is_some() && history.is_transient() {
+                    return Err(TodoError::HistoryNotPersisted);
+                }
+                if let Some(limit) = limit {
+                    history.set_limit(limit);
+                }
+                storage.save(&todo_list)?;
+            } else {
+                let history = todo_list.history();
+                for change in history.undo_changes() {
+                    println!("undo\t{}", change);
+                }
+                for change in history.redo_changes() {
+                    println!("redo\t{}", change);
+                }
+            }
+        }
+        Command::Compact => storage.compact(&todo_list)?,
+        Command::Migrate { to } => {
+            if Path::new(&to).exists() {
+                return Err(TodoError::FileExists(to));
+            }
+            let mut target = storage::open_file(&to, false, args.backups)?;
+            target.save(&todo_list)?;
+            println!(
+                "Copied {} tasks to {}",
+                todo_list.list_tasks(None).len(),
+                target.describe()
+            );
+        }
+        Command::List { .. } | Command::Shell => unreachable!(),
+    }
+    Ok(())
+}
+
+// PastList is the list as it was at an earlier time, rebuilt from a journal. It cannot be
+// changed
+struct PastList {
+    filename: String,
+    as_of: DateTime<Utc>,
+    todo_list: TodoList,
+}
+
+impl Storage for PastList {
+    fn describe(&self) -> String {
+        format!("{} as of {}", self.filename, self.as_of)
+    }
+
+    fn load(&mut self) -> Result<TodoList> {
+        Ok(self.todo_list.clone())
+    }
+
+    fn save(&mut self, _todo_list: &TodoList) -> Result<()> {
+        Err(TodoError::ReadOnly)
+    }
+
+    fn upsert_task(&mut self, _task: &Task) -> Result<()> {
+        Err(TodoError::ReadOnly)
+    }
+
+    fn delete_task(&mut self, _id: u32) -> Result<()> {
+        Err(TodoError::ReadOnly)
+    }
+}
+
+// open_storage opens the list file, see storage::open_file, or the list as it was at `as_of`
+fn open_storage(
+    filename: &str,
+    use_journal: bool,
+    as_of: Option<DateTime<Utc>>,
+    backups: usize,
+) -> Result<Box<dyn Storage>> {
+    Ok(match as_of {
+        Some(as_of) => Box::new(PastList {
+            filename: filename.to_string(),
+            as_of,
+            todo_list: Journal::replay(filename, as_of)?,
+        }),
+        None => storage::open_file(filename, use_journal, backups)?,
+    })
+}
+
+// parse_timestamp parses a point in time given on the command line
+fn parse_timestamp(s: &str) -> Result<DateTime<Utc>> {
+    if let Ok(time) = DateTime::parse_from_rfc3339(s) {
+        return Ok(time.with_timezone(&Utc));
+    }
+    let local = match NaiveDateTime::parse_from_str(s, "%Y-%m-%d %H:%M") {
+        Ok(time) => time,
+        Err(_) => parse_date(s)?.and_hms_opt(23, 59, 59).unwrap(),
+    };
+    Local
+        .from_local_datetime(&local)
+        .earliest()
+        .map(|time| time.with_timezone(&Utc))
+        .ok_or_else(|| TodoError::InvalidDate(s.to_string()))
+}
+
+// parse_date parses a due date given on the command line, see DateParser
+fn parse_date(s: &str) -> Result<NaiveDate> {
+    DateParser::from_env().parse(s, today())
+}
+
+// query_from_args joins the filter words back together, re-quoting any word the shell
+// unquoted that contains spaces
+fn query_from_args(words: &[String]) -> String {
+    words
+        .iter()
+        .map(|word| {
+            if word.contains(char::is_whitespace) && !word.contains('"') {
+                format!("\"{}\"", word)
+            } else {
+                word.clone()
+            }
+        })
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+// due_date_from_args picks the due date mode from the --due and --due-by flags
+fn due_date_from_args(due: Option<NaiveDate>, due_by: Option<NaiveDate>) -> Option<DueDate> {
+    due.map(DueDate::On).or(due_by.map(DueDate::Before))
+}
+
+// report_due_date shows the date a relative --due like `next fri` resolved to. It goes to
+// stderr so that stdout stays parseable
+fn report_due_date(todo_list: &TodoList, id: u32) {
+    if let Some(task) = todo_list.get_task(id) {
+        eprintln!("Task {} is due {}", id, task.due_date);
+    }
+}
+
+fn today() -> NaiveDate {
+    chrono::Local::now().date_naive()
+}
+
+fn print_tasks(tasks: Vec<&Task>) {
+    for task in tasks {
+        println!("{}", format_task_line(task));
+    }
+}
+
+// format_task_line renders a task on a single tab-separated line for scripting, with any
+// tags after the description and the checklist completion at the end
+pub fn format_task_line(task: &Task) -> String {
+    let due_date = match &task.due_date {
+        DueDate::On(d) => d.format("%Y-%m-%d").to_string(),
+        DueDate::Before(d) => d.format("<=%Y-%m-%d").to_string(),
+        DueDate::None => "-".to_string(),
+    };
+    let mut line = format!(
+        "{}\t{}\t{}\t{}\t{}\t{}",
+        task.id, task.status, due_date, task.priority, task.category, task.description
+    );
+    if !task.tags.is_empty() {
+        line.push(' ');
+        line.push_str(&cli::format_tags(task));
+    }
+    if let Some(percent) = task.checklist_percent() {
+        line.push_str(&format!(" ({}%)", percent));
+    }
+    line
+}
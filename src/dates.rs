@@ -0,0 +1,197 @@
+use crate::todo::TodoError;
+use chrono::{Datelike, Days, Months, NaiveDate, Weekday};
+use std::env;
+
+// DATE_FORMATS_ENV names the environment variable holding extra comma-separated date formats
+pub const DATE_FORMATS_ENV: &str = "TODO_DATE_FORMATS";
+
+// DEFAULT_DATE_FORMAT is the explicit format every parser accepts
+pub const DEFAULT_DATE_FORMAT: &str = "%Y-%m-%d";
+
+type Result<T> = std::result::Result<T, TodoError>;
+
+// DateParser turns user input such as "tomorrow", "next fri", "in 3 days", "2w" or an
+// explicit date in one of its formats into a calendar date
+#[derive(Clone, Debug)]
+pub struct DateParser {
+    formats: Vec<String>,
+}
+
+impl Default for DateParser {
+    fn default() -> Self {
+        DateParser {
+            formats: vec![DEFAULT_DATE_FORMAT.to_string()],
+        }
+    }
+}
+
+impl DateParser {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    // from_env builds a parser that also accepts the formats listed in TODO_DATE_FORMATS
+    pub fn from_env() -> Self {
+        let mut parser = Self::new();
+        if let Ok(formats) = env::var(DATE_FORMATS_ENV) {
+            for format in formats.split(',').map(str::trim).filter(|f| !f.is_empty()) {
+                parser = parser.with_format(format);
+            }
+        }
+        parser
+    }
+
+    // with_format adds an explicit chrono format, tried in the order added
+    pub fn with_format(mut self, format: &str) -> Self {
+        self.formats.push(format.to_string());
+        self
+    }
+
+    // parse resolves the input relative to `today`
+    pub fn parse(&self, input: &str, today: NaiveDate) -> Result<NaiveDate> {
+        let normalized = input.trim().to_lowercase();
+        let normalized = normalized.split_whitespace().collect::<Vec<_>>().join(" ");
+
+        for format in &self.formats {
+            if let Ok(date) = NaiveDate::parse_from_str(input.trim(), format) {
+                return Ok(date);
+            }
+        }
+
+        parse_keyword(&normalized, today)
+            .or_else(|| parse_weekday_phrase(&normalized, today))
+            .or_else(|| parse_offset(&normalized, today))
+            .ok_or_else(|| TodoError::InvalidDate(input.trim().to_string()))
+    }
+}
+
+// parse_date resolves a date with the default parser, see DateParser::parse
+pub fn parse_date(input: &str, today: NaiveDate) -> Result<NaiveDate> {
+    DateParser::new().parse(input, today)
+}
+
+fn parse_keyword(input: &str, today: NaiveDate) -> Option<NaiveDate> {
+    match input {
+        "today" | "tod" => Some(today),
+        "tomorrow" | "tmr" | "tom" => today.succ_opt(),
+        "yesterday" => today.pred_opt(),
+        "end of week" | "eow" => {
+            let days_left = 6 - today.weekday().num_days_from_monday();
+            today.checked_add_days(Days::new(days_left.into()))
+        }
+        "end of month" | "eom" => end_of_month(today),
+        "end of year" | "eoy" => NaiveDate::from_ymd_opt(today.year(), 12, 31),
+        _ => None,
+    }
+}
+
+// parse_weekday_phrase handles "fri" (the coming Friday, today included) and
+// "next fri" (the first Friday after today)
+fn parse_weekday_phrase(input: &str, today: NaiveDate) -> Option<NaiveDate> {
+    let (name, skip_today) = match input.strip_prefix("next ") {
+        Some(name) => (name, true),
+        None => (input, false),
+    };
+    let weekday = name.parse::<Weekday>().ok()?;
+
+    let mut days_ahead =
+        (7 + weekday.num_days_from_monday() - today.weekday().num_days_from_monday()) % 7;
+    if days_ahead == 0 && skip_today {
+        days_ahead = 7;
+    }
+    today.checked_add_days(Days::new(days_ahead.into()))
+}
+
+// parse_offset handles "in 3 days", "in 2 weeks", "in 1 month" and the short forms
+// "3d", "2w", "1m" and "1y"
+fn parse_offset(input: &str, today: NaiveDate) -> Option<NaiveDate> {
+    let (amount, unit) = match input.strip_prefix("in ") {
+        Some(rest) => rest.split_once(' ')?,
+        None => {
+            let split = input.find(|c: char| !c.is_ascii_digit())?;
+            input.split_at(split)
+        }
+    };
+    let amount: u32 = amount.parse().ok()?;
+
+    match unit.trim() {
+        "d" | "day" | "days" => today.checked_add_days(Days::new(amount.into())),
+        "w" | "week" | "weeks" => today.checked_add_days(Days::new(u64::from(amount) * 7)),
+        "m" | "month" | "months" => today.checked_add_months(Months::new(amount)),
+        "y" | "year" | "years" => today.checked_add_months(Months::new(amount.checked_mul(12)?)),
+        _ => None,
+    }
+}
+
+fn end_of_month(today: NaiveDate) -> Option<NaiveDate> {
+    today
+        .with_day(1)?
+        .checked_add_months(Months::new(1))?
+        .pred_opt()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // 2024-05-10 is a Friday
+    fn today() -> NaiveDate {
+        NaiveDate::from_ymd_opt(2024, 5, 10).unwrap()
+    }
+
+    fn ymd(y: i32, m: u32, d: u32) -> NaiveDate {
+        NaiveDate::from_ymd_opt(y, m, d).unwrap()
+    }
+
+    #[test]
+    fn test_parse_keywords() {
+        assert_eq!(parse_date("today", today()).unwrap(), today());
+        assert_eq!(parse_date(" Tomorrow ", today()).unwrap(), ymd(2024, 5, 11));
+        assert_eq!(
+            parse_date("end of month", today()).unwrap(),
+            ymd(2024, 5, 31)
+        );
+        assert_eq!(parse_date("eow", today()).unwrap(), ymd(2024, 5, 12));
+    }
+
+    #[test]
+    fn test_parse_weekdays() {
+        assert_eq!(parse_date("fri", today()).unwrap(), today());
+        assert_eq!(parse_date("next fri", today()).unwrap(), ymd(2024, 5, 17));
+        assert_eq!(
+            parse_date("next Monday", today()).unwrap(),
+            ymd(2024, 5, 13)
+        );
+        assert_eq!(parse_date("wed", today()).unwrap(), ymd(2024, 5, 15));
+    }
+
+    #[test]
+    fn test_parse_offsets() {
+        assert_eq!(parse_date("in 3 days", today()).unwrap(), ymd(2024, 5, 13));
+        assert_eq!(parse_date("2w", today()).unwrap(), ymd(2024, 5, 24));
+        assert_eq!(parse_date("in 1 month", today()).unwrap(), ymd(2024, 6, 10));
+        assert_eq!(parse_date("1y", today()).unwrap(), ymd(2025, 5, 10));
+    }
+
+    #[test]
+    fn test_parse_explicit_formats() {
+        assert_eq!(parse_date("2026-11-01", today()).unwrap(), ymd(2026, 11, 1));
+        assert!(parse_date("01/11/2026", today()).is_err());
+
+        let parser = DateParser::new().with_format("%d/%m/%Y");
+        assert_eq!(
+            parser.parse("01/11/2026", today()).unwrap(),
+            ymd(2026, 11, 1)
+        );
+    }
+
+    #[test]
+    fn test_parse_invalid_input() {
+        for input in ["", "soon", "in days", "next blursday", "2026-13-01", "3x"] {
+            assert!(
+                matches!(parse_date(input, today()), Err(TodoError::InvalidDate(_))),
+                "{input:?} should not parse"
+            );
+        }
+    }
+}
@@ -0,0 +1,412 @@
+use crate::dates::DateParser;
+use crate::patch::Patch;
+use crate::todo::{
+    normalize_tag, ChecklistItem, DueDate, Priority, Status, Task, TaskUpdate, TodoError, TodoList,
+    DEFAULT_CATEGORY,
+};
+use chrono::{Local, NaiveDate};
+use std::collections::BTreeSet;
+use std::fmt;
+
+type Result<T> = std::result::Result<T, TodoError>;
+
+// COMMENT_PREFIX starts a line the parser ignores, outside the notes
+pub const COMMENT_PREFIX: char = '#';
+
+// TaskDocument is the editable text form of a task: one `field: value` line per field, then a
+// checklist of `[x] item` lines and the notes. Rendering a document and parsing it back gives
+// the same document
+#[derive(Clone, Debug, PartialEq)]
+pub struct TaskDocument {
+    pub description: String,
+    pub due_date: DueDate,
+    pub category: String,
+    pub priority: Priority,
+    pub status: Status,
+    pub tags: BTreeSet<String>,
+    pub checklist: Vec<ChecklistItem>,
+    pub notes: String,
+}
+
+impl TaskDocument {
+    pub fn from_task(task: &Task) -> Self {
+        TaskDocument {
+            description: task.description.clone(),
+            due_date: task.due_date.clone(),
+            category: task.category.clone(),
+            priority: task.priority,
+            status: task.status,
+            tags: task.tags.clone(),
+            checklist: task.checklist.clone(),
+            notes: task.notes.clone(),
+        }
+    }
+
+    // parse reads a document, resolving relative due dates like `next fri` against today
+    pub fn parse(text: &str) -> Result<Self> {
+        Self::parse_with(text, &DateParser::from_env(), Local::now().date_naive())
+    }
+
+    // parse_with reads a document, resolving due dates with the given parser. Errors carry the
+    // number of the line they were found on, if they concern a single line
+    pub fn parse_with(text: &str, parser: &DateParser, today: NaiveDate) -> Result<Self> {
+        let mut fields = Fields::default();
+        let mut checklist = Vec::new();
+        let mut notes: Option<Vec<&str>> = None;
+        let mut in_checklist = false;
+
+        for (index, line) in text.lines().enumerate() {
+            let invalid = |message: String| TodoError::InvalidDocument {
+                line: Some(index + 1),
+                message,
+            };
+            if let Some(notes) = notes.as_mut() {
+                notes.push(line);
+                continue;
+            }
+            let trimmed = line.trim();
+            if trimmed.is_empty() || trimmed.starts_with(COMMENT_PREFIX) {
+                continue;
+            }
+
+            let Some((field, value)) = trimmed
+                .split_once(':')
+                .filter(|_| !trimmed.starts_with('['))
+            else {
+                if !in_checklist {
+                    return Err(invalid(format!(
+                        "expected 'field: value', found '{}'",
+                        trimmed
+                    )));
+                }
+                checklist.push(parse_checklist_item(trimmed).map_err(invalid)?);
+                continue;
+            };
+            let value = value.trim();
+            match field.trim().to_lowercase().as_str() {
+                "checklist" if value.is_empty() => in_checklist = true,
+                "notes" => {
+                    notes = Some(if value.is_empty() {
+                        vec![]
+                    } else {
+                        vec![value]
+                    });
+                }
+                field if in_checklist => {
+                    return Err(invalid(format!(
+                        "field '{}' must come before the checklist",
+                        field
+                    )))
+                }
+                field => fields.set(field, value, parser, today).map_err(invalid)?,
+            }
+        }
+
+        let description = match fields.description {
+            Some(description) if !description.is_empty() => description,
+            _ => {
+                return Err(TodoError::InvalidDocument {
+                    line: None,
+                    message: "description cannot be empty".to_string(),
+                })
+            }
+        };
+        let notes = notes.unwrap_or_default().join("\n");
+        Ok(TaskDocument {
+            description,
+            due_date: fields.due_date.unwrap_or(DueDate::None),
+            category: fields
+                .category
+                .filter(|category| !category.is_empty())
+                .unwrap_or_else(|| DEFAULT_CATEGORY.to_string()),
+            priority: fields.priority.unwrap_or(Priority::Low),
+            status: fields.status.unwrap_or_default(),
+            tags: fields.tags.unwrap_or_default(),
+            checklist,
+            notes: notes.trim_matches('\n').trim_end().to_string(),
+        })
+    }
+
+    // apply writes the document back to task `id`, returning the ID of the next occurrence if
+    // marking a recurring task completed spawned one
+    pub fn apply(self, todo_list: &mut TodoList, id: u32) -> Result<Option<u32>> {
+        let current = todo_list
+            .get_task(id)
+            .ok_or(TodoError::TaskNotFound)?
+            .status;
+        todo_list.update_task(
+            id,
+            TaskUpdate {
+                description: Patch::Set(self.description),
+                category: Patch::Set(self.category),
+                priority: Patch::Set(self.priority),
+                ..TaskUpdate::default()
+            }
+            .with_due_date(self.due_date),
+        )?;
+        todo_list.set_tags(id, &self.tags)?;
+        todo_list.set_checklist(id, self.checklist)?;
+        todo_list.set_notes(id, &self.notes)?;
+
+        match self.status {
+            status if status == current => Ok(None),
+            Status::NotStarted => todo_list.reopen_task(id).map(|()| None),
+            Status::InProgress => todo_list.start_task(id).map(|()| None),
+            Status::Completed => todo_list.complete_task(id),
+        }
+    }
+}
+
+// Fields holds the `field: value` lines of a document as they are parsed
+#[derive(Default)]
+struct Fields {
+    description: Option<String>,
+    due_date: Option<DueDate>,
+    category: Option<String>,
+    priority: Option<Priority>,
+    status: Option<Status>,
+    tags: Option<BTreeSet<String>>,
+}
+
+impl Fields {
+    fn set(
+        &mut self,
+        field: &str,
+        value: &str,
+        parser: &DateParser,
+        today: NaiveDate,
+    ) -> std::result::Result<(), String> {
+        match field {
+            "description" => set_once(&mut self.description, value.to_string(), field),
+            "due" => {
+                let due_date = match value.strip_prefix("by ") {
+                    _ if value.is_empty() || value.eq_ignore_ascii_case("none") => DueDate::None,
+                    Some(date) => DueDate::Before(parse_date(date, parser, today)?),
+                    None => DueDate::On(parse_date(value, parser, today)?),
+                };
+                set_once(&mut self.due_date, due_date, field)
+            }
+            "category" => set_once(&mut self.category, value.to_string(), field),
+            "priority" => {
+                let priority = value.parse().map_err(|e: TodoError| e.to_string())?;
+                set_once(&mut self.priority, priority, field)
+            }
+            "status" => {
+                let status = value.parse().map_err(|e: TodoError| e.to_string())?;
+                set_once(&mut self.status, status, field)
+            }
+            "tags" => {
+                let tags = value
+                    .split_whitespace()
+                    .map(normalize_tag)
+                    .collect::<Result<BTreeSet<_>>>()
+                    .map_err(|e| e.to_string())?;
+                set_once(&mut self.tags, tags, field)
+            }
+            field => Err(format!(
+                "unknown field '{}' (expected description, due, category, priority, status, tags, checklist or notes)",
+                field
+            )),
+        }
+    }
+}
+
+fn set_once<T>(slot: &mut Option<T>, value: T, field: &str) -> std::result::Result<(), String> {
+    match slot.replace(value) {
+        Some(_) => Err(format!("field '{}' is given twice", field)),
+        None => Ok(()),
+    }
+}
+
+fn parse_date(
+    value: &str,
+    parser: &DateParser,
+    today: NaiveDate,
+) -> std::result::Result<NaiveDate, String> {
+    parser.parse(value, today).map_err(|e| e.to_string())
+}
+
+// parse_checklist_item reads a `[x] item` or `[ ] item` line
+fn parse_checklist_item(line: &str) -> std::result::Result<ChecklistItem, String> {
+    let checked = match line.get(..3) {
+        Some("[x]") | Some("[X]") => true,
+        Some("[ ]") => false,
+        _ => {
+            return Err(format!(
+                "expected a checklist item like '[ ] item' or '[x] item', found '{}'",
+                line
+            ))
+        }
+    };
+    let text = line[3..].trim();
+    if text.is_empty() {
+        return Err("checklist items cannot be empty".to_string());
+    }
+    Ok(ChecklistItem {
+        text: text.to_string(),
+        checked,
+    })
+}
+
+impl fmt::Display for TaskDocument {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let due_date = match &self.due_date {
+            DueDate::On(date) => date.format("%Y-%m-%d").to_string(),
+            DueDate::Before(date) => date.format("by %Y-%m-%d").to_string(),
+            DueDate::None => String::new(),
+        };
+        let tags: Vec<&str> = self.tags.iter().map(String::as_str).collect();
+
+        writeln!(f, "description: {}", self.description)?;
+        writeln!(f, "due: {}", due_date)?;
+        writeln!(f, "category: {}", self.category)?;
+        writeln!(f, "priority: {}", self.priority)?;
+        writeln!(f, "status: {}", self.status)?;
+        writeln!(f, "tags: {}", tags.join(" "))?;
+        writeln!(f)?;
+        writeln!(f, "checklist:")?;
+        for item in &self.checklist {
+            let mark = if item.checked { "[x]" } else { "[ ]" };
+            writeln!(f, "{} {}", mark, item.text)?;
+        }
+        writeln!(f)?;
+        writeln!(f, "notes:")?;
+        if !self.notes.is_empty() {
+            writeln!(f, "{}", self.notes)?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::todo::NewTask;
+
+    // 2024-05-10 is a Friday
+    fn today() -> NaiveDate {
+        NaiveDate::from_ymd_opt(2024, 5, 10).unwrap()
+    }
+
+    fn parse(text: &str) -> Result<TaskDocument> {
+        TaskDocument::parse_with(text, &DateParser::new(), today())
+    }
+
+    #[test]
+    fn test_round_trip() {
+        let document = TaskDocument {
+            description: "Pack for trip".to_string(),
+            due_date: DueDate::Before(today()),
+            category: "Home/Travel".to_string(),
+            priority: Priority::High,
+            status: Status::InProgress,
+            tags: ["travel".to_string(), "urgent".to_string()].into(),
+            checklist: vec![
+                ChecklistItem {
+                    text: "Passport".to_string(),
+                    checked: true,
+                },
+                ChecklistItem {
+                    text: "Tickets: print them".to_string(),
+                    checked: false,
+                },
+            ],
+            notes: "Flight at 9\n\n# not a comment\ncategory: not a field".to_string(),
+        };
+        assert_eq!(parse(&document.to_string()).unwrap(), document);
+
+        let empty = TaskDocument {
+            due_date: DueDate::None,
+            tags: BTreeSet::new(),
+            checklist: Vec::new(),
+            notes: String::new(),
+            ..document
+        };
+        assert_eq!(parse(&empty.to_string()).unwrap(), empty);
+    }
+
+    #[test]
+    fn test_parse_clears_and_defaults() {
+        let text = "# a comment\ndescription: Call client\ndue: next fri\ncategory:\ntags:\n";
+        let document = parse(text).unwrap();
+        assert_eq!(
+            document.due_date,
+            DueDate::On(NaiveDate::from_ymd_opt(2024, 5, 17).unwrap())
+        );
+        assert_eq!(document.category, DEFAULT_CATEGORY);
+        assert_eq!(document.priority, Priority::Low);
+        assert!(document.tags.is_empty() && document.notes.is_empty());
+    }
+
+    #[test]
+    fn test_parse_errors_name_the_line() {
+        for (text, line, message) in [
+            (
+                "description: A\nprio: high",
+                Some(2),
+                "unknown field 'prio'",
+            ),
+            (
+                "description: A\ndue: someday",
+                Some(2),
+                "Invalid date: someday",
+            ),
+            (
+                "description: A\ndescription: B",
+                Some(2),
+                "field 'description' is given twice",
+            ),
+            (
+                "description: A\nchecklist:\nPassport",
+                Some(3),
+                "expected a checklist item",
+            ),
+            (
+                "description: A\nchecklist:\nstatus: done",
+                Some(3),
+                "field 'status' must come",
+            ),
+            ("due: tomorrow", None, "description cannot be empty"),
+        ] {
+            match parse(text) {
+                Err(TodoError::InvalidDocument {
+                    line: error_line,
+                    message: error,
+                }) => {
+                    assert_eq!(error_line, line, "{text:?}");
+                    assert!(error.starts_with(message), "{error:?} for {text:?}");
+                }
+                other => panic!("{text:?} should not parse, got {other:?}"),
+            }
+        }
+    }
+
+    #[test]
+    fn test_apply() {
+        let mut list = TodoList::new();
+        let id = list.add_task(NewTask {
+            description: "Call client".to_string(),
+            due_date: DueDate::On(today()),
+            category: "Work".to_string(),
+            priority: Priority::Low,
+        });
+        list.add_tag(id, "phone").unwrap();
+
+        let document = parse(
+            "description: Email client\ndue:\ncategory: Work/Acme\npriority: medium\n\
+             status: done\ntags: email\nchecklist:\n[ ] Draft\nnotes:\nBe polite",
+        )
+        .unwrap();
+        assert_eq!(document.apply(&mut list, id).unwrap(), None);
+
+        let task = list.get_task(id).unwrap();
+        assert_eq!(task.description, "Email client");
+        assert_eq!(task.due_date, DueDate::None);
+        assert_eq!(task.category, "Work/Acme");
+        assert_eq!(task.status, Status::Completed);
+        assert!(task.completed_at.is_some());
+        assert_eq!(task.tags, ["email".to_string()].into());
+        assert_eq!(task.checklist_percent(), Some(0));
+        assert_eq!(task.notes, "Be polite");
+    }
+}
@@ -0,0 +1,220 @@
+use crate::todo::TodoError;
+use std::ffi::OsString;
+use std::fs::{self, File, OpenOptions, TryLockError};
+use std::io::{self, Read, Seek, Write};
+use std::path::{Path, PathBuf};
+use std::process;
+
+type Result<T> = std::result::Result<T, TodoError>;
+
+// DEFAULT_BACKUPS is how many earlier versions of a list file saving keeps
+pub const DEFAULT_BACKUPS: usize = 3;
+
+// write_atomic replaces the file at `path` with `contents` so that a crash or a full disk
+// leaves either the old file or the new one, never a mix. The contents go to a temporary
+// file that is synced and then renamed over `path`. The old file is kept as `<path>.bak.1`,
+// with older backups moving along to `<path>.bak.<backups>`
+pub fn write_atomic(path: &Path, contents: &[u8], backups: usize) -> io::Result<()> {
+    check_file_name(path)?;
+    let temp_path = sibling(path, ".tmp");
+    let result = File::create(&temp_path).and_then(|mut file| {
+        file.write_all(contents)?;
+        file.sync_all()?;
+        drop(file);
+        rotate_backups(path, backups)?;
+        fs::rename(&temp_path, path)
+    });
+    if let Err(e) = result {
+        let _ = fs::remove_file(&temp_path);
+        return Err(e);
+    }
+    sync_parent(path)
+}
+
+// rotate_backups copies the file at `path` to its first backup, moving the older backups
+// along and dropping the oldest
+fn rotate_backups(path: &Path, backups: usize) -> io::Result<()> {
+    if backups > 0 && path.exists() {
+        for generation in (1..backups).rev() {
+            let older = backup_path(path, generation);
+            if older.exists() {
+                fs::rename(&older, backup_path(path, generation + 1))?;
+            }
+        }
+        fs::copy(path, backup_path(path, 1))?;
+    }
+    Ok(())
+}
+
+// check_file_name rejects a path that names no file, such as an empty one, before the files
+// kept next to it end up in the current directory
+fn check_file_name(path: &Path) -> io::Result<()> {
+    if path.file_name().is_none() {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            format!("'{}' is not a file name", path.display()),
+        ));
+    }
+    Ok(())
+}
+
+// backup_path is where the backup of `path` from `generation` saves ago is kept
+pub fn backup_path(path: &Path, generation: usize) -> PathBuf {
+    sibling(path, &format!(".bak.{}", generation))
+}
+
+// same_file reports whether two paths name the same file, however they are spelled, as with
+// `./todo.json` and `todo.json`. Either file may not exist yet
+pub fn same_file(a: impl AsRef<Path>, b: impl AsRef<Path>) -> bool {
+    canonical(a.as_ref()) == canonical(b.as_ref())
+}
+
+// canonical resolves a path to its absolute form, following symlinks where the file or its
+// directory exists
+fn canonical(path: &Path) -> PathBuf {
+    if let Ok(canonical) = fs::canonicalize(path) {
+        return canonical;
+    }
+    let parent = match path.parent() {
+        Some(parent) if !parent.as_os_str().is_empty() => parent,
+        _ => Path::new("."),
+    };
+    match (fs::canonicalize(parent), path.file_name()) {
+        (Ok(parent), Some(name)) => parent.join(name),
+        _ => std::path::absolute(path).unwrap_or_else(|_| path.to_path_buf()),
+    }
+}
+
+fn sibling(path: &Path, suffix: &str) -> PathBuf {
+    let mut name = OsString::from(path.as_os_str());
+    name.push(suffix);
+    PathBuf::from(name)
+}
+
+// sync_parent makes the rename of a file durable by syncing the directory holding it
+#[cfg(unix)]
+fn sync_parent(path: &Path) -> io::Result<()> {
+    let parent = match path.parent() {
+        Some(parent) if !parent.as_os_str().is_empty() => parent,
+        _ => Path::new("."),
+    };
+    File::open(parent)?.sync_all()
+}
+
+#[cfg(not(unix))]
+fn sync_parent(_path: &Path) -> io::Result<()> {
+    Ok(())
+}
+
+// ListLock is an advisory lock on a list file, held until it is dropped, so two processes
+// cannot have the same list open and overwrite each other's changes. The lock is taken on
+// `<path>.lock` rather than the list file, which saving replaces, and records the ID of the
+// process holding it
+#[derive(Debug)]
+pub struct ListLock {
+    path: PathBuf,
+    _file: File,
+}
+
+impl ListLock {
+    // acquire locks the list file at `path`, failing with ListLocked if another process
+    // holds the lock
+    pub fn acquire(path: impl AsRef<Path>) -> Result<ListLock> {
+        let path = path.as_ref().to_path_buf();
+        check_file_name(&path)?;
+        let mut file = OpenOptions::new()
+            .read(true)
+            .write(true)
+            .create(true)
+            .truncate(false)
+            .open(sibling(&path, ".lock"))?;
+        match file.try_lock() {
+            Ok(()) => {}
+            Err(TryLockError::WouldBlock) => {
+                let mut owner = String::new();
+                file.read_to_string(&mut owner)?;
+                return Err(TodoError::ListLocked {
+                    path: path.display().to_string(),
+                    pid: owner.trim().parse().ok(),
+                });
+            }
+            Err(TryLockError::Error(e)) => return Err(e.into()),
+        }
+        file.set_len(0)?;
+        file.rewind()?;
+        write!(file, "{}", process::id())?;
+        file.sync_data()?;
+        Ok(ListLock { path, _file: file })
+    }
+
+    // is_for reports whether this is the lock on the list file at `path`
+    pub fn is_for(&self, path: impl AsRef<Path>) -> bool {
+        same_file(&self.path, path)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn temp_path(name: &str) -> PathBuf {
+        std::env::temp_dir().join(format!("todo_fileio_{}_{}.json", name, process::id()))
+    }
+
+    #[test]
+    fn test_write_atomic_rotates_backups() {
+        let path = temp_path("backups");
+        for contents in ["one", "two", "three", "four"] {
+            write_atomic(&path, contents.as_bytes(), 2).unwrap();
+        }
+        assert_eq!(fs::read_to_string(&path).unwrap(), "four");
+        assert_eq!(fs::read_to_string(backup_path(&path, 1)).unwrap(), "three");
+        assert_eq!(fs::read_to_string(backup_path(&path, 2)).unwrap(), "two");
+        assert!(!backup_path(&path, 3).exists());
+        assert!(!sibling(&path, ".tmp").exists());
+
+        for file in [path.clone(), backup_path(&path, 1), backup_path(&path, 2)] {
+            fs::remove_file(file).unwrap();
+        }
+    }
+
+    #[test]
+    fn test_write_atomic_cleans_up_after_failing() {
+        // A directory can't be replaced by a file, so the final rename fails
+        let path = temp_path("directory");
+        fs::create_dir_all(path.join("inside")).unwrap();
+        assert!(write_atomic(&path, b"list", 0).is_err());
+        assert!(!sibling(&path, ".tmp").exists());
+        fs::remove_dir_all(&path).unwrap();
+
+        let empty = Path::new("");
+        assert_eq!(
+            write_atomic(empty, b"list", 0).unwrap_err().kind(),
+            io::ErrorKind::InvalidInput
+        );
+        assert!(ListLock::acquire(empty).is_err());
+        assert!(!Path::new(".tmp").exists() && !Path::new(".lock").exists());
+    }
+
+    #[test]
+    fn test_lock_is_exclusive() {
+        let path = temp_path("lock");
+        let lock = ListLock::acquire(&path).unwrap();
+        assert!(lock.is_for(&path));
+        let relative = path
+            .parent()
+            .unwrap()
+            .join(".")
+            .join(path.file_name().unwrap());
+        assert!(lock.is_for(relative));
+        assert!(!lock.is_for(temp_path("other")));
+        match ListLock::acquire(&path) {
+            Err(TodoError::ListLocked { pid, .. }) => assert_eq!(pid, Some(process::id())),
+            other => panic!("expected ListLocked, got {:?}", other),
+        }
+        drop(lock);
+        ListLock::acquire(&path).unwrap();
+
+        fs::remove_file(sibling(&path, ".lock")).unwrap();
+    }
+}
@@ -0,0 +1,208 @@
+use crate::todo::{RemoveSubtasks, Task, TaskUpdate};
+use serde::{Deserialize, Serialize};
+use std::collections::VecDeque;
+use std::fmt;
+
+// DEFAULT_HISTORY_LIMIT is how many changes can be undone unless the list says otherwise
+pub const DEFAULT_HISTORY_LIMIT: usize = 100;
+
+// Action is the kind of change a history entry records, as shown to the user
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Action {
+    Add,
+    Remove,
+    Update,
+}
+
+impl fmt::Display for Action {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Action::Add => write!(f, "add"),
+            Action::Remove => write!(f, "remove"),
+            Action::Update => write!(f, "update"),
+        }
+    }
+}
+
+// Operation is a change to the tasks of a list. Applying one gives back the operation that
+// reverses it, so undoing and redoing swap an operation for its inverse
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "op", rename_all = "lowercase")]
+pub enum Operation {
+    // Insert puts removed tasks back, the removed task first, and restores the subtasks and
+    // blockers its removal changed
+    Insert {
+        tasks: Vec<Task>,
+        subtasks: RemoveSubtasks,
+        // promoted are the subtasks that were moved up to the removed task's parent
+        #[serde(default)]
+        promoted: Vec<u32>,
+        // unblocked are the (task, blocker) links dropped with the removed tasks
+        #[serde(default)]
+        unblocked: Vec<(u32, u32)>,
+    },
+    Delete {
+        id: u32,
+        subtasks: RemoveSubtasks,
+    },
+    Update {
+        id: u32,
+        update: TaskUpdate,
+    },
+}
+
+// Change is one entry in the history: what the user did to which task, and the operation that
+// takes it back (on the undo stack) or does it again (on the redo stack)
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Change {
+    pub action: Action,
+    pub id: u32,
+    pub operation: Operation,
+}
+
+impl fmt::Display for Change {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{} task {}", self.action, self.id)
+    }
+}
+
+// History holds the changes that can be undone and redone, keeping at most `limit` of them.
+// It is only written to the save file when `persist` is set
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct History {
+    #[serde(default = "default_limit")]
+    limit: usize,
+    #[serde(default)]
+    persist: bool,
+    #[serde(default)]
+    undo: VecDeque<Change>,
+    #[serde(default)]
+    redo: Vec<Change>,
+}
+
+fn default_limit() -> usize {
+    DEFAULT_HISTORY_LIMIT
+}
+
+impl Default for History {
+    fn default() -> Self {
+        History {
+            limit: DEFAULT_HISTORY_LIMIT,
+            persist: false,
+            undo: VecDeque::new(),
+            redo: Vec::new(),
+        }
+    }
+}
+
+impl History {
+    // record adds a change to undo, dropping the oldest one past the limit. A new change
+    // means the undone ones can no longer be redone
+    pub fn record(&mut self, change: Change) {
+        self.redo.clear();
+        self.undo.push_back(change);
+        self.trim();
+    }
+
+    pub fn pop_undo(&mut self) -> Option<Change> {
+        self.undo.pop_back()
+    }
+
+    pub fn pop_redo(&mut self) -> Option<Change> {
+        self.redo.pop()
+    }
+
+    // push_undo puts back a change that was redone, or that failed to undo, without
+    // clearing the redo stack
+    pub fn push_undo(&mut self, change: Change) {
+        self.undo.push_back(change);
+        self.trim();
+    }
+
+    pub fn push_redo(&mut self, change: Change) {
+        self.redo.push(change);
+    }
+
+    // undo_changes lists the changes that can be undone, most recent first
+    pub fn undo_changes(&self) -> impl Iterator<Item = &Change> {
+        self.undo.iter().rev()
+    }
+
+    // redo_changes lists the changes that can be redone, next one first
+    pub fn redo_changes(&self) -> impl Iterator<Item = &Change> {
+        self.redo.iter().rev()
+    }
+
+    pub fn limit(&self) -> usize {
+        self.limit
+    }
+
+    // set_limit changes how many changes are kept, forgetting the oldest ones past it
+    pub fn set_limit(&mut self, limit: usize) {
+        self.limit = limit;
+        self.trim();
+        // The next redo is at the end of the stack, so the furthest ones go first
+        let excess = self.redo.len().saturating_sub(limit);
+        self.redo.drain(..excess);
+    }
+
+    pub fn is_persisted(&self) -> bool {
+        self.persist
+    }
+
+    pub fn set_persisted(&mut self, persist: bool) {
+        self.persist = persist;
+    }
+
+    // is_transient says whether the history stays out of the save file
+    pub fn is_transient(&self) -> bool {
+        !self.persist
+    }
+
+    fn trim(&mut self) {
+        while self.undo.len() > self.limit {
+            self.undo.pop_front();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn change(id: u32) -> Change {
+        Change {
+            action: Action::Add,
+            id,
+            operation: Operation::Delete {
+                id,
+                subtasks: RemoveSubtasks::Promote,
+            },
+        }
+    }
+
+    #[test]
+    fn test_history_is_bounded() {
+        let mut history = History::default();
+        history.set_limit(3);
+        for id in 1..=5 {
+            history.record(change(id));
+        }
+        let ids: Vec<u32> = history.undo_changes().map(|change| change.id).collect();
+        assert_eq!(ids, [5, 4, 3]);
+
+        let undone = history.pop_undo().unwrap();
+        history.push_redo(undone);
+        assert_eq!(history.redo_changes().count(), 1);
+        history.record(change(6));
+        assert_eq!(history.redo_changes().count(), 0);
+
+        history.set_limit(1);
+        assert_eq!(history.undo_changes().count(), 1);
+        assert_eq!(
+            history.undo_changes().next().unwrap().to_string(),
+            "add task 6"
+        );
+    }
+}
@@ -0,0 +1,473 @@
+use crate::fileio::{self, DEFAULT_BACKUPS};
+use crate::storage::Storage;
+use crate::todo::{Task, TodoError, TodoList};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::fs::{self, OpenOptions};
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+
+type Result<T> = std::result::Result<T, TodoError>;
+
+// DEFAULT_SNAPSHOT_AFTER is how many entries a journal collects after its latest snapshot
+// before saving appends a new one
+pub const DEFAULT_SNAPSHOT_AFTER: usize = 1000;
+
+// Event is one change recorded in a journal
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "event", rename_all = "lowercase")]
+pub enum Event {
+    // Snapshot replaces the whole list. A compacted journal starts with one, and saving
+    // appends one now and then so that opening the journal need not replay all of it
+    Snapshot { next_id: u32, tasks: Vec<Task> },
+    // Put adds a task or replaces it with a new version
+    Put { task: Box<Task> },
+    // Delete removes a task. `next_id` keeps the IDs of deleted tasks from being reused
+    Delete { id: u32, next_id: u32 },
+}
+
+// Entry is one line of a journal: an event and when it was recorded
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Entry {
+    pub at: DateTime<Utc>,
+    #[serde(flatten)]
+    pub event: Event,
+}
+
+// Journal stores a list as an append-only file of JSON lines, one Entry each. Saving appends
+// an event for every task that changed since the last save, so a crash can at worst lose the
+// line being written, and replaying the entries up to a point in time rebuilds the list as it
+// was then
+#[derive(Debug)]
+pub struct Journal {
+    path: PathBuf,
+    // tasks and next_id are the list as the journal last recorded it, which saves are
+    // compared against
+    tasks: HashMap<u32, Task>,
+    next_id: u32,
+    since_snapshot: usize,
+    snapshot_after: Option<usize>,
+    // backups is how many earlier versions of the file compacting keeps
+    backups: usize,
+}
+
+impl Journal {
+    // open replays the journal at `path` from its latest snapshot, starting an empty one if
+    // the file does not exist yet. A last line cut short by a crash is dropped
+    pub fn open(path: impl AsRef<Path>) -> Result<(Journal, TodoList)> {
+        let path = path.as_ref().to_path_buf();
+        let entries = if path.exists() {
+            let (entries, valid_len) = read_entries(&path)?;
+            let file = OpenOptions::new().write(true).open(&path)?;
+            if file.metadata()?.len() > valid_len {
+                file.set_len(valid_len)?;
+            }
+            entries
+        } else {
+            Vec::new()
+        };
+
+        let mut journal = Journal {
+            path,
+            tasks: HashMap::new(),
+            next_id: 1,
+            since_snapshot: 0,
+            snapshot_after: Some(DEFAULT_SNAPSHOT_AFTER),
+            backups: DEFAULT_BACKUPS,
+        };
+        let latest_snapshot = entries
+            .iter()
+            .rposition(|entry| matches!(entry.event, Event::Snapshot { .. }))
+            .unwrap_or(0);
+        for entry in entries.into_iter().skip(latest_snapshot) {
+            journal.apply(entry.event);
+        }
+        let list = journal.to_list();
+        Ok((journal, list))
+    }
+
+    // create starts a journal at `path` holding a snapshot of the list, replacing whatever
+    // the file held, such as the list saved as a JSON snapshot, of which `backups` earlier
+    // versions are kept
+    pub fn create(path: impl AsRef<Path>, todo_list: &TodoList, backups: usize) -> Result<Journal> {
+        let mut journal = Journal {
+            path: path.as_ref().to_path_buf(),
+            tasks: HashMap::new(),
+            next_id: 1,
+            since_snapshot: 0,
+            snapshot_after: Some(DEFAULT_SNAPSHOT_AFTER),
+            backups,
+        };
+        journal.compact_at(todo_list, Utc::now())?;
+        Ok(journal)
+    }
+
+    // replay rebuilds the list as it was at `as_of` from the journal at `path`. Only an
+    // explicit compaction drops the entries needed for that
+    pub fn replay(path: impl AsRef<Path>, as_of: DateTime<Utc>) -> Result<TodoList> {
+        let (entries, _) = read_entries(path.as_ref())?;
+        if let Some(first) = entries.first() {
+            if matches!(first.event, Event::Snapshot { .. }) && first.at > as_of {
+                return Err(TodoError::JournalCompacted(first.at));
+            }
+        }
+
+        let mut journal = Journal {
+            path: path.as_ref().to_path_buf(),
+            tasks: HashMap::new(),
+            next_id: 1,
+            since_snapshot: 0,
+            snapshot_after: None,
+            backups: DEFAULT_BACKUPS,
+        };
+        for entry in entries.into_iter().take_while(|entry| entry.at <= as_of) {
+            journal.apply(entry.event);
+        }
+        Ok(journal.to_list())
+    }
+
+    // set_snapshot_after sets how many entries may follow the latest snapshot before saving
+    // appends a new one, or turns automatic snapshots off when given None
+    pub fn set_snapshot_after(&mut self, snapshot_after: Option<usize>) {
+        self.snapshot_after = snapshot_after;
+    }
+
+    // set_backups sets how many earlier versions of the file compacting keeps
+    pub fn set_backups(&mut self, backups: usize) {
+        self.backups = backups;
+    }
+
+    // record appends an event for every change to the list since the journal last recorded
+    // it and returns how many were written
+    pub fn record(&mut self, todo_list: &TodoList) -> Result<usize> {
+        self.record_at(todo_list, Utc::now())
+    }
+
+    pub fn record_at(&mut self, todo_list: &TodoList, now: DateTime<Utc>) -> Result<usize> {
+        let mut events = Vec::new();
+        for task in sorted(todo_list.list_tasks(None)) {
+            if self.tasks.get(&task.id) != Some(task) {
+                events.push(Event::Put {
+                    task: Box::new(task.clone()),
+                });
+            }
+        }
+        let mut deleted: Vec<u32> = self
+            .tasks
+            .keys()
+            .copied()
+            .filter(|id| todo_list.get_task(*id).is_none())
+            .collect();
+        deleted.sort();
+        for id in deleted {
+            events.push(Event::Delete {
+                id,
+                next_id: todo_list.next_id(),
+            });
+        }
+        self.append(events, now)
+    }
+
+    // append writes events to the end of the journal in one go, followed by a snapshot when
+    // the entries since the latest one have grown past the limit
+    fn append(&mut self, events: Vec<Event>, now: DateTime<Utc>) -> Result<usize> {
+        if events.is_empty() {
+            return Ok(0);
+        }
+        let mut lines = String::new();
+        for event in &events {
+            let entry = Entry {
+                at: now,
+                event: event.clone(),
+            };
+            lines.push_str(&serde_json::to_string(&entry)?);
+            lines.push('\n');
+        }
+        let mut file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&self.path)?;
+        file.write_all(lines.as_bytes())?;
+        file.sync_data()?;
+
+        let written = events.len();
+        for event in events {
+            self.apply(event);
+        }
+        if self
+            .snapshot_after
+            .is_some_and(|limit| self.since_snapshot > limit)
+        {
+            let snapshot = snapshot(&self.to_list());
+            self.append(vec![snapshot], now)?;
+        }
+        Ok(written)
+    }
+
+    // compact_at rewrites the journal as a single snapshot of the list. The list can no
+    // longer be rebuilt as of any time before that
+    pub fn compact_at(&mut self, todo_list: &TodoList, now: DateTime<Utc>) -> Result<()> {
+        let entry = Entry {
+            at: now,
+            event: snapshot(todo_list),
+        };
+        // The journal being replaced is kept as a backup, since its history is otherwise lost
+        let line = format!("{}\n", serde_json::to_string(&entry)?);
+        fileio::write_atomic(&self.path, line.as_bytes(), self.backups)?;
+
+        self.apply(entry.event);
+        Ok(())
+    }
+
+    fn apply(&mut self, event: Event) {
+        match event {
+            Event::Snapshot { next_id, tasks } => {
+                self.tasks = tasks.into_iter().map(|task| (task.id, task)).collect();
+                self.next_id = next_id;
+                self.since_snapshot = 0;
+                return;
+            }
+            Event::Put { task } => {
+                self.next_id = self.next_id.max(task.id + 1);
+                self.tasks.insert(task.id, *task);
+            }
+            Event::Delete { id, next_id } => {
+                self.next_id = self.next_id.max(next_id);
+                self.tasks.remove(&id);
+            }
+        }
+        self.since_snapshot += 1;
+    }
+
+    fn to_list(&self) -> TodoList {
+        TodoList::from_tasks(self.tasks.values().cloned(), self.next_id)
+    }
+}
+
+// A journal saves by appending the changes since it was last written, and upserts and
+// deletes tasks by appending a single event
+impl Storage for Journal {
+    fn describe(&self) -> String {
+        format!("journal {}", self.path.display())
+    }
+
+    fn path(&self) -> Option<&Path> {
+        Some(&self.path)
+    }
+
+    fn load(&mut self) -> Result<TodoList> {
+        let (journal, todo_list) = Journal::open(&self.path)?;
+        *self = Journal {
+            snapshot_after: self.snapshot_after,
+            backups: self.backups,
+            ..journal
+        };
+        Ok(todo_list)
+    }
+
+    fn save(&mut self, todo_list: &TodoList) -> Result<()> {
+        self.record(todo_list).map(|_| ())
+    }
+
+    fn upsert_task(&mut self, task: &Task) -> Result<()> {
+        let event = Event::Put {
+            task: Box::new(task.clone()),
+        };
+        self.append(vec![event], Utc::now()).map(|_| ())
+    }
+
+    fn delete_task(&mut self, id: u32) -> Result<()> {
+        if !self.tasks.contains_key(&id) {
+            return Err(TodoError::TaskNotFound);
+        }
+        let event = Event::Delete {
+            id,
+            next_id: self.next_id,
+        };
+        self.append(vec![event], Utc::now()).map(|_| ())
+    }
+
+    fn compact(&mut self, todo_list: &TodoList) -> Result<()> {
+        self.compact_at(todo_list, Utc::now())
+    }
+}
+
+fn snapshot(todo_list: &TodoList) -> Event {
+    Event::Snapshot {
+        next_id: todo_list.next_id(),
+        tasks: sorted(todo_list.list_tasks(None))
+            .into_iter()
+            .cloned()
+            .collect(),
+    }
+}
+
+fn sorted(mut tasks: Vec<&Task>) -> Vec<&Task> {
+    tasks.sort_by_key(|task| task.id);
+    tasks
+}
+
+// read_entries parses a journal and returns its entries with the length of the file they
+// cover. Only the last line may be unreadable, as left by a crash part way through a save
+fn read_entries(path: &Path) -> Result<(Vec<Entry>, u64)> {
+    let text = fs::read_to_string(path)?;
+    let mut entries = Vec::new();
+    let mut valid_len = 0;
+    for (number, line) in text.split_inclusive('\n').enumerate() {
+        if line.trim().is_empty() {
+            valid_len += line.len();
+            continue;
+        }
+        match serde_json::from_str(line) {
+            Ok(entry) => entries.push(entry),
+            // A file whose only line is unreadable is not a journal at all
+            Err(_) if !line.ends_with('\n') && !entries.is_empty() => break,
+            Err(e) => return Err(TodoError::InvalidJournal(number + 1, e.to_string())),
+        }
+        valid_len += line.len();
+    }
+    Ok((entries, valid_len as u64))
+}
+
+// is_journal says whether a file holds a journal rather than a JSON snapshot of the list
+pub fn is_journal(path: impl AsRef<Path>) -> io::Result<bool> {
+    let text = fs::read_to_string(path)?;
+    Ok(text
+        .lines()
+        .next()
+        .is_some_and(|line| serde_json::from_str::<Entry>(line).is_ok()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::patch::Patch;
+    use crate::todo::{DueDate, NewTask, Priority, TaskUpdate};
+    use chrono::TimeZone;
+
+    fn at(hour: u32) -> DateTime<Utc> {
+        Utc.with_ymd_and_hms(2026, 10, 1, hour, 0, 0).unwrap()
+    }
+
+    fn journal_path(name: &str) -> PathBuf {
+        let path = std::env::temp_dir().join(format!(
+            "todo_journal_{}_{}.jsonl",
+            name,
+            std::process::id()
+        ));
+        let _ = fs::remove_file(&path);
+        path
+    }
+
+    fn new_task(description: &str) -> NewTask {
+        NewTask {
+            description: description.to_string(),
+            due_date: DueDate::None,
+            category: "Work".to_string(),
+            priority: Priority::Low,
+        }
+    }
+
+    #[test]
+    fn test_replay_and_as_of() {
+        let path = journal_path("replay");
+        let (mut journal, mut list) = Journal::open(&path).unwrap();
+        list.add_task(new_task("Write report"));
+        list.add_task(new_task("Send invoice"));
+        assert_eq!(journal.record_at(&list, at(9)).unwrap(), 2);
+        assert_eq!(journal.record_at(&list, at(9)).unwrap(), 0);
+
+        let update = TaskUpdate {
+            priority: Patch::Set(Priority::High),
+            ..TaskUpdate::default()
+        };
+        list.update_task(1, update).unwrap();
+        list.remove_task(2).unwrap();
+        assert_eq!(journal.record_at(&list, at(10)).unwrap(), 2);
+
+        let (_, reopened) = Journal::open(&path).unwrap();
+        assert_eq!(reopened.get_task(1).unwrap().priority, Priority::High);
+        assert!(reopened.get_task(2).is_none());
+        assert_eq!(reopened.next_id(), 3);
+
+        let earlier = Journal::replay(&path, at(9)).unwrap();
+        assert_eq!(earlier.get_task(1).unwrap().priority, Priority::Low);
+        assert!(earlier.get_task(2).is_some());
+        assert!(Journal::replay(&path, at(8))
+            .unwrap()
+            .list_tasks(None)
+            .is_empty());
+
+        fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn test_snapshots_and_compaction() {
+        let path = journal_path("compact");
+        let (mut journal, mut list) = Journal::open(&path).unwrap();
+        journal.set_snapshot_after(Some(3));
+        for (hour, description) in [(9, "One"), (10, "Two"), (11, "Three"), (12, "Four")] {
+            list.add_task(new_task(description));
+            journal.record_at(&list, at(hour)).unwrap();
+        }
+        // The fourth entry went past the limit, so a snapshot follows it and the history
+        // before it is kept
+        let text = fs::read_to_string(&path).unwrap();
+        assert_eq!(text.lines().count(), 5);
+        let last: Entry = serde_json::from_str(text.lines().last().unwrap()).unwrap();
+        assert!(matches!(last.event, Event::Snapshot { .. }));
+
+        let (_, reopened) = Journal::open(&path).unwrap();
+        assert_eq!(reopened.list_tasks(None).len(), 4);
+        assert_eq!(
+            Journal::replay(&path, at(10))
+                .unwrap()
+                .list_tasks(None)
+                .len(),
+            2
+        );
+
+        journal.compact_at(&list, at(13)).unwrap();
+        assert_eq!(fs::read_to_string(&path).unwrap().lines().count(), 1);
+        assert!(is_journal(&path).unwrap());
+        let (_, reopened) = Journal::open(&path).unwrap();
+        assert_eq!(reopened.list_tasks(None).len(), 4);
+        assert!(matches!(
+            Journal::replay(&path, at(11)),
+            Err(TodoError::JournalCompacted(_))
+        ));
+
+        fs::remove_file(&path).unwrap();
+        fs::remove_file(fileio::backup_path(&path, 1)).unwrap();
+    }
+
+    #[test]
+    fn test_torn_last_line_is_dropped() {
+        let path = journal_path("torn");
+        let (mut journal, mut list) = Journal::open(&path).unwrap();
+        list.add_task(new_task("Survives"));
+        journal.record_at(&list, at(9)).unwrap();
+        let mut file = OpenOptions::new().append(true).open(&path).unwrap();
+        write!(
+            file,
+            "{{\"at\":\"2026-10-01T10:00:00Z\",\"event\":\"put\",\"ta"
+        )
+        .unwrap();
+
+        let (mut journal, mut list) = Journal::open(&path).unwrap();
+        assert_eq!(list.list_tasks(None).len(), 1);
+        list.add_task(new_task("Appended cleanly"));
+        journal.record_at(&list, at(11)).unwrap();
+        let (_, reopened) = Journal::open(&path).unwrap();
+        assert_eq!(reopened.list_tasks(None).len(), 2);
+
+        fs::write(&path, "not json\n{}\n").unwrap();
+        assert!(matches!(
+            Journal::open(&path),
+            Err(TodoError::InvalidJournal(1, _))
+        ));
+
+        fs::remove_file(&path).unwrap();
+    }
+}
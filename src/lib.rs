@@ -1,3 +1,18 @@
 // src/lib.rs
 pub mod cli;
+pub mod commands;
+pub mod dates;
+pub mod document;
+pub mod fileio;
+pub mod history;
+pub mod journal;
+pub mod migrations;
+pub mod patch;
+pub mod query;
+pub mod recurrence;
+pub mod sort;
+pub mod sqlite;
+pub mod storage;
+pub mod timelog;
 pub mod todo;
+pub mod workspace;
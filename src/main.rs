@@ -1,10 +1,13 @@
-// src/main.rs
-mod cli;
-mod todo;
+use clap::Parser;
+use std::process::ExitCode;
+use todo::commands::{self, Args};
 
-use todo::TodoList;
-
-fn main() {
-    let mut todo_list = TodoList::new();
-    let _ = cli::run_cli(&mut todo_list);
+fn main() -> ExitCode {
+    match commands::run(Args::parse()) {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(e) => {
+            eprintln!("error: {}", e);
+            ExitCode::FAILURE
+        }
+    }
 }
@@ -0,0 +1,151 @@
+use crate::todo::{normalize_category, TodoError, TodoList, DEFAULT_CATEGORY};
+use serde::Serialize;
+use serde_json::Value;
+use std::collections::BTreeMap;
+
+type Result<T> = std::result::Result<T, TodoError>;
+
+// FORMAT_VERSION is the version of the save file format written by save_to_file. Bump it and
+// add a migration to MIGRATIONS whenever saved lists need rewriting to load correctly
+pub const FORMAT_VERSION: u32 = 2;
+
+// MIGRATIONS[n] upgrades a list saved in format version n + 1 to version n + 2. Files written
+// before the format was versioned are version 1
+const MIGRATIONS: [fn(Value) -> Result<Value>; (FORMAT_VERSION - 1) as usize] =
+    [normalize_categories];
+
+// Envelope is how a list is saved: the format version next to the list itself
+#[derive(Serialize)]
+struct Envelope<'a> {
+    version: u32,
+    list: &'a TodoList,
+}
+
+// to_json saves a list in the current format
+pub fn to_json(todo_list: &TodoList) -> Result<String> {
+    Ok(serde_json::to_string(&Envelope {
+        version: FORMAT_VERSION,
+        list: todo_list,
+    })?)
+}
+
+// from_json loads a list saved in the current format or any earlier one, migrating it as it
+// goes. Files from a newer version are rejected rather than loaded with data missing
+pub fn from_json(json: &str) -> Result<TodoList> {
+    from_value(serde_json::from_str(json)?)
+}
+
+// from_value loads a list from a saved file already parsed as JSON, like from_json
+pub fn from_value(saved: Value) -> Result<TodoList> {
+    let (version, mut list) = match saved {
+        Value::Object(mut envelope) if envelope.contains_key("version") => {
+            let version = envelope
+                .get("version")
+                .and_then(Value::as_u64)
+                .and_then(|version| u32::try_from(version).ok())
+                .filter(|&version| version >= 1)
+                .ok_or_else(|| {
+                    TodoError::InvalidSaveFile(
+                        "the format version must be a positive number".to_string(),
+                    )
+                })?;
+            let list = envelope
+                .remove("list")
+                .ok_or_else(|| TodoError::InvalidSaveFile("the list is missing".to_string()))?;
+            (version, list)
+        }
+        list => (1, list),
+    };
+    if version > FORMAT_VERSION {
+        return Err(TodoError::UnsupportedVersion {
+            found: version,
+            supported: FORMAT_VERSION,
+        });
+    }
+
+    for migrate in &MIGRATIONS[version as usize - 1..] {
+        list = migrate(list)?;
+    }
+    Ok(serde_json::from_value(list)?)
+}
+
+// normalize_categories upgrades version 1 files, whose categories were stored as typed, to
+// normalized categories with one spelling each, as list operations have kept them since
+fn normalize_categories(mut list: Value) -> Result<Value> {
+    let Some(tasks) = list.get_mut("tasks").and_then(Value::as_object_mut) else {
+        return Ok(list);
+    };
+    let categories: Vec<(String, String)> = tasks
+        .iter()
+        .filter_map(|(id, task)| {
+            let category = normalize_category(task.get("category")?.as_str()?);
+            if category.is_empty() {
+                Some((id.clone(), DEFAULT_CATEGORY.to_string()))
+            } else {
+                Some((id.clone(), category))
+            }
+        })
+        .collect();
+
+    // Categories differing only in case take the spelling that sorts first
+    let mut spellings: BTreeMap<String, String> = BTreeMap::new();
+    for (_, category) in &categories {
+        let spelling = spellings
+            .entry(category.to_lowercase())
+            .or_insert_with(|| category.clone());
+        if category < spelling {
+            *spelling = category.clone();
+        }
+    }
+    for (id, category) in categories {
+        if let Some(task) = tasks.get_mut(&id) {
+            task["category"] = Value::String(spellings[&category.to_lowercase()].clone());
+        }
+    }
+    Ok(list)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::todo::{DueDate, NewTask, Priority};
+
+    #[test]
+    fn test_round_trip_writes_the_current_version() {
+        let mut list = TodoList::new();
+        list.add_task(NewTask {
+            description: "Task".to_string(),
+            due_date: DueDate::None,
+            category: "Work".to_string(),
+            priority: Priority::Low,
+        });
+        let json = to_json(&list).unwrap();
+        let saved: Value = serde_json::from_str(&json).unwrap();
+        assert_eq!(saved["version"], FORMAT_VERSION);
+        assert_eq!(
+            from_json(&json).unwrap().get_task(1).unwrap().category,
+            "Work"
+        );
+    }
+
+    #[test]
+    fn test_version_errors() {
+        assert!(matches!(
+            from_json(r#"{"version": 99, "list": {}}"#),
+            Err(TodoError::UnsupportedVersion {
+                found: 99,
+                supported: FORMAT_VERSION
+            })
+        ));
+        for invalid in [
+            r#"{"version": "2", "list": {}}"#,
+            r#"{"version": 0, "list": {}}"#,
+            r#"{"version": 2}"#,
+        ] {
+            assert!(matches!(
+                from_json(invalid),
+                Err(TodoError::InvalidSaveFile(_))
+            ));
+        }
+    }
+}
@@ -0,0 +1,62 @@
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+// Patch is a change to one field: keep the current value, set a new one, or clear it back to
+// the field's default. In JSON Merge Patch (RFC 7386) terms a missing member keeps the field,
+// null clears it and any other value sets it, so struct fields holding a Patch need
+// `#[serde(default, skip_serializing_if = "Patch::is_keep")]`
+#[derive(Clone, Debug, Default, PartialEq)]
+pub enum Patch<T> {
+    #[default]
+    Keep,
+    Set(T),
+    Clear,
+}
+
+impl<T> Patch<T> {
+    pub fn is_keep(&self) -> bool {
+        matches!(self, Patch::Keep)
+    }
+
+    // resolve returns the field's value after the patch, given its current value and the
+    // value it takes when cleared
+    pub fn resolve(self, current: T, cleared: T) -> T {
+        match self {
+            Patch::Keep => current,
+            Patch::Set(value) => value,
+            Patch::Clear => cleared,
+        }
+    }
+
+    pub fn map<U>(self, f: impl FnOnce(T) -> U) -> Patch<U> {
+        match self {
+            Patch::Keep => Patch::Keep,
+            Patch::Set(value) => Patch::Set(f(value)),
+            Patch::Clear => Patch::Clear,
+        }
+    }
+}
+
+// An optional value sets the field when present and keeps it when not, matching the
+// "leave blank to keep current" prompts
+impl<T> From<Option<T>> for Patch<T> {
+    fn from(value: Option<T>) -> Self {
+        value.map_or(Patch::Keep, Patch::Set)
+    }
+}
+
+impl<T: Serialize> Serialize for Patch<T> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        match self {
+            Patch::Set(value) => serializer.serialize_some(value),
+            Patch::Keep | Patch::Clear => serializer.serialize_none(),
+        }
+    }
+}
+
+// A present member deserializes to Set or, when null, Clear; a missing member is left to
+// `#[serde(default)]`, which gives Keep
+impl<'de, T: Deserialize<'de>> Deserialize<'de> for Patch<T> {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        Ok(Option::<T>::deserialize(deserializer)?.map_or(Patch::Clear, Patch::Set))
+    }
+}
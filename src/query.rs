@@ -0,0 +1,536 @@
+use crate::dates::DateParser;
+use crate::todo::{in_category, normalize_tag, Priority, Status, Task, TodoError};
+use chrono::{Local, NaiveDate};
+use std::cmp::Ordering;
+use std::str::FromStr;
+
+type Result<T> = std::result::Result<T, TodoError>;
+
+// Comparison is the operator in a field filter such as `prio>=medium`
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Comparison {
+    Eq,
+    Ne,
+    Lt,
+    Le,
+    Gt,
+    Ge,
+}
+
+impl Comparison {
+    fn holds(self, ordering: Ordering) -> bool {
+        match self {
+            Comparison::Eq => ordering == Ordering::Equal,
+            Comparison::Ne => ordering != Ordering::Equal,
+            Comparison::Lt => ordering == Ordering::Less,
+            Comparison::Le => ordering != Ordering::Greater,
+            Comparison::Gt => ordering == Ordering::Greater,
+            Comparison::Ge => ordering != Ordering::Less,
+        }
+    }
+}
+
+// TaskQuery is a filter over tasks. It can be built directly or parsed from text like
+// `cat:Work prio>=medium due<2026-11-01 status:open "invoice"`, where terms next to each
+// other must all match, `OR` separates alternatives, `NOT` or a leading `-` negates a term
+// and parentheses group. `tag:a,b` (or `+a +b`) needs every tag, `anytag:a,b` any of them
+#[derive(Clone, Debug, PartialEq)]
+pub enum TaskQuery {
+    All,
+    Category(String),
+    Priority(Comparison, Priority),
+    Due(Comparison, NaiveDate),
+    NoDueDate,
+    Status(Status),
+    // AnyTag matches tasks carrying at least one of the tags
+    AnyTag(Vec<String>),
+    // AllTags matches tasks carrying every one of the tags
+    AllTags(Vec<String>),
+    // Text matches tasks whose description contains the text, ignoring case
+    Text(String),
+    And(Vec<TaskQuery>),
+    Or(Vec<TaskQuery>),
+    Not(Box<TaskQuery>),
+}
+
+impl TaskQuery {
+    // parse reads the query syntax, resolving relative dates like `due<tomorrow` against today
+    pub fn parse(input: &str) -> Result<TaskQuery> {
+        Self::parse_with(input, &DateParser::from_env(), Local::now().date_naive())
+    }
+
+    // parse_with reads the query syntax using the given date parser and current date
+    pub fn parse_with(input: &str, dates: &DateParser, today: NaiveDate) -> Result<TaskQuery> {
+        let tokens = tokenize(input)?;
+        if tokens.is_empty() {
+            return Ok(TaskQuery::All);
+        }
+        let mut parser = Parser {
+            tokens,
+            position: 0,
+            dates,
+            today,
+        };
+        let query = parser.parse_or()?;
+        match parser.peek() {
+            None => Ok(query),
+            Some(token) => Err(invalid(format!("unexpected {}", token.describe()))),
+        }
+    }
+
+    // open matches tasks that are not completed
+    pub fn open() -> TaskQuery {
+        TaskQuery::Status(Status::Completed).negate()
+    }
+
+    // any_tag matches tasks carrying at least one of the tags
+    pub fn any_tag<I, S>(tags: I) -> Result<TaskQuery>
+    where
+        I: IntoIterator<Item = S>,
+        S: AsRef<str>,
+    {
+        Ok(TaskQuery::AnyTag(normalize_tags(tags)?))
+    }
+
+    // all_tags matches tasks carrying every one of the tags
+    pub fn all_tags<I, S>(tags: I) -> Result<TaskQuery>
+    where
+        I: IntoIterator<Item = S>,
+        S: AsRef<str>,
+    {
+        Ok(TaskQuery::AllTags(normalize_tags(tags)?))
+    }
+
+    pub fn and(self, other: TaskQuery) -> TaskQuery {
+        match (self, other) {
+            (TaskQuery::All, query) | (query, TaskQuery::All) => query,
+            (TaskQuery::And(mut queries), other) => {
+                queries.push(other);
+                TaskQuery::And(queries)
+            }
+            (query, other) => TaskQuery::And(vec![query, other]),
+        }
+    }
+
+    pub fn or(self, other: TaskQuery) -> TaskQuery {
+        match self {
+            TaskQuery::Or(mut queries) => {
+                queries.push(other);
+                TaskQuery::Or(queries)
+            }
+            query => TaskQuery::Or(vec![query, other]),
+        }
+    }
+
+    pub fn negate(self) -> TaskQuery {
+        TaskQuery::Not(Box::new(self))
+    }
+
+    // matches reports whether a task passes the filter
+    pub fn matches(&self, task: &Task) -> bool {
+        match self {
+            TaskQuery::All => true,
+            TaskQuery::Category(category) => in_category(&task.category, category),
+            TaskQuery::Priority(op, priority) => op.holds(task.priority.cmp(priority)),
+            TaskQuery::Due(op, date) => task.due_date.date().is_some_and(|d| op.holds(d.cmp(date))),
+            TaskQuery::NoDueDate => task.due_date.date().is_none(),
+            TaskQuery::Status(status) => task.status == *status,
+            TaskQuery::AnyTag(tags) => tags.iter().any(|tag| task.tags.contains(tag)),
+            TaskQuery::AllTags(tags) => tags.iter().all(|tag| task.tags.contains(tag)),
+            TaskQuery::Text(text) => task
+                .description
+                .to_lowercase()
+                .contains(&text.to_lowercase()),
+            TaskQuery::And(queries) => queries.iter().all(|q| q.matches(task)),
+            TaskQuery::Or(queries) => queries.iter().any(|q| q.matches(task)),
+            TaskQuery::Not(query) => !query.matches(task),
+        }
+    }
+}
+
+impl FromStr for TaskQuery {
+    type Err = TodoError;
+
+    fn from_str(s: &str) -> Result<Self> {
+        TaskQuery::parse(s)
+    }
+}
+
+fn normalize_tags<I, S>(tags: I) -> Result<Vec<String>>
+where
+    I: IntoIterator<Item = S>,
+    S: AsRef<str>,
+{
+    tags.into_iter()
+        .map(|tag| normalize_tag(tag.as_ref()))
+        .collect()
+}
+
+fn invalid(message: String) -> TodoError {
+    TodoError::InvalidQuery(message)
+}
+
+#[derive(Clone, Debug, PartialEq)]
+enum Token {
+    Word(String),
+    // Quoted is text that was entirely in quotes, which is always a description search
+    Quoted(String),
+    Open,
+    Close,
+}
+
+impl Token {
+    fn describe(&self) -> String {
+        match self {
+            Token::Word(word) => format!("'{}'", word),
+            Token::Quoted(text) => format!("\"{}\"", text),
+            Token::Open => "'('".to_string(),
+            Token::Close => "')'".to_string(),
+        }
+    }
+}
+
+fn tokenize(input: &str) -> Result<Vec<Token>> {
+    let mut tokens = Vec::new();
+    let mut chars = input.chars().peekable();
+
+    while let Some(&c) = chars.peek() {
+        match c {
+            c if c.is_whitespace() => {
+                chars.next();
+            }
+            '(' => {
+                chars.next();
+                tokens.push(Token::Open);
+            }
+            ')' => {
+                chars.next();
+                tokens.push(Token::Close);
+            }
+            _ => {
+                // A word runs to the next space or parenthesis, and may contain a quoted
+                // part such as cat:"Client A"
+                let mut word = String::new();
+                let mut fully_quoted = c == '"';
+                while let Some(&c) = chars.peek() {
+                    if c.is_whitespace() || c == '(' || c == ')' {
+                        break;
+                    }
+                    chars.next();
+                    if c == '"' {
+                        let mut closed = false;
+                        for c in chars.by_ref() {
+                            if c == '"' {
+                                closed = true;
+                                break;
+                            }
+                            word.push(c);
+                        }
+                        if !closed {
+                            return Err(invalid("unterminated quote".to_string()));
+                        }
+                    } else {
+                        fully_quoted = false;
+                        word.push(c);
+                    }
+                }
+                tokens.push(if fully_quoted {
+                    Token::Quoted(word)
+                } else {
+                    Token::Word(word)
+                });
+            }
+        }
+    }
+    Ok(tokens)
+}
+
+struct Parser<'a> {
+    tokens: Vec<Token>,
+    position: usize,
+    dates: &'a DateParser,
+    today: NaiveDate,
+}
+
+impl Parser<'_> {
+    fn peek(&self) -> Option<&Token> {
+        self.tokens.get(self.position)
+    }
+
+    fn next(&mut self) -> Option<Token> {
+        let token = self.tokens.get(self.position).cloned();
+        self.position += 1;
+        token
+    }
+
+    fn peek_keyword(&self, keyword: &str) -> bool {
+        matches!(self.peek(), Some(Token::Word(w)) if w.eq_ignore_ascii_case(keyword))
+    }
+
+    fn parse_or(&mut self) -> Result<TaskQuery> {
+        let mut queries = vec![self.parse_and()?];
+        while self.peek_keyword("or") || self.peek() == Some(&Token::Word("|".to_string())) {
+            self.next();
+            queries.push(self.parse_and()?);
+        }
+        Ok(if queries.len() == 1 {
+            queries.remove(0)
+        } else {
+            TaskQuery::Or(queries)
+        })
+    }
+
+    fn parse_and(&mut self) -> Result<TaskQuery> {
+        let mut queries = vec![self.parse_unary()?];
+        loop {
+            if self.peek_keyword("and") {
+                self.next();
+            } else if self.peek().is_none()
+                || self.peek() == Some(&Token::Close)
+                || self.peek_keyword("or")
+                || self.peek() == Some(&Token::Word("|".to_string()))
+            {
+                break;
+            }
+            queries.push(self.parse_unary()?);
+        }
+        Ok(if queries.len() == 1 {
+            queries.remove(0)
+        } else {
+            TaskQuery::And(queries)
+        })
+    }
+
+    fn parse_unary(&mut self) -> Result<TaskQuery> {
+        match self.next() {
+            None => Err(invalid("expected a filter".to_string())),
+            Some(Token::Word(word)) if word.eq_ignore_ascii_case("not") => {
+                Ok(self.parse_unary()?.negate())
+            }
+            Some(Token::Word(word)) if word.len() > 1 && word.starts_with('+') => {
+                TaskQuery::all_tags([&word])
+            }
+            Some(Token::Word(word)) if word.len() > 1 && word.starts_with('-') => {
+                Ok(self.parse_term(&word[1..])?.negate())
+            }
+            Some(Token::Open) => {
+                let query = self.parse_or()?;
+                match self.next() {
+                    Some(Token::Close) => Ok(query),
+                    _ => Err(invalid("missing ')'".to_string())),
+                }
+            }
+            Some(Token::Word(word))
+                if word == "|"
+                    || word.eq_ignore_ascii_case("or")
+                    || word.eq_ignore_ascii_case("and") =>
+            {
+                Err(invalid(format!("expected a filter before '{}'", word)))
+            }
+            Some(Token::Close) => Err(invalid("unexpected ')'".to_string())),
+            Some(Token::Quoted(text)) => Ok(TaskQuery::Text(text)),
+            Some(Token::Word(word)) => self.parse_term(&word),
+        }
+    }
+
+    // parse_term reads a field filter like `prio>=medium`, or plain text to search for
+    fn parse_term(&self, word: &str) -> Result<TaskQuery> {
+        let field_end = word
+            .find(|c: char| !(c.is_ascii_alphabetic() || c == '_'))
+            .unwrap_or(word.len());
+        let (field, rest) = word.split_at(field_end);
+        let Some((op, value)) = split_operator(rest) else {
+            return Ok(TaskQuery::Text(word.to_string()));
+        };
+        if field.is_empty() {
+            return Ok(TaskQuery::Text(word.to_string()));
+        }
+
+        match field.to_lowercase().as_str() {
+            "cat" | "category" => match op {
+                Comparison::Eq => Ok(TaskQuery::Category(value.to_string())),
+                Comparison::Ne => Ok(TaskQuery::Category(value.to_string()).negate()),
+                _ => Err(invalid(format!(
+                    "categories can only be compared with ':' in {}",
+                    word
+                ))),
+            },
+            "prio" | "priority" => Ok(TaskQuery::Priority(op, value.parse()?)),
+            "due" if value.eq_ignore_ascii_case("none") => match op {
+                Comparison::Eq => Ok(TaskQuery::NoDueDate),
+                Comparison::Ne => Ok(TaskQuery::NoDueDate.negate()),
+                _ => Err(invalid(format!(
+                    "'none' can only be compared with ':' in {}",
+                    word
+                ))),
+            },
+            "due" => Ok(TaskQuery::Due(op, self.dates.parse(value, self.today)?)),
+            "tag" | "tags" | "anytag" => {
+                let tags = value.split(',').filter(|tag| !tag.is_empty());
+                let query = if field.eq_ignore_ascii_case("anytag") {
+                    TaskQuery::any_tag(tags)?
+                } else {
+                    TaskQuery::all_tags(tags)?
+                };
+                match op {
+                    Comparison::Eq => Ok(query),
+                    Comparison::Ne => Ok(query.negate()),
+                    _ => Err(invalid(format!(
+                        "tags can only be compared with ':' in {}",
+                        word
+                    ))),
+                }
+            }
+            "status" | "is" => {
+                let query = match value.to_lowercase().as_str() {
+                    "open" => TaskQuery::open(),
+                    _ => TaskQuery::Status(value.parse()?),
+                };
+                match op {
+                    Comparison::Eq => Ok(query),
+                    Comparison::Ne => Ok(query.negate()),
+                    _ => Err(invalid(format!(
+                        "status can only be compared with ':' in {}",
+                        word
+                    ))),
+                }
+            }
+            _ => Err(invalid(format!("unknown field '{}'", field))),
+        }
+    }
+}
+
+// split_operator splits the operator off the start of `:value`, `>=value` and the like
+fn split_operator(rest: &str) -> Option<(Comparison, &str)> {
+    const OPERATORS: [(&str, Comparison); 7] = [
+        ("!=", Comparison::Ne),
+        (">=", Comparison::Ge),
+        ("<=", Comparison::Le),
+        (":", Comparison::Eq),
+        ("=", Comparison::Eq),
+        (">", Comparison::Gt),
+        ("<", Comparison::Lt),
+    ];
+    OPERATORS
+        .iter()
+        .find_map(|(symbol, op)| rest.strip_prefix(symbol).map(|value| (*op, value)))
+        .filter(|(_, value)| !value.is_empty())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::todo::{DueDate, NewTask, TodoList};
+
+    fn parse(input: &str) -> Result<TaskQuery> {
+        TaskQuery::parse_with(
+            input,
+            &DateParser::new(),
+            NaiveDate::from_ymd_opt(2026, 10, 18).unwrap(),
+        )
+    }
+
+    fn task(description: &str, category: &str, priority: Priority, due_date: DueDate) -> Task {
+        let mut list = TodoList::new();
+        let id = list.add_task(NewTask {
+            description: description.to_string(),
+            due_date,
+            category: category.to_string(),
+            priority,
+        });
+        list.get_task(id).unwrap().clone()
+    }
+
+    #[test]
+    fn test_parse_example_query() {
+        let query = parse(r#"cat:Work prio>=medium due<2026-11-01 status:open "invoice""#).unwrap();
+        let date = |d| DueDate::On(NaiveDate::from_ymd_opt(2026, 10, d).unwrap());
+
+        assert!(query.matches(&task("Send invoice", "Work", Priority::High, date(30))));
+        assert!(!query.matches(&task("Send invoice", "Home", Priority::High, date(30))));
+        assert!(!query.matches(&task("Send invoice", "Work", Priority::Low, date(30))));
+        assert!(!query.matches(&task("Send invoice", "Work", Priority::High, DueDate::None)));
+        assert!(!query.matches(&task("Call client", "Work", Priority::High, date(30))));
+        assert!(query.matches(&task(
+            "Send invoice",
+            "Work/ClientA",
+            Priority::High,
+            date(30)
+        )));
+        assert!(!query.matches(&task("Send invoice", "Workshop", Priority::High, date(30))));
+
+        let mut done = task("Send invoice", "Work", Priority::High, date(30));
+        done.status = Status::Completed;
+        assert!(!query.matches(&done));
+    }
+
+    #[test]
+    fn test_parse_boolean_operators() {
+        let query = parse("(cat:Work OR cat:Home) -prio:low NOT due:none").unwrap();
+        assert_eq!(
+            query,
+            TaskQuery::And(vec![
+                TaskQuery::Or(vec![
+                    TaskQuery::Category("Work".to_string()),
+                    TaskQuery::Category("Home".to_string()),
+                ]),
+                TaskQuery::Priority(Comparison::Eq, Priority::Low).negate(),
+                TaskQuery::NoDueDate.negate(),
+            ])
+        );
+        assert_eq!(
+            parse("cat:\"Client A\" | tax").unwrap(),
+            TaskQuery::Or(vec![
+                TaskQuery::Category("Client A".to_string()),
+                TaskQuery::Text("tax".to_string()),
+            ])
+        );
+        assert_eq!(parse("  ").unwrap(), TaskQuery::All);
+        assert_eq!(
+            parse("due<=tomorrow").unwrap(),
+            TaskQuery::Due(
+                Comparison::Le,
+                NaiveDate::from_ymd_opt(2026, 10, 19).unwrap()
+            )
+        );
+    }
+
+    #[test]
+    fn test_parse_tags() {
+        let mut list = TodoList::new();
+        let id = list.add_task(NewTask {
+            description: "Call client".to_string(),
+            due_date: DueDate::None,
+            category: "Work".to_string(),
+            priority: Priority::Low,
+        });
+        list.add_tag(id, "urgent").unwrap();
+        list.add_tag(id, "phone").unwrap();
+        let task = list.get_task(id).unwrap();
+
+        assert!(parse("+urgent").unwrap().matches(task));
+        assert!(parse("tag:phone,Urgent").unwrap().matches(task));
+        assert!(!parse("tag:phone,email").unwrap().matches(task));
+        assert!(parse("anytag:phone,email").unwrap().matches(task));
+        assert!(!parse("tag!=urgent").unwrap().matches(task));
+        assert_eq!(
+            parse("anytag:a,b").unwrap(),
+            TaskQuery::any_tag(["a", "b"]).unwrap()
+        );
+    }
+
+    #[test]
+    fn test_parse_errors() {
+        for input in [
+            "prio>=urgent",
+            "colour:red",
+            "(cat:Work",
+            "cat:Work)",
+            "\"unterminated",
+            "cat>Work",
+            "OR",
+        ] {
+            assert!(parse(input).is_err(), "{input:?} should not parse");
+        }
+    }
+}
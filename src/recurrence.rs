@@ -0,0 +1,233 @@
+use crate::todo::TodoError;
+use chrono::{Datelike, Days, Months, NaiveDate, Weekday};
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::str::FromStr;
+
+type Result<T> = std::result::Result<T, TodoError>;
+
+// Frequency is how often a recurring task comes back
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub enum Frequency {
+    Daily,
+    // Weekly repeats on the given weekdays, or on the weekday of the due date if empty
+    Weekly(Vec<Weekday>),
+    // Monthly repeats on a day of the month, moved to the last day in shorter months
+    Monthly(u32),
+    // AfterCompletion repeats a number of days after the previous occurrence was completed
+    AfterCompletion(u32),
+}
+
+// RecurrenceEnd is when a recurring task stops spawning new occurrences
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub enum RecurrenceEnd {
+    Never,
+    // Until stops once the next due date would fall after the date
+    Until(NaiveDate),
+    // Count is the number of occurrences left, this one included
+    Count(u32),
+}
+
+// Recurrence is the rule a recurring task follows
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct Recurrence {
+    pub frequency: Frequency,
+    pub end: RecurrenceEnd,
+}
+
+impl Recurrence {
+    pub fn new(frequency: Frequency) -> Self {
+        Recurrence {
+            frequency,
+            end: RecurrenceEnd::Never,
+        }
+    }
+
+    // next returns the due date and rule of the occurrence following one due on `due`
+    // and completed on `completed_on`, or None when the series has ended
+    pub fn next(
+        &self,
+        due: Option<NaiveDate>,
+        completed_on: NaiveDate,
+    ) -> Option<(NaiveDate, Self)> {
+        let end = match self.end {
+            RecurrenceEnd::Count(remaining) if remaining <= 1 => return None,
+            RecurrenceEnd::Count(remaining) => RecurrenceEnd::Count(remaining - 1),
+            ref end => end.clone(),
+        };
+
+        let base = due.unwrap_or(completed_on);
+        let next = match &self.frequency {
+            Frequency::Daily => base.succ_opt()?,
+            Frequency::Weekly(days) if days.is_empty() => base.checked_add_days(Days::new(7))?,
+            Frequency::Weekly(days) => (1..=7)
+                .filter_map(|n| base.checked_add_days(Days::new(n)))
+                .find(|d| days.contains(&d.weekday()))?,
+            Frequency::Monthly(day) => next_monthly(base, *day)?,
+            Frequency::AfterCompletion(days) => {
+                completed_on.checked_add_days(Days::new((*days).into()))?
+            }
+        };
+
+        if let RecurrenceEnd::Until(until) = end {
+            if next > until {
+                return None;
+            }
+        }
+        Some((
+            next,
+            Recurrence {
+                frequency: self.frequency.clone(),
+                end,
+            },
+        ))
+    }
+}
+
+// next_monthly finds the first date after `base` that falls on `day` of its month
+fn next_monthly(base: NaiveDate, day: u32) -> Option<NaiveDate> {
+    let first = base.with_day(1)?;
+    (0..=2)
+        .filter_map(|n| first.checked_add_months(Months::new(n)))
+        .map(|month| {
+            let last_day = month.checked_add_months(Months::new(1))?.pred_opt()?.day();
+            month.with_day(day.clamp(1, last_day))
+        })
+        .find_map(|d| d.filter(|d| *d > base))
+}
+
+impl fmt::Display for Recurrence {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match &self.frequency {
+            Frequency::Daily => write!(f, "daily")?,
+            Frequency::Weekly(days) if days.is_empty() => write!(f, "weekly")?,
+            Frequency::Weekly(days) => {
+                let days: Vec<String> = days.iter().map(|d| d.to_string()).collect();
+                write!(f, "weekly on {}", days.join(", "))?
+            }
+            Frequency::Monthly(day) => write!(f, "monthly on day {}", day)?,
+            Frequency::AfterCompletion(days) => write!(f, "every {} days after completion", days)?,
+        }
+        match self.end {
+            RecurrenceEnd::Never => Ok(()),
+            RecurrenceEnd::Until(until) => write!(f, " until {}", until.format("%Y-%m-%d")),
+            RecurrenceEnd::Count(n) => write!(f, " ({} occurrences left)", n),
+        }
+    }
+}
+
+// Frequency parses from "daily", "weekly", "weekly mon,thu", "monthly 15" or
+// "every 3 days" (counted from completion)
+impl FromStr for Frequency {
+    type Err = TodoError;
+
+    fn from_str(s: &str) -> Result<Self> {
+        let input = s.trim().to_lowercase();
+        let (kind, rest) = input.split_once(' ').unwrap_or((&input, ""));
+        let rest = rest.trim();
+        let invalid = || TodoError::InvalidRecurrence(s.trim().to_string());
+
+        match kind {
+            "daily" if rest.is_empty() => Ok(Frequency::Daily),
+            "weekly" => {
+                let days = rest
+                    .split([',', ' '])
+                    .filter(|d| !d.is_empty())
+                    .map(|d| d.parse::<Weekday>().map_err(|_| invalid()))
+                    .collect::<Result<Vec<_>>>()?;
+                Ok(Frequency::Weekly(days))
+            }
+            "monthly" => match rest.parse() {
+                Ok(day @ 1..=31) => Ok(Frequency::Monthly(day)),
+                _ => Err(invalid()),
+            },
+            "every" => {
+                let days = rest.strip_suffix("days").or(rest.strip_suffix("day"));
+                match days.map(str::trim).map(str::parse) {
+                    Some(Ok(days @ 1..)) => Ok(Frequency::AfterCompletion(days)),
+                    _ => Err(invalid()),
+                }
+            }
+            _ => Err(invalid()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn ymd(y: i32, m: u32, d: u32) -> NaiveDate {
+        NaiveDate::from_ymd_opt(y, m, d).unwrap()
+    }
+
+    #[test]
+    fn test_next_due_dates() {
+        // 2024-05-10 is a Friday
+        let due = Some(ymd(2024, 5, 10));
+        let done = ymd(2024, 5, 12);
+        let next = |frequency| Recurrence::new(frequency).next(due, done).unwrap().0;
+
+        assert_eq!(next(Frequency::Daily), ymd(2024, 5, 11));
+        assert_eq!(next(Frequency::Weekly(vec![])), ymd(2024, 5, 17));
+        assert_eq!(
+            next(Frequency::Weekly(vec![Weekday::Mon, Weekday::Thu])),
+            ymd(2024, 5, 13)
+        );
+        assert_eq!(next(Frequency::Monthly(15)), ymd(2024, 5, 15));
+        assert_eq!(next(Frequency::Monthly(5)), ymd(2024, 6, 5));
+        assert_eq!(next(Frequency::AfterCompletion(3)), ymd(2024, 5, 15));
+    }
+
+    #[test]
+    fn test_monthly_clamps_to_short_months() {
+        let recurrence = Recurrence::new(Frequency::Monthly(31));
+        let (next, _) = recurrence
+            .next(Some(ymd(2024, 1, 31)), ymd(2024, 1, 31))
+            .unwrap();
+        assert_eq!(next, ymd(2024, 2, 29));
+    }
+
+    #[test]
+    fn test_series_end() {
+        let until = Recurrence {
+            frequency: Frequency::Daily,
+            end: RecurrenceEnd::Until(ymd(2024, 5, 11)),
+        };
+        let (next, rule) = until
+            .next(Some(ymd(2024, 5, 10)), ymd(2024, 5, 10))
+            .unwrap();
+        assert!(rule.next(Some(next), next).is_none());
+
+        let count = Recurrence {
+            frequency: Frequency::Daily,
+            end: RecurrenceEnd::Count(2),
+        };
+        let (next, rule) = count.next(None, ymd(2024, 5, 10)).unwrap();
+        assert_eq!(rule.end, RecurrenceEnd::Count(1));
+        assert!(rule.next(Some(next), next).is_none());
+    }
+
+    #[test]
+    fn test_parse_frequency() {
+        assert_eq!("daily".parse::<Frequency>().unwrap(), Frequency::Daily);
+        assert_eq!(
+            "Weekly mon, thu".parse::<Frequency>().unwrap(),
+            Frequency::Weekly(vec![Weekday::Mon, Weekday::Thu])
+        );
+        assert_eq!(
+            "monthly 15".parse::<Frequency>().unwrap(),
+            Frequency::Monthly(15)
+        );
+        assert_eq!(
+            "every 3 days".parse::<Frequency>().unwrap(),
+            Frequency::AfterCompletion(3)
+        );
+        for input in ["hourly", "monthly 32", "every 0 days", "weekly someday"] {
+            assert!(
+                input.parse::<Frequency>().is_err(),
+                "{input:?} should not parse"
+            );
+        }
+    }
+}
@@ -0,0 +1,235 @@
+use crate::todo::{Task, TodoError};
+use std::cmp::Ordering;
+use std::fmt;
+use std::str::FromStr;
+
+type Result<T> = std::result::Result<T, TodoError>;
+
+// SortField is a task attribute listings can be ordered by
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum SortField {
+    Id,
+    Due,
+    Priority,
+    Category,
+    Created,
+    Description,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum SortDirection {
+    Ascending,
+    Descending,
+}
+
+// SortKey is one field of a sort with its direction
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct SortKey {
+    pub field: SortField,
+    pub direction: SortDirection,
+}
+
+// TaskSort orders tasks by a list of keys, the first key deciding first. Tasks that compare
+// equal on every key are ordered by ID so listings are always stable
+#[derive(Clone, Debug, PartialEq)]
+pub struct TaskSort {
+    keys: Vec<SortKey>,
+}
+
+// The default sort puts the highest priority first, then the earliest due date
+impl Default for TaskSort {
+    fn default() -> Self {
+        TaskSort::by(SortField::Priority, SortDirection::Descending)
+            .then(SortField::Due, SortDirection::Ascending)
+    }
+}
+
+impl TaskSort {
+    pub fn by(field: SortField, direction: SortDirection) -> Self {
+        TaskSort {
+            keys: vec![SortKey { field, direction }],
+        }
+    }
+
+    pub fn then(mut self, field: SortField, direction: SortDirection) -> Self {
+        self.keys.push(SortKey { field, direction });
+        self
+    }
+
+    pub fn keys(&self) -> &[SortKey] {
+        &self.keys
+    }
+
+    // compare orders two tasks by the sort keys. Undated tasks come after dated ones when
+    // sorting by due date in either direction
+    pub fn compare(&self, a: &Task, b: &Task) -> Ordering {
+        self.keys
+            .iter()
+            .map(|key| {
+                let ordering = match key.field {
+                    SortField::Id => a.id.cmp(&b.id),
+                    SortField::Due => match (a.due_date.date(), b.due_date.date()) {
+                        (Some(a), Some(b)) => a.cmp(&b),
+                        (a, b) => return a.is_none().cmp(&b.is_none()),
+                    },
+                    SortField::Priority => a.priority.cmp(&b.priority),
+                    SortField::Category => {
+                        a.category.to_lowercase().cmp(&b.category.to_lowercase())
+                    }
+                    SortField::Created => a.created_at.cmp(&b.created_at),
+                    SortField::Description => a
+                        .description
+                        .to_lowercase()
+                        .cmp(&b.description.to_lowercase()),
+                };
+                match key.direction {
+                    SortDirection::Ascending => ordering,
+                    SortDirection::Descending => ordering.reverse(),
+                }
+            })
+            .find(|ordering| ordering.is_ne())
+            .unwrap_or_else(|| a.id.cmp(&b.id))
+    }
+
+    pub fn sort(&self, tasks: &mut [&Task]) {
+        tasks.sort_by(|a, b| self.compare(a, b));
+    }
+}
+
+impl FromStr for SortField {
+    type Err = TodoError;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s.trim().to_lowercase().as_str() {
+            "id" => Ok(SortField::Id),
+            "due" | "due_date" => Ok(SortField::Due),
+            "priority" | "prio" => Ok(SortField::Priority),
+            "category" | "cat" => Ok(SortField::Category),
+            "created" | "created_at" => Ok(SortField::Created),
+            "description" | "text" => Ok(SortField::Description),
+            _ => Err(TodoError::InvalidSort(s.trim().to_string())),
+        }
+    }
+}
+
+// TaskSort parses from comma-separated keys such as `priority:desc,due,id`, where a key is
+// ascending unless it ends in `:desc` or starts with `-`
+impl FromStr for TaskSort {
+    type Err = TodoError;
+
+    fn from_str(s: &str) -> Result<Self> {
+        let keys = s
+            .split(',')
+            .map(str::trim)
+            .filter(|key| !key.is_empty())
+            .map(|key| {
+                let (field, direction) = match key.split_once(':') {
+                    Some((field, "asc")) => (field, SortDirection::Ascending),
+                    Some((field, "desc")) => (field, SortDirection::Descending),
+                    Some(_) => return Err(TodoError::InvalidSort(key.to_string())),
+                    None => match key.strip_prefix('-') {
+                        Some(field) => (field, SortDirection::Descending),
+                        None => (key, SortDirection::Ascending),
+                    },
+                };
+                Ok(SortKey {
+                    field: field.parse()?,
+                    direction,
+                })
+            })
+            .collect::<Result<Vec<_>>>()?;
+
+        if keys.is_empty() {
+            return Err(TodoError::InvalidSort(s.to_string()));
+        }
+        Ok(TaskSort { keys })
+    }
+}
+
+impl fmt::Display for TaskSort {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let keys: Vec<String> = self
+            .keys
+            .iter()
+            .map(|key| {
+                let field = match key.field {
+                    SortField::Id => "id",
+                    SortField::Due => "due",
+                    SortField::Priority => "priority",
+                    SortField::Category => "category",
+                    SortField::Created => "created",
+                    SortField::Description => "description",
+                };
+                match key.direction {
+                    SortDirection::Ascending => field.to_string(),
+                    SortDirection::Descending => format!("{}:desc", field),
+                }
+            })
+            .collect();
+        write!(f, "{}", keys.join(","))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::todo::{DueDate, NewTask, Priority, TodoList};
+    use chrono::NaiveDate;
+
+    fn sample_list() -> TodoList {
+        let mut list = TodoList::new();
+        let date = |d| DueDate::On(NaiveDate::from_ymd_opt(2024, 5, d).unwrap());
+        for (description, category, due_date, priority) in [
+            ("b", "Work", date(3), Priority::Low),
+            ("C", "home", DueDate::None, Priority::High),
+            ("a", "Home", date(1), Priority::Low),
+        ] {
+            list.add_task(NewTask {
+                description: description.to_string(),
+                due_date,
+                category: category.to_string(),
+                priority,
+            });
+        }
+        list
+    }
+
+    fn sorted_ids(list: &TodoList, sort: &TaskSort) -> Vec<u32> {
+        let mut tasks = list.list_tasks(None);
+        sort.sort(&mut tasks);
+        tasks.iter().map(|task| task.id).collect()
+    }
+
+    #[test]
+    fn test_sort_keys() {
+        let list = sample_list();
+        let sort = |s: &str| s.parse::<TaskSort>().unwrap();
+
+        assert_eq!(sorted_ids(&list, &sort("description")), [3, 1, 2]);
+        assert_eq!(sorted_ids(&list, &sort("-id")), [3, 2, 1]);
+        assert_eq!(sorted_ids(&list, &sort("due")), [3, 1, 2]);
+        assert_eq!(sorted_ids(&list, &sort("due:desc")), [1, 3, 2]);
+        assert_eq!(
+            sorted_ids(&list, &sort("category,priority:desc")),
+            [2, 3, 1]
+        );
+        assert_eq!(sorted_ids(&list, &sort("priority")), [1, 3, 2]);
+        assert_eq!(sorted_ids(&list, &TaskSort::default()), [2, 3, 1]);
+    }
+
+    #[test]
+    fn test_parse_sort() {
+        assert_eq!(
+            "prio:desc, due".parse::<TaskSort>().unwrap(),
+            TaskSort::by(SortField::Priority, SortDirection::Descending)
+                .then(SortField::Due, SortDirection::Ascending)
+        );
+        assert_eq!(TaskSort::default().to_string(), "priority:desc,due");
+        for input in ["", "size", "due:sideways"] {
+            assert!(
+                input.parse::<TaskSort>().is_err(),
+                "{input:?} should not parse"
+            );
+        }
+    }
+}
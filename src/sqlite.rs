@@ -0,0 +1,564 @@
+use crate::migrations::{self, FORMAT_VERSION};
+use crate::query::{Comparison, TaskQuery};
+use crate::storage::Storage;
+use crate::todo::{normalize_category, Status, Task, TodoError, TodoList, CATEGORY_SEPARATOR};
+use rusqlite::types::Value;
+use rusqlite::{params, params_from_iter, Connection, OptionalExtension};
+use serde_json::json;
+use std::collections::{BTreeSet, HashMap};
+use std::fs::File;
+use std::io::{self, Read};
+use std::path::{Path, PathBuf};
+
+type Result<T> = std::result::Result<T, TodoError>;
+
+// DATABASE_EXTENSIONS are the file extensions that make a new list file a database
+pub const DATABASE_EXTENSIONS: [&str; 3] = ["db", "sqlite", "sqlite3"];
+
+// HEADER is how every SQLite database file starts
+const HEADER: &[u8; 16] = b"SQLite format 3\0";
+
+// SCHEMA creates the tables of a list database. Each task is kept whole as JSON in `data`,
+// next to copies of the fields filters look at, which are indexed. `metadata` holds the
+// format version of that JSON, the next task ID and the undo history when it is persisted
+const SCHEMA: &str = "
+    PRAGMA foreign_keys = ON;
+    CREATE TABLE IF NOT EXISTS metadata (
+        key TEXT PRIMARY KEY,
+        value TEXT NOT NULL
+    );
+    CREATE TABLE IF NOT EXISTS categories (
+        id INTEGER PRIMARY KEY,
+        name TEXT NOT NULL,
+        -- key is the lowercased name, which categories are matched by
+        key TEXT NOT NULL UNIQUE
+    );
+    CREATE TABLE IF NOT EXISTS tasks (
+        id INTEGER PRIMARY KEY,
+        description TEXT NOT NULL,
+        -- search is the lowercased description, which text filters look in
+        search TEXT NOT NULL,
+        due_date TEXT,
+        category_id INTEGER NOT NULL REFERENCES categories (id),
+        priority INTEGER NOT NULL,
+        status TEXT NOT NULL,
+        data TEXT NOT NULL
+    );
+    CREATE TABLE IF NOT EXISTS task_tags (
+        task_id INTEGER NOT NULL REFERENCES tasks (id) ON DELETE CASCADE,
+        tag TEXT NOT NULL,
+        PRIMARY KEY (task_id, tag)
+    );
+    CREATE INDEX IF NOT EXISTS tasks_due_date ON tasks (due_date);
+    CREATE INDEX IF NOT EXISTS tasks_category ON tasks (category_id);
+    CREATE INDEX IF NOT EXISTS tasks_status ON tasks (status);
+    CREATE INDEX IF NOT EXISTS task_tags_tag ON task_tags (tag);
+";
+
+// Database stores a list in an SQLite database, one row per task. Saving writes only the
+// tasks that changed since the list was loaded, in a single transaction, and filters run as
+// SQL against the indexed columns rather than over the whole list
+pub struct Database {
+    path: PathBuf,
+    connection: Connection,
+    // saved holds the tasks as last loaded or saved, which saves are compared against. Until
+    // the list is loaded a save rewrites every task
+    saved: Option<HashMap<u32, Task>>,
+}
+
+impl Database {
+    // open opens the database at `path`, creating it if it does not exist yet. A database
+    // written in an older format is migrated on the spot
+    pub fn open(path: impl AsRef<Path>) -> Result<Database> {
+        let path = path.as_ref().to_path_buf();
+        let connection = Connection::open(&path)?;
+        connection.execute_batch(SCHEMA)?;
+        let mut database = Database {
+            path,
+            connection,
+            saved: None,
+        };
+
+        let version = database.format_version()?;
+        if version > FORMAT_VERSION {
+            return Err(TodoError::UnsupportedVersion {
+                found: version,
+                supported: FORMAT_VERSION,
+            });
+        }
+        if version < FORMAT_VERSION {
+            let todo_list = database.load()?;
+            database.saved = None;
+            database.save(&todo_list)?;
+        }
+        Ok(database)
+    }
+
+    // format_version is the version of the format the tasks are stored in. A new database
+    // has the current one
+    fn format_version(&self) -> Result<u32> {
+        match get_metadata(&self.connection, "format_version")? {
+            Some(version) => version.parse().map_err(|_| {
+                TodoError::InvalidSaveFile(format!("invalid format version {}", version))
+            }),
+            None => {
+                set_metadata(&self.connection, "format_version", FORMAT_VERSION)?;
+                Ok(FORMAT_VERSION)
+            }
+        }
+    }
+}
+
+impl Storage for Database {
+    fn describe(&self) -> String {
+        format!("database {}", self.path.display())
+    }
+
+    fn path(&self) -> Option<&Path> {
+        Some(&self.path)
+    }
+
+    // load reads the tasks back into a saved list, so they go through the same migrations as
+    // list files
+    fn load(&mut self) -> Result<TodoList> {
+        let mut tasks = serde_json::Map::new();
+        let mut statement = self.connection.prepare("SELECT id, data FROM tasks")?;
+        let mut rows = statement.query([])?;
+        while let Some(row) = rows.next()? {
+            let id: u32 = row.get(0)?;
+            let data: String = row.get(1)?;
+            tasks.insert(id.to_string(), serde_json::from_str(&data)?);
+        }
+        let next_id: u32 = get_metadata(&self.connection, "next_id")?
+            .and_then(|next_id| next_id.parse().ok())
+            .unwrap_or(1);
+        let mut list = json!({ "tasks": tasks, "next_id": next_id });
+        if let Some(history) = get_metadata(&self.connection, "history")? {
+            list["history"] = serde_json::from_str(&history)?;
+        }
+
+        let version = self.format_version()?;
+        let todo_list = migrations::from_value(json!({ "version": version, "list": list }))?;
+        self.saved = Some(
+            todo_list
+                .list_tasks(None)
+                .into_iter()
+                .map(|task| (task.id, task.clone()))
+                .collect(),
+        );
+        Ok(todo_list)
+    }
+
+    fn save(&mut self, todo_list: &TodoList) -> Result<()> {
+        let transaction = self.connection.transaction()?;
+        let tasks = todo_list.list_tasks(None);
+        match &self.saved {
+            Some(saved) => {
+                for task in &tasks {
+                    if saved.get(&task.id) != Some(*task) {
+                        write_task(&transaction, task)?;
+                    }
+                }
+                for id in saved.keys() {
+                    if todo_list.get_task(*id).is_none() {
+                        transaction.execute("DELETE FROM tasks WHERE id = ?1", [id])?;
+                    }
+                }
+            }
+            None => {
+                transaction.execute("DELETE FROM tasks", [])?;
+                for task in &tasks {
+                    write_task(&transaction, task)?;
+                }
+            }
+        }
+        set_metadata(&transaction, "next_id", todo_list.next_id())?;
+        if todo_list.history().is_transient() {
+            transaction.execute("DELETE FROM metadata WHERE key = 'history'", [])?;
+        } else {
+            let history = serde_json::to_string(todo_list.history())?;
+            set_metadata(&transaction, "history", history)?;
+        }
+        set_metadata(&transaction, "format_version", FORMAT_VERSION)?;
+        drop_unused_categories(&transaction)?;
+        transaction.commit()?;
+
+        self.saved = Some(
+            tasks
+                .into_iter()
+                .map(|task| (task.id, task.clone()))
+                .collect(),
+        );
+        Ok(())
+    }
+
+    fn upsert_task(&mut self, task: &Task) -> Result<()> {
+        let transaction = self.connection.transaction()?;
+        write_task(&transaction, task)?;
+        transaction.execute(
+            "INSERT INTO metadata (key, value) VALUES ('next_id', ?1)
+             ON CONFLICT (key) DO UPDATE SET value = MAX(CAST(value AS INTEGER), ?1)",
+            [task.id + 1],
+        )?;
+        drop_unused_categories(&transaction)?;
+        transaction.commit()?;
+
+        if let Some(saved) = &mut self.saved {
+            saved.insert(task.id, task.clone());
+        }
+        Ok(())
+    }
+
+    fn delete_task(&mut self, id: u32) -> Result<()> {
+        let transaction = self.connection.transaction()?;
+        if transaction.execute("DELETE FROM tasks WHERE id = ?1", [id])? == 0 {
+            return Err(TodoError::TaskNotFound);
+        }
+        drop_unused_categories(&transaction)?;
+        transaction.commit()?;
+
+        if let Some(saved) = &mut self.saved {
+            saved.remove(&id);
+        }
+        Ok(())
+    }
+
+    fn find_tasks(&mut self, query: &TaskQuery) -> Result<Vec<Task>> {
+        let mut params = Vec::new();
+        let sql = format!(
+            "SELECT t.data FROM tasks t JOIN categories c ON c.id = t.category_id WHERE {}",
+            where_clause(query, &mut params)
+        );
+        let mut statement = self.connection.prepare(&sql)?;
+        let mut rows = statement.query(params_from_iter(params))?;
+        let mut tasks = Vec::new();
+        while let Some(row) = rows.next()? {
+            let data: String = row.get(0)?;
+            tasks.push(serde_json::from_str(&data)?);
+        }
+        Ok(tasks)
+    }
+
+    // compact also rebuilds the database file, returning the space of deleted tasks
+    fn compact(&mut self, todo_list: &TodoList) -> Result<()> {
+        self.save(todo_list)?;
+        self.connection.execute_batch("VACUUM")?;
+        Ok(())
+    }
+}
+
+// is_database reports whether the file at `path` is an SQLite database
+pub fn is_database(path: impl AsRef<Path>) -> io::Result<bool> {
+    let mut header = [0; 16];
+    match File::open(path)?.read_exact(&mut header) {
+        Ok(()) => Ok(&header == HEADER),
+        Err(e) if e.kind() == io::ErrorKind::UnexpectedEof => Ok(false),
+        Err(e) => Err(e),
+    }
+}
+
+// has_database_extension reports whether `path` is named like a database, see
+// DATABASE_EXTENSIONS
+pub fn has_database_extension(path: impl AsRef<Path>) -> bool {
+    path.as_ref()
+        .extension()
+        .and_then(|extension| extension.to_str())
+        .is_some_and(|extension| DATABASE_EXTENSIONS.contains(&extension.to_lowercase().as_str()))
+}
+
+// write_task stores a task, replacing the stored version and its tags if there is one
+fn write_task(connection: &Connection, task: &Task) -> Result<()> {
+    let category = normalize_category(&task.category);
+    connection.execute(
+        "INSERT INTO categories (name, key) VALUES (?1, ?2) ON CONFLICT (key) DO NOTHING",
+        params![category, category.to_lowercase()],
+    )?;
+    connection.execute(
+        "INSERT INTO tasks (id, description, search, due_date, category_id, priority, status, data)
+         VALUES (?1, ?2, ?3, ?4, (SELECT id FROM categories WHERE key = ?5), ?6, ?7, ?8)
+         ON CONFLICT (id) DO UPDATE SET
+             description = excluded.description,
+             search = excluded.search,
+             due_date = excluded.due_date,
+             category_id = excluded.category_id,
+             priority = excluded.priority,
+             status = excluded.status,
+             data = excluded.data",
+        params![
+            task.id,
+            task.description,
+            task.description.to_lowercase(),
+            task.due_date.date().map(|date| date.to_string()),
+            category.to_lowercase(),
+            task.priority as i64,
+            status_key(task.status),
+            serde_json::to_string(task)?,
+        ],
+    )?;
+    connection.execute("DELETE FROM task_tags WHERE task_id = ?1", [task.id])?;
+    for tag in &task.tags {
+        connection.execute(
+            "INSERT INTO task_tags (task_id, tag) VALUES (?1, ?2)",
+            params![task.id, tag],
+        )?;
+    }
+    Ok(())
+}
+
+// drop_unused_categories removes categories no task is in any more
+fn drop_unused_categories(connection: &Connection) -> Result<()> {
+    connection.execute(
+        "DELETE FROM categories WHERE id NOT IN (SELECT category_id FROM tasks)",
+        [],
+    )?;
+    Ok(())
+}
+
+fn get_metadata(connection: &Connection, key: &str) -> Result<Option<String>> {
+    Ok(connection
+        .query_row("SELECT value FROM metadata WHERE key = ?1", [key], |row| {
+            row.get(0)
+        })
+        .optional()?)
+}
+
+fn set_metadata(connection: &Connection, key: &str, value: impl ToString) -> Result<()> {
+    connection.execute(
+        "INSERT INTO metadata (key, value) VALUES (?1, ?2)
+         ON CONFLICT (key) DO UPDATE SET value = excluded.value",
+        params![key, value.to_string()],
+    )?;
+    Ok(())
+}
+
+fn status_key(status: Status) -> &'static str {
+    match status {
+        Status::NotStarted => "not_started",
+        Status::InProgress => "in_progress",
+        Status::Completed => "completed",
+    }
+}
+
+// where_clause translates a filter into an SQL condition on `tasks t` joined with
+// `categories c`, adding the values it compares against to `params`. Every condition is
+// either true or false, never NULL, so negating one matches what TaskQuery::matches does
+fn where_clause(query: &TaskQuery, params: &mut Vec<Value>) -> String {
+    match query {
+        TaskQuery::All => "1".to_string(),
+        TaskQuery::Category(category) => {
+            // The category itself, or any category below it
+            let key = normalize_category(category).to_lowercase();
+            let prefix = format!("{}{}", key, CATEGORY_SEPARATOR);
+            params.push(Value::Text(key));
+            params.push(Value::Integer(prefix.chars().count() as i64));
+            params.push(Value::Text(prefix));
+            "(c.key = ? OR substr(c.key, 1, ?) = ?)".to_string()
+        }
+        TaskQuery::Priority(op, priority) => {
+            params.push(Value::Integer(*priority as i64));
+            format!("t.priority {} ?", sql_operator(*op))
+        }
+        TaskQuery::Due(op, date) => {
+            params.push(Value::Text(date.to_string()));
+            format!(
+                "(t.due_date IS NOT NULL AND t.due_date {} ?)",
+                sql_operator(*op)
+            )
+        }
+        TaskQuery::NoDueDate => "t.due_date IS NULL".to_string(),
+        TaskQuery::Status(status) => {
+            params.push(Value::Text(status_key(*status).to_string()));
+            "t.status = ?".to_string()
+        }
+        TaskQuery::AnyTag(tags) | TaskQuery::AllTags(tags) => {
+            let tags: BTreeSet<&String> = tags.iter().collect();
+            if tags.is_empty() {
+                return if matches!(query, TaskQuery::AnyTag(_)) {
+                    "0".to_string()
+                } else {
+                    "1".to_string()
+                };
+            }
+            let placeholders = vec!["?"; tags.len()].join(", ");
+            let count = tags.len();
+            params.extend(tags.into_iter().map(|tag| Value::Text(tag.clone())));
+            let matching = format!(
+                "SELECT COUNT(*) FROM task_tags WHERE task_id = t.id AND tag IN ({})",
+                placeholders
+            );
+            if matches!(query, TaskQuery::AnyTag(_)) {
+                format!("({}) > 0", matching)
+            } else {
+                format!("({}) = {}", matching, count)
+            }
+        }
+        TaskQuery::Text(text) => {
+            params.push(Value::Text(text.to_lowercase()));
+            "instr(t.search, ?) > 0".to_string()
+        }
+        TaskQuery::And(queries) => join_clauses(queries, " AND ", "1", params),
+        TaskQuery::Or(queries) => join_clauses(queries, " OR ", "0", params),
+        TaskQuery::Not(query) => format!("NOT ({})", where_clause(query, params)),
+    }
+}
+
+fn join_clauses(
+    queries: &[TaskQuery],
+    separator: &str,
+    empty: &str,
+    params: &mut Vec<Value>,
+) -> String {
+    if queries.is_empty() {
+        return empty.to_string();
+    }
+    let clauses: Vec<String> = queries
+        .iter()
+        .map(|query| format!("({})", where_clause(query, params)))
+        .collect();
+    clauses.join(separator)
+}
+
+fn sql_operator(op: Comparison) -> &'static str {
+    match op {
+        Comparison::Eq => "=",
+        Comparison::Ne => "<>",
+        Comparison::Lt => "<",
+        Comparison::Le => "<=",
+        Comparison::Gt => ">",
+        Comparison::Ge => ">=",
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::dates::DateParser;
+    use crate::todo::{DueDate, NewTask, Priority};
+    use chrono::NaiveDate;
+
+    fn temp_path(name: &str) -> PathBuf {
+        let path =
+            std::env::temp_dir().join(format!("todo_sqlite_{}_{}.db", name, std::process::id()));
+        let _ = std::fs::remove_file(&path);
+        path
+    }
+
+    fn date(day: u32) -> NaiveDate {
+        NaiveDate::from_ymd_opt(2026, 11, day).unwrap()
+    }
+
+    fn sample_list() -> TodoList {
+        let mut todo_list = TodoList::new();
+        let tasks = [
+            (
+                "Write invoice +billing",
+                DueDate::On(date(2)),
+                "Work/ClientA",
+                Priority::High,
+            ),
+            (
+                "Call Ölaf +urgent +billing",
+                DueDate::Before(date(5)),
+                "work",
+                Priority::Medium,
+            ),
+            ("Water plants", DueDate::None, "Home", Priority::Low),
+            (
+                "Plan trip +urgent",
+                DueDate::On(date(20)),
+                "Home/Travel",
+                Priority::Low,
+            ),
+            ("Workout", DueDate::None, "Workshop", Priority::Medium),
+        ];
+        for (description, due_date, category, priority) in tasks {
+            let (description, tags) = description.split_once(" +").unwrap_or((description, ""));
+            let id = todo_list.add_task(NewTask {
+                description: description.to_string(),
+                due_date,
+                category: category.to_string(),
+                priority,
+            });
+            for tag in tags.split(" +").filter(|tag| !tag.is_empty()) {
+                todo_list.add_tag(id, tag).unwrap();
+            }
+        }
+        todo_list.complete_task(3).unwrap();
+        todo_list
+    }
+
+    fn ids(tasks: impl IntoIterator<Item = Task>) -> Vec<u32> {
+        let mut ids: Vec<u32> = tasks.into_iter().map(|task| task.id).collect();
+        ids.sort();
+        ids
+    }
+
+    #[test]
+    fn test_filters_match_the_list() {
+        let path = temp_path("filters");
+        let todo_list = sample_list();
+        let mut database = Database::open(&path).unwrap();
+        database.save(&todo_list).unwrap();
+
+        for filter in [
+            "",
+            "cat:work",
+            "cat:Work/ClientA",
+            "cat:home -cat:home/travel",
+            "prio>=medium",
+            "prio!=low",
+            "due<2026-11-05",
+            "NOT due>=2026-11-05",
+            "due:none",
+            "status:open",
+            "status:completed OR +urgent",
+            "+urgent +billing",
+            "anytag:urgent,billing",
+            "NOT tag:billing",
+            "\"öLAF\"",
+            "(cat:home OR prio:high) -status:completed",
+        ] {
+            let query = TaskQuery::parse_with(filter, &DateParser::default(), date(1)).unwrap();
+            let expected: Vec<u32> = todo_list.query(&query).iter().map(|t| t.id).collect();
+            let found = ids(database.find_tasks(&query).unwrap());
+            let mut expected = expected;
+            expected.sort();
+            assert_eq!(found, expected, "filter {:?}", filter);
+        }
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn test_saves_and_reopens() {
+        let path = temp_path("reopen");
+        let mut todo_list = sample_list();
+        todo_list.history_mut().set_persisted(true);
+        let mut database = Database::open(&path).unwrap();
+        database.save(&todo_list).unwrap();
+
+        todo_list.start_task(1).unwrap();
+        todo_list.remove_task(2).unwrap();
+        database.save(&todo_list).unwrap();
+        drop(database);
+
+        let mut reopened = Database::open(&path).unwrap();
+        let mut loaded = reopened.load().unwrap();
+        assert_eq!(loaded.get_task(1), todo_list.get_task(1));
+        assert!(loaded.get_task(2).is_none());
+        assert_eq!(loaded.next_id(), 6);
+        // The persisted history comes back with the list
+        loaded.undo().unwrap();
+        assert_eq!(loaded.get_task(2).unwrap().description, "Call Ölaf");
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn test_detects_databases() {
+        let path = temp_path("detect");
+        assert!(has_database_extension(&path));
+        assert!(!has_database_extension("todo.json"));
+        Database::open(&path).unwrap();
+        assert!(is_database(&path).unwrap());
+        std::fs::remove_file(&path).unwrap();
+    }
+}
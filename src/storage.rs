@@ -0,0 +1,314 @@
+use crate::fileio::ListLock;
+use crate::journal::{self, Journal};
+use crate::query::TaskQuery;
+use crate::sqlite::{self, Database};
+use crate::todo::{Task, TodoError, TodoList};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+type Result<T> = std::result::Result<T, TodoError>;
+
+// Storage is somewhere a list is kept between sessions. Besides loading and saving the whole
+// list, a backend can store single task changes, which backends that keep tasks apart write
+// without touching the rest of the list. Those backends' save already writes only the tasks
+// that changed since the last load or save, so code holding the whole list saves it; the
+// single task calls are for changes made without loading the list
+pub trait Storage {
+    // describe names where the list is kept, for messages
+    fn describe(&self) -> String;
+
+    // path is the file the list is kept in, for backends that keep it in one
+    fn path(&self) -> Option<&Path> {
+        None
+    }
+
+    fn load(&mut self) -> Result<TodoList>;
+
+    fn save(&mut self, todo_list: &TodoList) -> Result<()>;
+
+    // upsert_task stores a new task or the new version of an existing one
+    fn upsert_task(&mut self, task: &Task) -> Result<()>;
+
+    // delete_task removes a stored task, failing with TaskNotFound if there is none
+    fn delete_task(&mut self, id: u32) -> Result<()>;
+
+    // find_tasks lists the stored tasks matching a filter, in no particular order. Backends
+    // that index their tasks answer it without loading the whole list
+    fn find_tasks(&mut self, query: &TaskQuery) -> Result<Vec<Task>> {
+        Ok(self.load()?.query(query).into_iter().cloned().collect())
+    }
+
+    // compact stores the list in as little space as the backend can, dropping any history it
+    // keeps. Backends without history just save
+    fn compact(&mut self, todo_list: &TodoList) -> Result<()> {
+        self.save(todo_list)
+    }
+}
+
+// JsonFile keeps a list as a JSON snapshot, rewriting the whole file for every change, see
+// TodoList::save_to_file_with_backups
+#[derive(Debug)]
+pub struct JsonFile {
+    path: PathBuf,
+    backups: usize,
+}
+
+impl JsonFile {
+    pub fn new(path: impl AsRef<Path>, backups: usize) -> Self {
+        JsonFile {
+            path: path.as_ref().to_path_buf(),
+            backups,
+        }
+    }
+}
+
+impl Storage for JsonFile {
+    fn describe(&self) -> String {
+        self.path.display().to_string()
+    }
+
+    fn path(&self) -> Option<&Path> {
+        Some(&self.path)
+    }
+
+    // load starts an empty list when the file does not exist yet
+    fn load(&mut self) -> Result<TodoList> {
+        if self.path.exists() {
+            TodoList::load_from_file(&self.path.to_string_lossy())
+        } else {
+            Ok(TodoList::new())
+        }
+    }
+
+    fn save(&mut self, todo_list: &TodoList) -> Result<()> {
+        todo_list.save_to_file_with_backups(&self.path.to_string_lossy(), self.backups)
+    }
+
+    fn upsert_task(&mut self, task: &Task) -> Result<()> {
+        let mut todo_list = self.load()?;
+        todo_list.put_task(task.clone());
+        self.save(&todo_list)
+    }
+
+    fn delete_task(&mut self, id: u32) -> Result<()> {
+        let mut todo_list = self.load()?;
+        todo_list.discard_task(id).ok_or(TodoError::TaskNotFound)?;
+        self.save(&todo_list)
+    }
+}
+
+// Memory keeps a list in memory only, for tests and for lists that are never saved
+#[derive(Clone, Debug, Default)]
+pub struct Memory {
+    todo_list: TodoList,
+}
+
+impl Memory {
+    pub fn new(todo_list: TodoList) -> Self {
+        Memory { todo_list }
+    }
+}
+
+impl Storage for Memory {
+    fn describe(&self) -> String {
+        "memory".to_string()
+    }
+
+    fn load(&mut self) -> Result<TodoList> {
+        Ok(self.todo_list.clone())
+    }
+
+    fn save(&mut self, todo_list: &TodoList) -> Result<()> {
+        self.todo_list = todo_list.clone();
+        Ok(())
+    }
+
+    fn upsert_task(&mut self, task: &Task) -> Result<()> {
+        self.todo_list.put_task(task.clone());
+        Ok(())
+    }
+
+    fn delete_task(&mut self, id: u32) -> Result<()> {
+        self.todo_list
+            .discard_task(id)
+            .map(|_| ())
+            .ok_or(TodoError::TaskNotFound)
+    }
+
+    fn find_tasks(&mut self, query: &TaskQuery) -> Result<Vec<Task>> {
+        Ok(self.todo_list.query(query).into_iter().cloned().collect())
+    }
+}
+
+// Locked holds the lock on a list file for as long as another backend uses the file
+pub struct Locked<S> {
+    storage: S,
+    _lock: ListLock,
+}
+
+impl<S: Storage> Locked<S> {
+    pub fn new(storage: S, lock: ListLock) -> Self {
+        Locked {
+            storage,
+            _lock: lock,
+        }
+    }
+}
+
+impl<S: Storage> Storage for Locked<S> {
+    fn describe(&self) -> String {
+        self.storage.describe()
+    }
+
+    fn path(&self) -> Option<&Path> {
+        self.storage.path()
+    }
+
+    fn load(&mut self) -> Result<TodoList> {
+        self.storage.load()
+    }
+
+    fn save(&mut self, todo_list: &TodoList) -> Result<()> {
+        self.storage.save(todo_list)
+    }
+
+    fn upsert_task(&mut self, task: &Task) -> Result<()> {
+        self.storage.upsert_task(task)
+    }
+
+    fn delete_task(&mut self, id: u32) -> Result<()> {
+        self.storage.delete_task(id)
+    }
+
+    fn find_tasks(&mut self, query: &TaskQuery) -> Result<Vec<Task>> {
+        self.storage.find_tasks(query)
+    }
+
+    fn compact(&mut self, todo_list: &TodoList) -> Result<()> {
+        self.storage.compact(todo_list)
+    }
+}
+
+// open_file locks the list file at `path` and picks the backend for it: a database when the
+// file holds one or a new file is named like one (see sqlite::DATABASE_EXTENSIONS), a journal
+// when asked for one or when the file already holds one, and a JSON snapshot otherwise. A
+// snapshot file opened as a journal becomes the journal's first entry
+pub fn open_file(
+    path: impl AsRef<Path>,
+    use_journal: bool,
+    backups: usize,
+) -> Result<Box<dyn Storage>> {
+    let path = path.as_ref();
+    let lock = ListLock::acquire(path)?;
+    let has_tasks = path.exists() && fs::metadata(path)?.len() > 0;
+    if has_tasks && sqlite::is_database(path)? || !has_tasks && sqlite::has_database_extension(path)
+    {
+        return Ok(Box::new(Locked::new(Database::open(path)?, lock)));
+    }
+    if has_tasks && journal::is_journal(path)? || !has_tasks && use_journal {
+        let (mut journal, _) = Journal::open(path)?;
+        journal.set_backups(backups);
+        return Ok(Box::new(Locked::new(journal, lock)));
+    }
+    let mut json_file = JsonFile::new(path, backups);
+    if use_journal {
+        let todo_list = json_file.load()?;
+        let journal = Journal::create(path, &todo_list, backups)?;
+        return Ok(Box::new(Locked::new(journal, lock)));
+    }
+    Ok(Box::new(Locked::new(json_file, lock)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::todo::{DueDate, NewTask, Priority};
+
+    fn sample_list() -> TodoList {
+        let mut todo_list = TodoList::new();
+        for description in ["One", "Two"] {
+            todo_list.add_task(NewTask {
+                description: description.to_string(),
+                due_date: DueDate::None,
+                category: "Work".to_string(),
+                priority: Priority::Low,
+            });
+        }
+        todo_list
+    }
+
+    // exercise runs the same steps against any backend, which must end up holding task 1
+    // renamed and task 2 gone
+    fn exercise(storage: &mut dyn Storage) {
+        let todo_list = sample_list();
+        storage.save(&todo_list).unwrap();
+
+        let mut task = todo_list.get_task(1).unwrap().clone();
+        task.description = "One, renamed".to_string();
+        storage.upsert_task(&task).unwrap();
+        storage.delete_task(2).unwrap();
+        assert!(matches!(
+            storage.delete_task(2),
+            Err(TodoError::TaskNotFound)
+        ));
+
+        let loaded = storage.load().unwrap();
+        assert_eq!(loaded.get_task(1).unwrap().description, "One, renamed");
+        assert!(loaded.get_task(2).is_none());
+        assert_eq!(loaded.next_id(), 3);
+    }
+
+    fn temp_path(name: &str) -> PathBuf {
+        let path =
+            std::env::temp_dir().join(format!("todo_storage_{}_{}.json", name, std::process::id()));
+        let _ = fs::remove_file(&path);
+        path
+    }
+
+    #[test]
+    fn test_memory_storage() {
+        exercise(&mut Memory::default());
+    }
+
+    #[test]
+    fn test_json_file_storage() {
+        let path = temp_path("json");
+        exercise(&mut JsonFile::new(&path, 0));
+        fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn test_journal_storage() {
+        let path = temp_path("journal");
+        let mut storage = open_file(&path, true, 0).unwrap();
+        exercise(storage.as_mut());
+        // Saving the list wrote two tasks, then one line each for the upsert and the delete
+        assert_eq!(fs::read_to_string(&path).unwrap().lines().count(), 4);
+        assert!(matches!(
+            open_file(&path, false, 0),
+            Err(TodoError::ListLocked { .. })
+        ));
+        drop(storage);
+
+        let mut reopened = open_file(&path, false, 0).unwrap();
+        assert!(reopened.describe().starts_with("journal"));
+        assert_eq!(reopened.load().unwrap().list_tasks(None).len(), 1);
+        fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn test_database_storage() {
+        let path = temp_path("database").with_extension("db");
+        let _ = fs::remove_file(&path);
+        let mut storage = open_file(&path, false, 0).unwrap();
+        exercise(storage.as_mut());
+        let found = storage.find_tasks(&TaskQuery::Text("renamed".to_string()));
+        assert_eq!(found.unwrap().len(), 1);
+        drop(storage);
+
+        let mut reopened = open_file(&path, false, 0).unwrap();
+        assert!(reopened.describe().starts_with("database"));
+        assert_eq!(reopened.load().unwrap().list_tasks(None).len(), 1);
+        fs::remove_file(&path).unwrap();
+    }
+}
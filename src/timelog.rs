@@ -0,0 +1,278 @@
+use crate::todo::{Task, TodoError, TodoList};
+use chrono::{DateTime, Duration, Local, NaiveDate, Utc};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fmt;
+use std::str::FromStr;
+
+type Result<T> = std::result::Result<T, TodoError>;
+
+// TimeEntry is a stretch of time spent on a task. An entry without a stop time is the
+// running timer
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct TimeEntry {
+    pub started_at: DateTime<Utc>,
+    #[serde(default)]
+    pub stopped_at: Option<DateTime<Utc>>,
+}
+
+impl TimeEntry {
+    // duration is the length of the entry, counting a running timer up to `now`
+    pub fn duration(&self, now: DateTime<Utc>) -> Duration {
+        self.stopped_at.unwrap_or(now) - self.started_at
+    }
+
+    // date is the local day the entry started on, which reports file it under
+    pub fn date(&self) -> NaiveDate {
+        self.started_at.with_timezone(&Local).date_naive()
+    }
+}
+
+// ReportGroup is what a time report totals by
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum ReportGroup {
+    Task,
+    Category,
+    Day,
+}
+
+impl FromStr for ReportGroup {
+    type Err = TodoError;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s.trim().to_lowercase().as_str() {
+            "task" | "tasks" => Ok(ReportGroup::Task),
+            "category" | "categories" | "cat" => Ok(ReportGroup::Category),
+            "day" | "days" | "date" => Ok(ReportGroup::Day),
+            _ => Err(TodoError::InvalidReport(s.trim().to_string())),
+        }
+    }
+}
+
+// ReportRow is the time logged against one task, category or day
+#[derive(Clone, Debug, PartialEq)]
+pub struct ReportRow {
+    // task is the task ID when the report is grouped by task
+    pub task: Option<u32>,
+    pub label: String,
+    pub duration: Duration,
+}
+
+// TimeReport totals the time entries that started within a date range
+#[derive(Clone, Debug, PartialEq)]
+pub struct TimeReport {
+    pub group: ReportGroup,
+    pub rows: Vec<ReportRow>,
+}
+
+impl TimeReport {
+    // new builds a report over the entries that started between `from` and `to`, both
+    // included, with a running timer counted up to `now`. Rows are ordered by task ID,
+    // category or day
+    pub fn new(
+        todo_list: &TodoList,
+        group: ReportGroup,
+        from: Option<NaiveDate>,
+        to: Option<NaiveDate>,
+        now: DateTime<Utc>,
+    ) -> Self {
+        // Keyed so rows come out by task ID, or by category or day name
+        let mut totals: BTreeMap<(Option<u32>, String), (String, Duration)> = BTreeMap::new();
+        for task in todo_list.list_tasks(None) {
+            for entry in &task.time_entries {
+                let date = entry.date();
+                if from.is_some_and(|from| date < from) || to.is_some_and(|to| date > to) {
+                    continue;
+                }
+                let (key, label) = match group {
+                    ReportGroup::Task => ((Some(task.id), String::new()), task.description.clone()),
+                    ReportGroup::Category => {
+                        ((None, task.category.to_lowercase()), task.category.clone())
+                    }
+                    ReportGroup::Day => {
+                        let day = date.format("%Y-%m-%d").to_string();
+                        ((None, day.clone()), day)
+                    }
+                };
+                totals
+                    .entry(key)
+                    .or_insert_with(|| (label, Duration::zero()))
+                    .1 += entry.duration(now);
+            }
+        }
+
+        let rows = totals
+            .into_iter()
+            .map(|((task, _), (label, duration))| ReportRow {
+                task,
+                label,
+                duration,
+            })
+            .collect();
+        TimeReport { group, rows }
+    }
+
+    pub fn total(&self) -> Duration {
+        self.rows.iter().map(|row| row.duration).sum()
+    }
+
+    // to_csv renders the report as CSV with a header row and the time in decimal hours, as
+    // billing tools expect
+    pub fn to_csv(&self) -> String {
+        let header = match self.group {
+            ReportGroup::Task => "task_id,description,hours",
+            ReportGroup::Category => "category,hours",
+            ReportGroup::Day => "date,hours",
+        };
+        let mut csv = format!("{}\n", header);
+        for row in &self.rows {
+            if let Some(id) = row.task {
+                csv.push_str(&format!("{},", id));
+            }
+            csv.push_str(&format!(
+                "{},{:.2}\n",
+                csv_field(&row.label),
+                row.duration.num_seconds() as f64 / 3600.0
+            ));
+        }
+        csv
+    }
+}
+
+// Text reports list one row per line and end with the total
+impl fmt::Display for TimeReport {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for row in &self.rows {
+            match row.task {
+                Some(id) => writeln!(
+                    f,
+                    "{:>8}  {}: {}",
+                    format_duration(row.duration),
+                    id,
+                    row.label
+                )?,
+                None => writeln!(f, "{:>8}  {}", format_duration(row.duration), row.label)?,
+            }
+        }
+        write!(f, "{:>8}  total", format_duration(self.total()))
+    }
+}
+
+// format_duration renders a duration as hours and minutes, like `2h 05m`
+pub fn format_duration(duration: Duration) -> String {
+    let minutes = duration.num_minutes().max(0);
+    format!("{}h {:02}m", minutes / 60, minutes % 60)
+}
+
+// tracked_time is the total time logged against a task, counting a running timer up to `now`
+pub fn tracked_time(task: &Task, now: DateTime<Utc>) -> Duration {
+    task.time_entries
+        .iter()
+        .map(|entry| entry.duration(now))
+        .sum()
+}
+
+fn csv_field(field: &str) -> String {
+    if field.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", field.replace('"', "\"\""))
+    } else {
+        field.to_string()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::todo::{DueDate, NewTask, Priority};
+    use chrono::TimeZone;
+
+    fn at(day: u32, hour: u32, minute: u32) -> DateTime<Utc> {
+        Utc.with_ymd_and_hms(2024, 5, day, hour, minute, 0).unwrap()
+    }
+
+    fn sample_list() -> TodoList {
+        let mut list = TodoList::new();
+        for (description, category) in [
+            ("Fix login", "Work/Acme"),
+            ("Review, \"quickly\"", "Work/Acme"),
+            ("Taxes", "Home"),
+        ] {
+            list.add_task(NewTask {
+                description: description.to_string(),
+                due_date: DueDate::None,
+                category: category.to_string(),
+                priority: Priority::Low,
+            });
+        }
+        list.log_time(1, at(10, 9, 0), at(10, 10, 30)).unwrap();
+        list.log_time(2, at(10, 11, 0), at(10, 11, 45)).unwrap();
+        list.log_time(3, at(11, 12, 0), at(11, 13, 0)).unwrap();
+        list.log_time(1, at(12, 12, 0), at(12, 12, 20)).unwrap();
+        list
+    }
+
+    #[test]
+    fn test_report_groups() {
+        let list = sample_list();
+        let now = at(20, 0, 0);
+        let minutes = |report: &TimeReport| -> Vec<(String, i64)> {
+            report
+                .rows
+                .iter()
+                .map(|row| (row.label.clone(), row.duration.num_minutes()))
+                .collect()
+        };
+
+        let by_task = TimeReport::new(&list, ReportGroup::Task, None, None, now);
+        assert_eq!(by_task.rows[0].task, Some(1));
+        assert_eq!(by_task.rows[0].duration.num_minutes(), 110);
+        assert_eq!(by_task.total().num_minutes(), 215);
+
+        let by_category = TimeReport::new(&list, ReportGroup::Category, None, None, now);
+        assert_eq!(
+            minutes(&by_category),
+            [("Home".to_string(), 60), ("Work/Acme".to_string(), 155)]
+        );
+
+        let from = Some(at(11, 12, 0).with_timezone(&Local).date_naive());
+        let ranged = TimeReport::new(&list, ReportGroup::Day, from, None, now);
+        assert_eq!(ranged.total().num_minutes(), 80);
+    }
+
+    #[test]
+    fn test_report_csv() {
+        let list = sample_list();
+        let report = TimeReport::new(&list, ReportGroup::Task, None, None, at(20, 0, 0));
+        assert_eq!(
+            report.to_csv(),
+            "task_id,description,hours\n1,Fix login,1.83\n2,\"Review, \"\"quickly\"\"\",0.75\n3,Taxes,1.00\n"
+        );
+        assert_eq!(format_duration(report.total()), "3h 35m");
+    }
+
+    #[test]
+    fn test_one_timer_at_a_time() {
+        let mut list = sample_list();
+        list.start_timer_at(1, at(13, 9, 0)).unwrap();
+        assert!(matches!(
+            list.start_timer_at(2, at(13, 9, 5)),
+            Err(TodoError::TimerRunning(1))
+        ));
+        assert_eq!(list.running_timer(), Some((1, at(13, 9, 0))));
+
+        let report = TimeReport::new(&list, ReportGroup::Task, None, None, at(13, 9, 30));
+        assert_eq!(report.rows[0].duration.num_minutes(), 140);
+
+        let (id, duration) = list.stop_timer_at(at(13, 10, 0)).unwrap();
+        assert_eq!((id, duration.num_minutes()), (1, 60));
+        assert!(list.running_timer().is_none());
+        assert!(matches!(
+            list.stop_timer_at(at(13, 11, 0)),
+            Err(TodoError::NoTimerRunning)
+        ));
+        assert!(matches!(
+            list.log_time(2, at(13, 11, 0), at(13, 10, 0)),
+            Err(TodoError::InvalidTimeEntry(_))
+        ));
+    }
+}
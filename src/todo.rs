@@ -1,19 +1,79 @@
 // src/todo.rs
-use chrono::NaiveDate;
+use crate::fileio::{self, DEFAULT_BACKUPS};
+use crate::history::{Action, Change, History, Operation};
+use crate::migrations;
+use crate::patch::Patch;
+use crate::query::TaskQuery;
+use crate::recurrence::Recurrence;
+use crate::sort::TaskSort;
+use crate::timelog::TimeEntry;
+use chrono::{DateTime, Days, Duration, Local, NaiveDate, Utc};
 use serde::{Deserialize, Serialize};
-use std::collections::HashMap;
-use std::fs::{self, File};
-use std::io::{self, Read, Write};
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+use std::fmt;
+use std::fs;
+use std::io;
+use std::path::Path;
+use std::str::FromStr;
 use thiserror::Error;
 
 // Task is a thing to do and its details
-#[derive(Clone, Serialize, Deserialize, Debug)]
+#[derive(Clone, Serialize, Deserialize, Debug, PartialEq)]
 pub struct Task {
     pub id: u32,
     pub description: String,
     pub due_date: DueDate,
     pub category: String,
     pub priority: Priority,
+    #[serde(default)]
+    pub status: Status,
+    // created_at is unknown for tasks saved before it was recorded
+    #[serde(default)]
+    pub created_at: Option<DateTime<Utc>>,
+    #[serde(default)]
+    pub completed_at: Option<DateTime<Utc>>,
+    #[serde(default)]
+    pub recurrence: Option<Recurrence>,
+    // recurs_from is the ID of the occurrence this task was spawned from
+    #[serde(default)]
+    pub recurs_from: Option<u32>,
+    // next_occurrence is the ID of the occurrence spawned when this task was completed
+    #[serde(default)]
+    pub next_occurrence: Option<u32>,
+    // parent is the ID of the task this task is a subtask of
+    #[serde(default)]
+    pub parent: Option<u32>,
+    // blocked_by holds the IDs of the tasks that must be completed before this one
+    #[serde(default)]
+    pub blocked_by: BTreeSet<u32>,
+    #[serde(default)]
+    pub tags: BTreeSet<String>,
+    // notes is a free-form, possibly multi-line body kept alongside the description
+    #[serde(default)]
+    pub notes: String,
+    #[serde(default)]
+    pub checklist: Vec<ChecklistItem>,
+    #[serde(default)]
+    pub time_entries: Vec<TimeEntry>,
+}
+
+impl Task {
+    // checklist_percent is the share of checklist items checked, or None without a checklist
+    pub fn checklist_percent(&self) -> Option<u32> {
+        if self.checklist.is_empty() {
+            return None;
+        }
+        let checked = self.checklist.iter().filter(|item| item.checked).count();
+        Some((checked * 100 / self.checklist.len()) as u32)
+    }
+}
+
+// ChecklistItem is a step inside a task, lighter than a subtask
+#[derive(Clone, Serialize, Deserialize, Debug, PartialEq)]
+pub struct ChecklistItem {
+    pub text: String,
+    #[serde(default)]
+    pub checked: bool,
 }
 
 // NewTask is the information required to make a new Task
@@ -24,20 +84,94 @@ pub struct NewTask {
     pub priority: Priority,
 }
 
-// TaskUpdate represents an update from a user to a task
+// TaskUpdate represents an update from a user to a task. Each field is kept, set or cleared;
+// cleared fields go back to what a new task gets (no due date, DEFAULT_CATEGORY, low
+// priority), and the description cannot be cleared. The due date and its mode patch
+// separately, so a deadline can become a fixed day without restating the date
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
 pub struct TaskUpdate {
-    pub description: Option<String>,
-    pub due_date: Option<NaiveDate>,
-    pub category: Option<String>,
+    #[serde(default, skip_serializing_if = "Patch::is_keep")]
+    pub description: Patch<String>,
+    #[serde(default, skip_serializing_if = "Patch::is_keep")]
+    pub due_date: Patch<NaiveDate>,
+    #[serde(default, skip_serializing_if = "Patch::is_keep")]
+    pub due_mode: Patch<DueMode>,
+    #[serde(default, skip_serializing_if = "Patch::is_keep")]
+    pub category: Patch<String>,
+    #[serde(default, skip_serializing_if = "Patch::is_keep")]
+    pub priority: Patch<Priority>,
+}
+
+impl TaskUpdate {
+    // from_merge_patch reads a JSON Merge Patch such as
+    // `{"due_date": "2026-11-01", "due_mode": "by", "category": null}`
+    pub fn from_merge_patch(json: &str) -> Result<Self> {
+        let invalid = |e: serde_json::Error| TodoError::InvalidPatch(e.to_string());
+        let patch: serde_json::Value = serde_json::from_str(json).map_err(invalid)?;
+        if !patch.is_object() {
+            return Err(TodoError::InvalidPatch(
+                "a merge patch must be a JSON object".to_string(),
+            ));
+        }
+        serde_json::from_value(patch).map_err(invalid)
+    }
+
+    // with_due_date sets both the due date and its mode from a DueDate, clearing the date for
+    // DueDate::None
+    pub fn with_due_date(mut self, due_date: DueDate) -> Self {
+        self.due_date = due_date.date().map_or(Patch::Clear, Patch::Set);
+        self.due_mode = due_date.mode().map_or(Patch::Keep, Patch::Set);
+        self
+    }
+
+    // from_task is the update that sets every field back to the task's current values
+    pub fn from_task(task: &Task) -> Self {
+        TaskUpdate {
+            description: Patch::Set(task.description.clone()),
+            category: Patch::Set(task.category.clone()),
+            priority: Patch::Set(task.priority),
+            ..TaskUpdate::default()
+        }
+        .with_due_date(task.due_date.clone())
+    }
 }
-// Priority is a priority level for a task
-#[derive(Debug, PartialEq, Serialize, Deserialize, Clone, Copy)]
+// Priority is a priority level for a task, ordered from Low to High
+#[derive(Debug, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize, Clone, Copy)]
 pub enum Priority {
+    #[serde(alias = "low")]
     Low,
+    #[serde(alias = "medium")]
     Medium,
+    #[serde(alias = "high")]
     High,
 }
 
+impl fmt::Display for Priority {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let name = match self {
+            Priority::Low => "low",
+            Priority::Medium => "medium",
+            Priority::High => "high",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+impl FromStr for Priority {
+    type Err = TodoError;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s.trim().to_lowercase().as_str() {
+            "low" | "l" => Ok(Priority::Low),
+            "medium" | "med" | "m" => Ok(Priority::Medium),
+            "high" | "h" => Ok(Priority::High),
+            _ => Err(TodoError::InvalidPriority(s.to_string())),
+        }
+    }
+}
+
+// DueDate is when a task is due: on a given day, or on any day no later than a deadline
 #[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
 pub enum DueDate {
     On(NaiveDate),
@@ -45,18 +179,174 @@ pub enum DueDate {
     None,
 }
 
+impl DueDate {
+    // date returns the date of the deadline, if there is one
+    pub fn date(&self) -> Option<NaiveDate> {
+        match self {
+            DueDate::On(d) | DueDate::Before(d) => Some(*d),
+            DueDate::None => None,
+        }
+    }
+
+    pub fn mode(&self) -> Option<DueMode> {
+        match self {
+            DueDate::On(_) => Some(DueMode::On),
+            DueDate::Before(_) => Some(DueMode::Before),
+            DueDate::None => None,
+        }
+    }
+
+    // is_overdue reports whether the last day the task could be done on has passed: the day
+    // of an `On` task, or the deadline of a `Before` task
+    pub fn is_overdue(&self, today: NaiveDate) -> bool {
+        self.date().is_some_and(|d| d < today)
+    }
+
+    // is_due_within reports whether the task falls due between today and `days` days from
+    // now: the day of an `On` task, or the deadline of a `Before` task
+    pub fn is_due_within(&self, today: NaiveDate, days: u32) -> bool {
+        let last = today + Days::new(days.into());
+        self.date().is_some_and(|d| today <= d && d <= last)
+    }
+}
+
+impl fmt::Display for DueDate {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            DueDate::On(d) => write!(f, "on {}", d.format("%Y-%m-%d")),
+            DueDate::Before(d) => write!(f, "no later than {}", d.format("%Y-%m-%d")),
+            DueDate::None => write!(f, "none"),
+        }
+    }
+}
+
+// DueMode is whether a due date is the day a task happens on or the last day it can be done
+#[derive(Clone, Copy, Debug, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum DueMode {
+    #[default]
+    On,
+    #[serde(alias = "by")]
+    Before,
+}
+
+impl DueMode {
+    pub fn due(self, date: NaiveDate) -> DueDate {
+        match self {
+            DueMode::On => DueDate::On(date),
+            DueMode::Before => DueDate::Before(date),
+        }
+    }
+}
+
+impl FromStr for DueMode {
+    type Err = TodoError;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s.trim().to_lowercase().as_str() {
+            "on" => Ok(DueMode::On),
+            "by" | "before" | "no later than" => Ok(DueMode::Before),
+            _ => Err(TodoError::InvalidDueMode(s.trim().to_string())),
+        }
+    }
+}
+
 // Status is a status for a task
-#[derive(Debug, PartialEq)]
+#[derive(Debug, PartialEq, Serialize, Deserialize, Clone, Copy, Default)]
 pub enum Status {
+    #[default]
     NotStarted,
     InProgress,
     Completed,
 }
 
-#[derive(Serialize, Deserialize)]
+impl fmt::Display for Status {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let name = match self {
+            Status::NotStarted => "not started",
+            Status::InProgress => "in progress",
+            Status::Completed => "completed",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+impl FromStr for Status {
+    type Err = TodoError;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s.trim().to_lowercase().replace([' ', '-'], "_").as_str() {
+            "not_started" | "notstarted" | "todo" => Ok(Status::NotStarted),
+            "in_progress" | "inprogress" | "started" => Ok(Status::InProgress),
+            "completed" | "complete" | "done" => Ok(Status::Completed),
+            _ => Err(TodoError::InvalidStatus(s.to_string())),
+        }
+    }
+}
+
+// RemoveSubtasks says what happens to the subtasks of a removed task
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum RemoveSubtasks {
+    // Promote moves the subtasks up to the removed task's parent
+    Promote,
+    // Cascade removes the subtasks along with the task
+    Cascade,
+}
+
+// CategoryNode is one level of the category tree, with the number of tasks filed directly
+// under it and the number including its subcategories
+#[derive(Clone, Debug, PartialEq)]
+pub struct CategoryNode {
+    pub name: String,
+    pub path: String,
+    pub tasks: usize,
+    pub total: usize,
+    pub children: Vec<CategoryNode>,
+}
+
+impl CategoryNode {
+    fn new(name: &str, path: String) -> Self {
+        CategoryNode {
+            name: name.to_string(),
+            path,
+            tasks: 0,
+            total: 0,
+            children: Vec::new(),
+        }
+    }
+}
+
+// Progress is how many of a task's subtasks are completed
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct Progress {
+    pub completed: usize,
+    pub total: usize,
+}
+
+impl fmt::Display for Progress {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}/{} subtasks done", self.completed, self.total)
+    }
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
 pub struct TodoList {
     tasks: HashMap<u32, Task>,
     next_id: u32,
+    // history is only saved with the list when it is set to persist
+    #[serde(default, skip_serializing_if = "History::is_transient")]
+    history: History,
+    // changes counts the changes made to the list since it was created or loaded
+    #[serde(skip)]
+    changes: u64,
+}
+
+// Two lists are equal when they hold the same tasks and history, however they got there
+impl PartialEq for TodoList {
+    fn eq(&self, other: &Self) -> bool {
+        self.tasks == other.tasks && self.next_id == other.next_id && self.history == other.history
+    }
 }
 
 #[derive(Error, Debug)]
@@ -65,89 +355,1175 @@ pub enum TodoError {
     Io(#[from] io::Error),
     #[error("Serialization error: {0}")]
     Serialization(#[from] serde_json::Error),
+    #[error("Database error: {0}")]
+    Database(#[from] rusqlite::Error),
     #[error("Task not found")]
     TaskNotFound,
+    #[error("Invalid priority: {0} (expected low, medium or high)")]
+    InvalidPriority(String),
+    #[error("Invalid date: {0}")]
+    InvalidDate(String),
+    #[error("Invalid due date mode: {0} (expected on or by)")]
+    InvalidDueMode(String),
+    #[error("Invalid status: {0} (expected not_started, in_progress or completed)")]
+    InvalidStatus(String),
+    #[error(
+        "Invalid recurrence: {0} (expected daily, weekly [days], monthly <day> or every <n> days)"
+    )]
+    InvalidRecurrence(String),
+    #[error("Task {0} cannot be moved under task {1}, which is itself or one of its subtasks")]
+    InvalidParent(u32, u32),
+    #[error("Task {0} cannot be blocked by task {1}, which already depends on it")]
+    DependencyCycle(u32, u32),
+    #[error("Category not found: {0}")]
+    CategoryNotFound(String),
+    #[error("Category already exists: {0} (merge the categories instead)")]
+    CategoryExists(String),
+    #[error("Task {0} has no checklist item {1}")]
+    ChecklistItemNotFound(u32, usize),
+    #[error("A timer is already running on task {0}; stop it first")]
+    TimerRunning(u32),
+    #[error("No timer is running")]
+    NoTimerRunning,
+    #[error("Invalid time entry: {0}")]
+    InvalidTimeEntry(String),
+    #[error("Invalid report grouping: {0} (expected task, category or day)")]
+    InvalidReport(String),
+    #[error("Invalid tag: '{0}' (tags cannot be empty or contain spaces)")]
+    InvalidTag(String),
+    #[error("Invalid category name: '{0}' (categories cannot be blank)")]
+    InvalidCategory(String),
+    #[error(
+        "Invalid task document{}: {message}",
+        .line.map(|line| format!(" on line {}", line)).unwrap_or_default()
+    )]
+    InvalidDocument {
+        line: Option<usize>,
+        message: String,
+    },
+    #[error("Nothing to undo")]
+    NothingToUndo,
+    #[error("Nothing to redo")]
+    NothingToRedo,
+    #[error("The history limit is only kept with a persisted history (add --persist)")]
+    HistoryNotPersisted,
+    #[error("Task {0} already exists")]
+    TaskExists(u32),
+    #[error("Invalid journal entry on line {0}: {1}")]
+    InvalidJournal(usize, String),
+    #[error("The journal only goes back to {0}, when it was last compacted")]
+    JournalCompacted(DateTime<Utc>),
+    #[error("The list as of a past time is read-only")]
+    ReadOnly,
+    #[error(
+        "The list file was saved in format version {found} by a newer version of todo; this \
+         version reads up to format {supported}"
+    )]
+    UnsupportedVersion { found: u32, supported: u32 },
+    #[error("Invalid save file: {0}")]
+    InvalidSaveFile(String),
+    #[error("{0} already exists")]
+    FileExists(String),
+    #[error("No list named {0}")]
+    ListNotFound(String),
+    #[error("A list named {0} already exists")]
+    ListExists(String),
+    #[error(
+        "Invalid list name: '{0}' (names cannot be empty, start with '.' or contain '/' or '\\')"
+    )]
+    InvalidListName(String),
+    #[error("{0} is the current list")]
+    CurrentList(String),
+    #[error(
+        "{path} is open in another todo process{}; close it there first",
+        .pid.map(|pid| format!(" (process {})", pid)).unwrap_or_default()
+    )]
+    ListLocked { path: String, pid: Option<u32> },
+    #[error("Invalid patch: {0}")]
+    InvalidPatch(String),
+    #[error("Invalid query: {0}")]
+    InvalidQuery(String),
+    #[error(
+        "Invalid sort key: {0} (expected id, due, priority, category, created or description)"
+    )]
+    InvalidSort(String),
 }
 
 type Result<T> = std::result::Result<T, TodoError>;
 
+// DEFAULT_CATEGORY is the default category for new tasks
+pub const DEFAULT_CATEGORY: &str = "General";
+
+// CATEGORY_SEPARATOR splits a category into levels, as in `Work/ClientA/Bugs`
+pub const CATEGORY_SEPARATOR: char = '/';
+
+// normalize_category trims each level of a category, collapses runs of whitespace inside it
+// and drops empty levels
+pub fn normalize_category(category: &str) -> String {
+    category
+        .split(CATEGORY_SEPARATOR)
+        .map(|level| level.split_whitespace().collect::<Vec<_>>().join(" "))
+        .filter(|level| !level.is_empty())
+        .collect::<Vec<_>>()
+        .join(&CATEGORY_SEPARATOR.to_string())
+}
+
+// same_category compares two categories ignoring case and whitespace differences
+pub fn same_category(a: &str, b: &str) -> bool {
+    normalize_category(a).to_lowercase() == normalize_category(b).to_lowercase()
+}
+
+// in_category reports whether `category` is `filter` or one of its subcategories, so a filter
+// of `Work` takes in `Work/ClientA/Bugs`
+pub fn in_category(category: &str, filter: &str) -> bool {
+    let category = normalize_category(category).to_lowercase();
+    let filter = normalize_category(filter).to_lowercase();
+    category == filter
+        || category
+            .strip_prefix(&filter)
+            .is_some_and(|rest| rest.starts_with(CATEGORY_SEPARATOR))
+}
+
+// normalize_tag lowercases a tag and drops a leading '+', rejecting empty tags and tags
+// containing whitespace
+pub fn normalize_tag(tag: &str) -> Result<String> {
+    let normalized = tag.trim();
+    let normalized = normalized.strip_prefix('+').unwrap_or(normalized);
+    if normalized.is_empty() || normalized.contains(char::is_whitespace) {
+        return Err(TodoError::InvalidTag(tag.to_string()));
+    }
+    Ok(normalized.to_lowercase())
+}
+
+impl Default for TodoList {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+fn sort_category_nodes(nodes: &mut [CategoryNode]) {
+    nodes.sort_by_key(|node| node.name.to_lowercase());
+    for node in nodes {
+        sort_category_nodes(&mut node.children);
+    }
+}
+
 impl TodoList {
     pub fn new() -> TodoList {
         TodoList {
             tasks: HashMap::new(),
             next_id: 1,
+            history: History::default(),
+            changes: 0,
         }
     }
+    // from_tasks builds a list from saved tasks, with `next_id` raised past every task ID
+    pub fn from_tasks(tasks: impl IntoIterator<Item = Task>, next_id: u32) -> TodoList {
+        let tasks: HashMap<u32, Task> = tasks.into_iter().map(|task| (task.id, task)).collect();
+        let next_id = tasks.keys().map(|id| id + 1).fold(next_id.max(1), u32::max);
+        TodoList {
+            tasks,
+            next_id,
+            history: History::default(),
+            changes: 0,
+        }
+    }
+
+    // put_task stores a task as it is, replacing any task with its ID, without recording the
+    // change in the history. Storage backends use it to apply changes they were given
+    pub fn put_task(&mut self, task: Task) {
+        self.changes += 1;
+        self.next_id = self.next_id.max(task.id + 1);
+        self.tasks.insert(task.id, task);
+    }
+
+    // discard_task drops a task as it is, leaving its subtasks and dependents pointing at it,
+    // without recording the change in the history
+    pub fn discard_task(&mut self, id: u32) -> Option<Task> {
+        let task = self.tasks.remove(&id)?;
+        self.changes += 1;
+        Some(task)
+    }
+
+    // next_id is the ID the next added task gets
+    pub fn next_id(&self) -> u32 {
+        self.next_id
+    }
+
     // get_task returns a reference to a task with the given ID
     pub fn get_task(&self, id: u32) -> Option<&Task> {
         self.tasks.get(&id)
     }
 
-    // add_task adds a new task to the list
-    pub fn add_task(&mut self, new_task: NewTask) -> u32 {
-        let id = self.next_id;
+    // task_mut returns a task to change, counting it as a change to the list
+    fn task_mut(&mut self, id: u32) -> Result<&mut Task> {
+        let task = self.tasks.get_mut(&id).ok_or(TodoError::TaskNotFound)?;
+        self.changes += 1;
+        Ok(task)
+    }
+
+    // changes counts the changes made to the list since it was created or loaded, so callers
+    // can tell whether it changed without comparing it with a copy
+    pub fn changes(&self) -> u64 {
+        self.changes
+    }
+
+    // add_task adds a new task to the list
+    pub fn add_task(&mut self, new_task: NewTask) -> u32 {
+        let id = self.insert_task(new_task);
+        self.record_add(id);
+        id
+    }
+
+    // insert_task adds a new task without recording it in the history, for tasks added as a
+    // side effect of another change
+    fn insert_task(&mut self, new_task: NewTask) -> u32 {
+        let id = self.next_id;
+        let category = self.canonical_category(&new_task.category);
+        self.changes += 1;
+
+        self.tasks.insert(
+            id,
+            Task {
+                id,
+                description: new_task.description,
+                due_date: new_task.due_date,
+                category,
+                priority: new_task.priority,
+                status: Status::NotStarted,
+                created_at: Some(Utc::now()),
+                completed_at: None,
+                recurrence: None,
+                recurs_from: None,
+                next_occurrence: None,
+                parent: None,
+                blocked_by: BTreeSet::new(),
+                tags: BTreeSet::new(),
+                notes: String::new(),
+                checklist: Vec::new(),
+                time_entries: Vec::new(),
+            },
+        );
+        self.next_id += 1;
+        id
+    }
+
+    // remove_task removes a task, moving its subtasks up to the task's own parent
+    pub fn remove_task(&mut self, id: u32) -> Result<()> {
+        self.remove_task_with(id, RemoveSubtasks::Promote)?;
+        Ok(())
+    }
+
+    // remove_task_with removes a task, handling its subtasks as asked, and returns the IDs
+    // of every task removed
+    pub fn remove_task_with(&mut self, id: u32, subtasks: RemoveSubtasks) -> Result<Vec<u32>> {
+        let restore = self.delete_task(id, subtasks)?;
+        let removed = match &restore {
+            Operation::Insert { tasks, .. } => tasks.iter().map(|task| task.id).collect(),
+            _ => vec![id],
+        };
+        self.history.record(Change {
+            action: Action::Remove,
+            id,
+            operation: restore,
+        });
+        Ok(removed)
+    }
+
+    // delete_task removes a task as remove_task_with does and returns the operation that puts
+    // everything back
+    fn delete_task(&mut self, id: u32, subtasks: RemoveSubtasks) -> Result<Operation> {
+        let task = self.tasks.remove(&id).ok_or(TodoError::TaskNotFound)?;
+        self.changes += 1;
+        let mut removed = vec![task];
+        let mut promoted = Vec::new();
+
+        match subtasks {
+            RemoveSubtasks::Promote => {
+                let parent = removed[0].parent;
+                for child in self.tasks.values_mut().filter(|t| t.parent == Some(id)) {
+                    child.parent = parent;
+                    promoted.push(child.id);
+                }
+                promoted.sort();
+            }
+            RemoveSubtasks::Cascade => {
+                let mut descendants: Vec<u32> = self
+                    .tasks
+                    .keys()
+                    .copied()
+                    .filter(|&other| self.is_descendant_of(other, id))
+                    .collect();
+                descendants.sort();
+                for descendant in descendants {
+                    removed.extend(self.tasks.remove(&descendant));
+                }
+            }
+        }
+        let removed_ids: Vec<u32> = removed.iter().map(|task| task.id).collect();
+        let mut unblocked = Vec::new();
+        for task in self.tasks.values_mut() {
+            for blocker in task.blocked_by.iter().filter(|b| removed_ids.contains(b)) {
+                unblocked.push((task.id, *blocker));
+            }
+            task.blocked_by
+                .retain(|blocker| !removed_ids.contains(blocker));
+        }
+        unblocked.sort();
+        Ok(Operation::Insert {
+            tasks: removed,
+            subtasks,
+            promoted,
+            unblocked,
+        })
+    }
+
+    fn record_add(&mut self, id: u32) {
+        self.history.record(Change {
+            action: Action::Add,
+            id,
+            operation: Operation::Delete {
+                id,
+                subtasks: RemoveSubtasks::Promote,
+            },
+        });
+    }
+
+    // history returns the changes that can be undone and redone
+    pub fn history(&self) -> &History {
+        &self.history
+    }
+
+    pub fn history_mut(&mut self) -> &mut History {
+        self.changes += 1;
+        &mut self.history
+    }
+
+    // undo takes back the most recent add, remove or update still in the history and returns
+    // it. If it no longer applies it stays in the history and the list is left as it was
+    pub fn undo(&mut self) -> Result<Change> {
+        let change = self.history.pop_undo().ok_or(TodoError::NothingToUndo)?;
+        match self.apply_operation(change.operation.clone()) {
+            Ok(inverse) => {
+                self.history.push_redo(Change {
+                    operation: inverse,
+                    ..change.clone()
+                });
+                Ok(change)
+            }
+            Err(e) => {
+                self.history.push_undo(change);
+                Err(e)
+            }
+        }
+    }
+
+    // redo makes the most recently undone change again and returns it
+    pub fn redo(&mut self) -> Result<Change> {
+        let change = self.history.pop_redo().ok_or(TodoError::NothingToRedo)?;
+        match self.apply_operation(change.operation.clone()) {
+            Ok(inverse) => {
+                self.history.push_undo(Change {
+                    operation: inverse,
+                    ..change.clone()
+                });
+                Ok(change)
+            }
+            Err(e) => {
+                self.history.push_redo(change);
+                Err(e)
+            }
+        }
+    }
+
+    // apply_operation makes an operation's change without recording it and returns the
+    // operation that reverses it
+    fn apply_operation(&mut self, operation: Operation) -> Result<Operation> {
+        match operation {
+            Operation::Insert {
+                tasks,
+                subtasks,
+                promoted,
+                unblocked,
+            } => {
+                let id = tasks.first().ok_or(TodoError::TaskNotFound)?.id;
+                if let Some(task) = tasks.iter().find(|t| self.tasks.contains_key(&t.id)) {
+                    return Err(TodoError::TaskExists(task.id));
+                }
+                self.changes += 1;
+                for task in tasks {
+                    self.next_id = self.next_id.max(task.id + 1);
+                    self.tasks.insert(task.id, task);
+                }
+                for child in promoted {
+                    if let Some(task) = self.tasks.get_mut(&child) {
+                        task.parent = Some(id);
+                    }
+                }
+                for (task, blocker) in unblocked {
+                    if let Some(task) = self.tasks.get_mut(&task) {
+                        task.blocked_by.insert(blocker);
+                    }
+                }
+                Ok(Operation::Delete { id, subtasks })
+            }
+            Operation::Delete { id, subtasks } => self.delete_task(id, subtasks),
+            Operation::Update { id, update } => {
+                let task = self.tasks.get(&id).ok_or(TodoError::TaskNotFound)?;
+                let inverse = TaskUpdate::from_task(task);
+                self.apply_update(id, update)?;
+                Ok(Operation::Update {
+                    id,
+                    update: inverse,
+                })
+            }
+        }
+    }
+
+    // add_dependency records that `id` cannot start until `blocker` is completed. Links that
+    // would make a task depend on itself, directly or through other tasks, are rejected
+    pub fn add_dependency(&mut self, id: u32, blocker: u32) -> Result<()> {
+        if !self.tasks.contains_key(&id) || !self.tasks.contains_key(&blocker) {
+            return Err(TodoError::TaskNotFound);
+        }
+        if id == blocker || self.depends_on(blocker, id) {
+            return Err(TodoError::DependencyCycle(id, blocker));
+        }
+        self.task_mut(id)?.blocked_by.insert(blocker);
+        Ok(())
+    }
+
+    pub fn remove_dependency(&mut self, id: u32, blocker: u32) -> Result<()> {
+        let task = self.task_mut(id)?;
+        task.blocked_by.remove(&blocker);
+        Ok(())
+    }
+
+    // removal_dependents lists the tasks that removing a task with remove_task_with would
+    // unblock: those blocked by the task or, with Cascade, by any of its subtasks. Tasks that
+    // are removed along with it are left out
+    pub fn removal_dependents(&self, id: u32, subtasks: RemoveSubtasks) -> Vec<&Task> {
+        let removed = |other: u32| {
+            other == id || (subtasks == RemoveSubtasks::Cascade && self.is_descendant_of(other, id))
+        };
+        self.collect_tasks(|task| {
+            !removed(task.id) && task.blocked_by.iter().any(|&blocker| removed(blocker))
+        })
+    }
+
+    // dependents lists the tasks blocked by a task
+    pub fn dependents(&self, id: u32) -> Vec<&Task> {
+        self.collect_tasks(|task| task.blocked_by.contains(&id))
+    }
+
+    // open_blockers lists the tasks still blocking a task
+    pub fn open_blockers(&self, id: u32) -> Vec<&Task> {
+        let Some(task) = self.tasks.get(&id) else {
+            return Vec::new();
+        };
+        self.collect_tasks(|blocker| {
+            task.blocked_by.contains(&blocker.id) && blocker.status != Status::Completed
+        })
+    }
+
+    // actionable_tasks lists the unfinished tasks whose blockers are all completed
+    pub fn actionable_tasks(&self) -> Vec<&Task> {
+        self.collect_tasks(|task| {
+            task.status != Status::Completed
+                && task.blocked_by.iter().all(|blocker| {
+                    self.tasks
+                        .get(blocker)
+                        .is_none_or(|blocker| blocker.status == Status::Completed)
+                })
+        })
+    }
+
+    // depends_on reports whether `id` is blocked by `other`, directly or transitively
+    fn depends_on(&self, id: u32, other: u32) -> bool {
+        let mut seen = BTreeSet::new();
+        let mut pending = vec![id];
+        while let Some(current) = pending.pop() {
+            if !seen.insert(current) {
+                continue;
+            }
+            let Some(task) = self.tasks.get(&current) else {
+                continue;
+            };
+            if task.blocked_by.contains(&other) {
+                return true;
+            }
+            pending.extend(task.blocked_by.iter().copied());
+        }
+        false
+    }
+
+    // add_subtask adds a new task under an existing one
+    pub fn add_subtask(&mut self, parent: u32, new_task: NewTask) -> Result<u32> {
+        if !self.tasks.contains_key(&parent) {
+            return Err(TodoError::TaskNotFound);
+        }
+        let id = self.insert_task(new_task);
+        if let Some(task) = self.tasks.get_mut(&id) {
+            task.parent = Some(parent);
+        }
+        self.record_add(id);
+        Ok(id)
+    }
+
+    // move_task moves a task and its subtasks under a new parent, or to the top level when
+    // given None
+    pub fn move_task(&mut self, id: u32, parent: Option<u32>) -> Result<()> {
+        if !self.tasks.contains_key(&id) {
+            return Err(TodoError::TaskNotFound);
+        }
+        if let Some(parent) = parent {
+            if !self.tasks.contains_key(&parent) {
+                return Err(TodoError::TaskNotFound);
+            }
+            if parent == id || self.is_descendant_of(parent, id) {
+                return Err(TodoError::InvalidParent(id, parent));
+            }
+        }
+        self.task_mut(id)?.parent = parent;
+        Ok(())
+    }
+
+    // subtasks lists the direct subtasks of a task
+    pub fn subtasks(&self, id: u32) -> Vec<&Task> {
+        self.collect_tasks(|task| task.parent == Some(id))
+    }
+
+    // export_tasks copies a task followed by its subtasks at any depth, for adding to another
+    // list with import_tasks
+    pub fn export_tasks(&self, id: u32) -> Result<Vec<Task>> {
+        let task = self.tasks.get(&id).ok_or(TodoError::TaskNotFound)?;
+        let mut descendants: Vec<&Task> = self
+            .tasks
+            .values()
+            .filter(|other| self.is_descendant_of(other.id, id))
+            .collect();
+        descendants.sort_by_key(|task| task.id);
+        Ok(std::iter::once(task).chain(descendants).cloned().collect())
+    }
+
+    // import_tasks adds tasks from another list, as export_tasks gives them, under new IDs and
+    // returns the new IDs in the same order. Subtask, blocker and recurrence links between the
+    // imported tasks follow them; links to tasks left behind are dropped. The import is
+    // recorded as adding the first task, so undo removes it with its subtasks
+    pub fn import_tasks(&mut self, tasks: Vec<Task>) -> Vec<u32> {
+        let ids: HashMap<u32, u32> = tasks
+            .iter()
+            .enumerate()
+            .map(|(offset, task)| (task.id, self.next_id + offset as u32))
+            .collect();
+        let mut new_ids = Vec::new();
+        for mut task in tasks {
+            task.id = ids[&task.id];
+            task.category = self.canonical_category(&task.category);
+            task.parent = task.parent.and_then(|parent| ids.get(&parent).copied());
+            task.recurs_from = task.recurs_from.and_then(|from| ids.get(&from).copied());
+            task.next_occurrence = task
+                .next_occurrence
+                .and_then(|next| ids.get(&next).copied());
+            task.blocked_by = task
+                .blocked_by
+                .iter()
+                .filter_map(|blocker| ids.get(blocker).copied())
+                .collect();
+            new_ids.push(task.id);
+            self.changes += 1;
+            self.next_id = self.next_id.max(task.id + 1);
+            self.tasks.insert(task.id, task);
+        }
+        if let Some(&id) = new_ids.first() {
+            self.history.record(Change {
+                action: Action::Add,
+                id,
+                operation: Operation::Delete {
+                    id,
+                    subtasks: RemoveSubtasks::Cascade,
+                },
+            });
+        }
+        new_ids
+    }
+
+    // progress counts how many of a task's subtasks, at any depth, are completed
+    pub fn progress(&self, id: u32) -> Progress {
+        self.tasks
+            .values()
+            .filter(|task| self.is_descendant_of(task.id, id))
+            .fold(Progress::default(), |progress, task| Progress {
+                completed: progress.completed + usize::from(task.status == Status::Completed),
+                total: progress.total + 1,
+            })
+    }
+
+    // task_tree lists every task depth first, subtasks right after their parent, each with
+    // its depth in the tree. Top-level tasks and siblings keep the default order
+    pub fn task_tree(&self) -> Vec<(usize, &Task)> {
+        let mut tree = Vec::new();
+        let roots = self.collect_tasks(|task| {
+            task.parent
+                .is_none_or(|parent| !self.tasks.contains_key(&parent))
+        });
+        for root in roots {
+            self.push_subtree(root, 0, &mut tree);
+        }
+        tree
+    }
+
+    fn push_subtree<'a>(&'a self, task: &'a Task, depth: usize, tree: &mut Vec<(usize, &'a Task)>) {
+        tree.push((depth, task));
+        for child in self.subtasks(task.id) {
+            self.push_subtree(child, depth + 1, tree);
+        }
+    }
+
+    // is_descendant_of reports whether `id` sits anywhere below `ancestor`
+    fn is_descendant_of(&self, id: u32, ancestor: u32) -> bool {
+        let mut current = self.tasks.get(&id).and_then(|task| task.parent);
+        // Bounded by the number of tasks in case a hand-edited file contains a cycle
+        for _ in 0..self.tasks.len() {
+            match current {
+                Some(parent) if parent == ancestor => return true,
+                Some(parent) => current = self.tasks.get(&parent).and_then(|task| task.parent),
+                None => return false,
+            }
+        }
+        false
+    }
+
+    pub fn list_tasks(&self, category: Option<&str>) -> Vec<&Task> {
+        self.list_tasks_with_status(category, None)
+    }
+
+    // list_tasks_with_status lists tasks, optionally narrowed to a category and a status
+    pub fn list_tasks_with_status(
+        &self,
+        category: Option<&str>,
+        status: Option<Status>,
+    ) -> Vec<&Task> {
+        self.collect_tasks(|task| {
+            category.is_none_or(|c| in_category(&task.category, c))
+                && status.is_none_or(|s| task.status == s)
+        })
+    }
+
+    // query lists the tasks matching a TaskQuery, in the same order as list_tasks
+    pub fn query(&self, query: &TaskQuery) -> Vec<&Task> {
+        self.collect_tasks(|task| query.matches(task))
+    }
+
+    // query_sorted lists the tasks matching a TaskQuery in the order given by a TaskSort
+    pub fn query_sorted(&self, query: &TaskQuery, sort: &TaskSort) -> Vec<&Task> {
+        let mut tasks: Vec<&Task> = self.tasks.values().filter(|t| query.matches(t)).collect();
+        sort.sort(&mut tasks);
+        tasks
+    }
+
+    // overdue_tasks lists unfinished tasks whose due date has passed
+    pub fn overdue_tasks(&self, today: NaiveDate) -> Vec<&Task> {
+        self.collect_tasks(|task| {
+            task.status != Status::Completed && task.due_date.is_overdue(today)
+        })
+    }
+
+    // due_today_tasks lists unfinished tasks that are due today: tasks that happen today, and
+    // tasks whose deadline is today
+    pub fn due_today_tasks(&self, today: NaiveDate) -> Vec<&Task> {
+        self.due_within_days(today, 0)
+    }
+
+    // due_within_days lists unfinished tasks due between today and `days` days from today
+    pub fn due_within_days(&self, today: NaiveDate, days: u32) -> Vec<&Task> {
+        self.collect_tasks(|task| {
+            task.status != Status::Completed && task.due_date.is_due_within(today, days)
+        })
+    }
+
+    // collect_tasks gathers the tasks matching a predicate in the default TaskSort order:
+    // highest priority first, then earliest due date, with undated tasks last
+    fn collect_tasks(&self, predicate: impl Fn(&Task) -> bool) -> Vec<&Task> {
+        let mut tasks: Vec<&Task> = self.tasks.values().filter(|task| predicate(task)).collect();
+        TaskSort::default().sort(&mut tasks);
+        tasks
+    }
+
+    // start_task marks a task as in progress
+    pub fn start_task(&mut self, id: u32) -> Result<()> {
+        let task = self.task_mut(id)?;
+        task.status = Status::InProgress;
+        task.completed_at = None;
+        Ok(())
+    }
+
+    // complete_task marks a task as completed and records when it was finished. Completing a
+    // recurring task spawns its next occurrence, whose ID is returned
+    pub fn complete_task(&mut self, id: u32) -> Result<Option<u32>> {
+        let task = self.task_mut(id)?;
+        if task.status == Status::Completed {
+            return Ok(None);
+        }
+        let completed_at = Utc::now();
+        task.status = Status::Completed;
+        task.completed_at = Some(completed_at);
+        if task.next_occurrence.is_some() {
+            return Ok(None);
+        }
+
+        let Some((next_date, next_recurrence)) = task.recurrence.as_ref().and_then(|recurrence| {
+            recurrence.next(
+                task.due_date.date(),
+                completed_at.with_timezone(&Local).date_naive(),
+            )
+        }) else {
+            return Ok(None);
+        };
+        let due_date = match task.due_date {
+            DueDate::Before(_) => DueDate::Before(next_date),
+            DueDate::On(_) | DueDate::None => DueDate::On(next_date),
+        };
+        let next = NewTask {
+            description: task.description.clone(),
+            due_date,
+            category: task.category.clone(),
+            priority: task.priority,
+        };
+
+        let parent = task.parent;
+        let tags = task.tags.clone();
+        let notes = task.notes.clone();
+        // The next occurrence starts with the same checklist, all unchecked
+        let checklist = task
+            .checklist
+            .iter()
+            .map(|item| ChecklistItem {
+                text: item.text.clone(),
+                checked: false,
+            })
+            .collect();
+        let next_id = self.insert_task(next);
+        if let Some(next_task) = self.tasks.get_mut(&next_id) {
+            next_task.recurrence = Some(next_recurrence);
+            next_task.recurs_from = Some(id);
+            next_task.parent = parent;
+            next_task.tags = tags;
+            next_task.notes = notes;
+            next_task.checklist = checklist;
+        }
+        if let Some(task) = self.tasks.get_mut(&id) {
+            task.next_occurrence = Some(next_id);
+        }
+        Ok(Some(next_id))
+    }
+
+    // set_recurrence makes a task repeat, or stops it repeating when given None
+    pub fn set_recurrence(&mut self, id: u32, recurrence: Option<Recurrence>) -> Result<()> {
+        let task = self.task_mut(id)?;
+        task.recurrence = recurrence;
+        Ok(())
+    }
+
+    // occurrence_history lists the earlier occurrences of a recurring task, most recent first
+    pub fn occurrence_history(&self, id: u32) -> Vec<&Task> {
+        let mut history = Vec::new();
+        let mut previous = self.tasks.get(&id).and_then(|task| task.recurs_from);
+        while let Some(task) = previous.and_then(|id| self.tasks.get(&id)) {
+            history.push(task);
+            previous = task.recurs_from;
+        }
+        history
+    }
+
+    // reopen_task moves a task back to not started and clears its completion time
+    pub fn reopen_task(&mut self, id: u32) -> Result<()> {
+        let task = self.task_mut(id)?;
+        task.status = Status::NotStarted;
+        task.completed_at = None;
+        Ok(())
+    }
+
+    // update_task applies a TaskUpdate, leaving the task untouched if the update is invalid
+    pub fn update_task(&mut self, id: u32, task_update: TaskUpdate) -> Result<()> {
+        let task = self.tasks.get(&id).ok_or(TodoError::TaskNotFound)?;
+        let inverse = TaskUpdate::from_task(task);
+        self.apply_update(id, task_update)?;
+        self.history.record(Change {
+            action: Action::Update,
+            id,
+            operation: Operation::Update {
+                id,
+                update: inverse,
+            },
+        });
+        Ok(())
+    }
+
+    fn apply_update(&mut self, id: u32, task_update: TaskUpdate) -> Result<()> {
+        if task_update.description == Patch::Clear {
+            return Err(TodoError::InvalidPatch(
+                "the description cannot be cleared".to_string(),
+            ));
+        }
+        let task = self.tasks.get(&id).ok_or(TodoError::TaskNotFound)?;
+        let category = task_update
+            .category
+            .resolve(task.category.clone(), DEFAULT_CATEGORY.to_string());
+        let category = self.canonical_category(&category);
+        let task = self.task_mut(id)?;
+
+        if let Patch::Set(desc) = task_update.description {
+            task.description = desc;
+        }
+        let date = task_update
+            .due_date
+            .map(Some)
+            .resolve(task.due_date.date(), None);
+        let mode = task_update
+            .due_mode
+            .resolve(task.due_date.mode().unwrap_or_default(), DueMode::default());
+        task.due_date = date.map_or(DueDate::None, |date| mode.due(date));
+        task.category = category;
+        task.priority = task_update.priority.resolve(task.priority, Priority::Low);
+
+        Ok(())
+    }
+
+    // set_notes replaces a task's notes; trailing blank lines are dropped
+    pub fn set_notes(&mut self, id: u32, notes: &str) -> Result<()> {
+        let task = self.task_mut(id)?;
+        task.notes = notes.trim_end().to_string();
+        Ok(())
+    }
+
+    // set_checklist replaces a task's checklist
+    pub fn set_checklist(&mut self, id: u32, checklist: Vec<ChecklistItem>) -> Result<()> {
+        let task = self.task_mut(id)?;
+        task.checklist = checklist;
+        Ok(())
+    }
+
+    // add_checklist_item appends an unchecked item to a task's checklist and returns its
+    // number. Checklist items are numbered from 1, the way they are shown
+    pub fn add_checklist_item(&mut self, id: u32, text: &str) -> Result<usize> {
+        let task = self.task_mut(id)?;
+        task.checklist.push(ChecklistItem {
+            text: text.trim().to_string(),
+            checked: false,
+        });
+        Ok(task.checklist.len())
+    }
+
+    // toggle_checklist_item checks or unchecks a checklist item and returns its new state
+    pub fn toggle_checklist_item(&mut self, id: u32, number: usize) -> Result<bool> {
+        let item = self.checklist_item_mut(id, number)?;
+        item.checked = !item.checked;
+        Ok(item.checked)
+    }
+
+    pub fn remove_checklist_item(&mut self, id: u32, number: usize) -> Result<ChecklistItem> {
+        self.checklist_item_mut(id, number)?;
+        let task = self.task_mut(id)?;
+        Ok(task.checklist.remove(number - 1))
+    }
+
+    fn checklist_item_mut(&mut self, id: u32, number: usize) -> Result<&mut ChecklistItem> {
+        let task = self.task_mut(id)?;
+        number
+            .checked_sub(1)
+            .and_then(|index| task.checklist.get_mut(index))
+            .ok_or(TodoError::ChecklistItemNotFound(id, number))
+    }
+
+    // running_timer returns the task with the running timer and when the timer started
+    pub fn running_timer(&self) -> Option<(u32, DateTime<Utc>)> {
+        self.tasks.values().find_map(|task| {
+            task.time_entries
+                .iter()
+                .find(|entry| entry.stopped_at.is_none())
+                .map(|entry| (task.id, entry.started_at))
+        })
+    }
+
+    // start_timer starts timing work on a task. Only one timer runs at a time; it is kept in
+    // the list, so a saved list picks the timer back up when loaded again
+    pub fn start_timer(&mut self, id: u32) -> Result<()> {
+        self.start_timer_at(id, Utc::now())
+    }
+
+    pub fn start_timer_at(&mut self, id: u32, now: DateTime<Utc>) -> Result<()> {
+        if let Some((running, _)) = self.running_timer() {
+            return Err(TodoError::TimerRunning(running));
+        }
+        let task = self.task_mut(id)?;
+        task.time_entries.push(TimeEntry {
+            started_at: now,
+            stopped_at: None,
+        });
+        Ok(())
+    }
+
+    // stop_timer stops the running timer and returns its task and how long it ran
+    pub fn stop_timer(&mut self) -> Result<(u32, Duration)> {
+        self.stop_timer_at(Utc::now())
+    }
+
+    pub fn stop_timer_at(&mut self, now: DateTime<Utc>) -> Result<(u32, Duration)> {
+        let (id, _) = self.running_timer().ok_or(TodoError::NoTimerRunning)?;
+        let task = self.task_mut(id)?;
+        let entry = task
+            .time_entries
+            .iter_mut()
+            .find(|entry| entry.stopped_at.is_none())
+            .ok_or(TodoError::NoTimerRunning)?;
+        // A clock set back while the timer ran would otherwise give a negative entry
+        entry.stopped_at = Some(now.max(entry.started_at));
+        Ok((id, entry.duration(now)))
+    }
+
+    // log_time records time spent on a task after the fact
+    pub fn log_time(
+        &mut self,
+        id: u32,
+        started_at: DateTime<Utc>,
+        stopped_at: DateTime<Utc>,
+    ) -> Result<()> {
+        if stopped_at <= started_at {
+            return Err(TodoError::InvalidTimeEntry(
+                "the entry must stop after it starts".to_string(),
+            ));
+        }
+        let task = self.task_mut(id)?;
+        task.time_entries.push(TimeEntry {
+            started_at,
+            stopped_at: Some(stopped_at),
+        });
+        Ok(())
+    }
+
+    // add_tag tags a task; tags are stored lowercase and a leading '+' is dropped
+    pub fn add_tag(&mut self, id: u32, tag: &str) -> Result<()> {
+        let tag = normalize_tag(tag)?;
+        let task = self.task_mut(id)?;
+        task.tags.insert(tag);
+        Ok(())
+    }
+
+    // set_tags replaces a task's tags, leaving them unchanged if any tag is invalid
+    pub fn set_tags(&mut self, id: u32, tags: &BTreeSet<String>) -> Result<()> {
+        let tags = tags
+            .iter()
+            .map(|tag| normalize_tag(tag))
+            .collect::<Result<BTreeSet<_>>>()?;
+        let task = self.task_mut(id)?;
+        task.tags = tags;
+        Ok(())
+    }
+
+    pub fn remove_tag(&mut self, id: u32, tag: &str) -> Result<()> {
+        let tag = normalize_tag(tag)?;
+        let task = self.task_mut(id)?;
+        task.tags.remove(&tag);
+        Ok(())
+    }
 
-        self.tasks.insert(
-            id,
-            Task {
-                id,
-                description: new_task.description,
-                due_date: new_task.due_date,
-                category: new_task.category,
-                priority: new_task.priority,
-            },
-        );
-        self.next_id += 1;
-        id
+    // rename_tag renames a tag on every task that has it, merging it into the new tag where
+    // a task already has both, and returns the number of tasks changed
+    pub fn rename_tag(&mut self, old: &str, new: &str) -> Result<usize> {
+        let old = normalize_tag(old)?;
+        let new = normalize_tag(new)?;
+        let mut renamed = 0;
+        for task in self.tasks.values_mut() {
+            if task.tags.remove(&old) {
+                task.tags.insert(new.clone());
+                renamed += 1;
+            }
+        }
+        self.changes += renamed as u64;
+        Ok(renamed)
     }
 
-    pub fn remove_task(&mut self, id: u32) -> Result<()> {
-        self.tasks.remove(&id).ok_or(TodoError::TaskNotFound)?;
-        Ok(())
+    // tag_counts lists every tag in use with the number of tasks carrying it, by name
+    pub fn tag_counts(&self) -> Vec<(String, usize)> {
+        let mut counts = BTreeMap::new();
+        for tag in self.tasks.values().flat_map(|task| &task.tags) {
+            *counts.entry(tag.clone()).or_insert(0) += 1;
+        }
+        counts.into_iter().collect()
     }
 
-    pub fn list_tasks(&self, category: Option<&str>) -> Vec<&Task> {
-        self.tasks
-            .values()
-            .filter(|task| category.map_or(true, |c| task.category == c))
-            .collect()
+    // get_categories lists the categories in use, sorted and with spellings that differ only
+    // in case or whitespace shown once
+    pub fn get_categories(&self) -> Vec<String> {
+        let mut categories: BTreeMap<String, String> = BTreeMap::new();
+        for task in self.tasks.values() {
+            let spelling = normalize_category(&task.category);
+            categories
+                .entry(spelling.to_lowercase())
+                .and_modify(|existing| {
+                    if spelling < *existing {
+                        *existing = spelling.clone();
+                    }
+                })
+                .or_insert(spelling);
+        }
+        categories.into_values().collect()
     }
 
-    pub fn update_task(&mut self, id: u32, task_update: TaskUpdate) -> Result<()> {
-        let task = self.tasks.get_mut(&id).ok_or(TodoError::TaskNotFound)?;
+    // category_tree arranges the categories in use by level, parents before their
+    // subcategories and siblings sorted by name. Levels without tasks of their own, like
+    // `Work` when only `Work/ClientA` is used, still appear
+    pub fn category_tree(&self) -> Vec<CategoryNode> {
+        let mut roots: Vec<CategoryNode> = Vec::new();
+        let spellings: BTreeMap<String, String> = self
+            .get_categories()
+            .into_iter()
+            .map(|category| (category.to_lowercase(), category))
+            .collect();
+
+        for task in self.tasks.values() {
+            let key = normalize_category(&task.category).to_lowercase();
+            let Some(category) = spellings.get(&key) else {
+                continue;
+            };
+            let mut level = &mut roots;
+            let mut path = String::new();
+            let levels: Vec<&str> = category.split(CATEGORY_SEPARATOR).collect();
+            for (depth, name) in levels.iter().enumerate() {
+                if !path.is_empty() {
+                    path.push(CATEGORY_SEPARATOR);
+                }
+                path.push_str(name);
+                let index = match level
+                    .iter()
+                    .position(|node| same_category(&node.name, name))
+                {
+                    Some(index) => index,
+                    None => {
+                        level.push(CategoryNode::new(name, path.clone()));
+                        level.len() - 1
+                    }
+                };
+                let node = &mut level[index];
+                node.total += 1;
+                if depth == levels.len() - 1 {
+                    node.tasks += 1;
+                }
+                level = &mut node.children;
+            }
+        }
+        sort_category_nodes(&mut roots);
+        roots
+    }
 
-        if let Some(desc) = task_update.description {
-            task.description = desc.to_string();
+    // canonical_category normalizes a category, reusing the spelling already in use for each
+    // level so "work/clienta" and "Work / ClientA" end up in the same category. Blank
+    // categories become DEFAULT_CATEGORY
+    fn canonical_category(&self, category: &str) -> String {
+        let category = normalize_category(category);
+        if category.is_empty() {
+            return self.canonical_category(DEFAULT_CATEGORY);
+        }
+        let known = self.get_categories();
+        let mut canonical = String::new();
+        for (depth, name) in category.split(CATEGORY_SEPARATOR).enumerate() {
+            let prefix = if canonical.is_empty() {
+                name.to_string()
+            } else {
+                format!("{}{}{}", canonical, CATEGORY_SEPARATOR, name)
+            };
+            // An existing category at or below this level supplies the spelling of the level
+            let existing = known
+                .iter()
+                .filter(|existing| in_category(existing, &prefix))
+                .find_map(|existing| existing.split(CATEGORY_SEPARATOR).nth(depth));
+            if !canonical.is_empty() {
+                canonical.push(CATEGORY_SEPARATOR);
+            }
+            canonical.push_str(existing.unwrap_or(name));
         }
-        if let Some(date) = task_update.due_date {
-            task.due_date = DueDate::On(date)
+        canonical
+    }
+
+    // rename_category renames a category and its subcategories on every task in them and
+    // returns the number of tasks changed. Renaming onto another category in use fails;
+    // merge_categories does that. A blank name is rejected; delete_category moves tasks to
+    // DEFAULT_CATEGORY
+    pub fn rename_category(&mut self, old: &str, new: &str) -> Result<usize> {
+        if normalize_category(new).is_empty() {
+            return Err(TodoError::InvalidCategory(new.to_string()));
         }
-        if let Some(cat) = task_update.category {
-            task.category = cat;
+        let new = normalize_category(new);
+        let taken = self
+            .get_categories()
+            .iter()
+            .any(|c| in_category(c, &new) && !in_category(c, old));
+        if taken {
+            return Err(TodoError::CategoryExists(new));
         }
+        self.recategorize(old, &new, true)
+    }
 
-        Ok(())
+    // merge_categories moves every task in `from` and its subcategories into `into`, keeping
+    // the subcategories under their new parent, and returns the number of tasks moved
+    pub fn merge_categories(&mut self, from: &str, into: &str) -> Result<usize> {
+        let into = self.canonical_category(into);
+        self.recategorize(from, &into, true)
     }
 
-    pub fn get_categories(&self) -> Vec<String> {
-        self.tasks
-            .values()
-            .map(|task| task.category.clone())
-            .collect::<std::collections::HashSet<_>>()
-            .into_iter()
-            .collect()
+    // delete_category moves every task in the category and its subcategories to
+    // DEFAULT_CATEGORY and returns the number of tasks moved
+    pub fn delete_category(&mut self, category: &str) -> Result<usize> {
+        let into = self.canonical_category(DEFAULT_CATEGORY);
+        self.recategorize(category, &into, false)
+    }
+
+    fn recategorize(&mut self, from: &str, into: &str, keep_subcategories: bool) -> Result<usize> {
+        let depth = normalize_category(from).split(CATEGORY_SEPARATOR).count();
+        let mut moved = 0;
+        for task in self.tasks.values_mut() {
+            if !in_category(&task.category, from) {
+                continue;
+            }
+            let mut category = into.to_string();
+            if keep_subcategories {
+                for name in normalize_category(&task.category)
+                    .split(CATEGORY_SEPARATOR)
+                    .skip(depth)
+                {
+                    category.push(CATEGORY_SEPARATOR);
+                    category.push_str(name);
+                }
+            }
+            task.category = category;
+            moved += 1;
+        }
+        self.changes += moved as u64;
+        if moved == 0 {
+            return Err(TodoError::CategoryNotFound(normalize_category(from)));
+        }
+        Ok(moved)
     }
 
+    // save_to_file saves the list in the current format version, see migrations, keeping
+    // DEFAULT_BACKUPS earlier versions of the file
     pub fn save_to_file(&self, filename: &str) -> Result<()> {
-        let json = serde_json::to_string(self)?;
-        fs::write(filename, json)?;
+        self.save_to_file_with_backups(filename, DEFAULT_BACKUPS)
+    }
+
+    // save_to_file_with_backups saves the list without ever leaving a partly written file,
+    // keeping `backups` earlier versions as `<filename>.bak.1` onwards
+    pub fn save_to_file_with_backups(&self, filename: &str, backups: usize) -> Result<()> {
+        let json = migrations::to_json(self)?;
+        fileio::write_atomic(Path::new(filename), json.as_bytes(), backups)?;
         Ok(())
     }
 
+    // load_from_file loads a list saved by this or any earlier version
     pub fn load_from_file(filename: &str) -> Result<Self> {
         let json = fs::read_to_string(filename)?;
-        let todo_list: TodoList = serde_json::from_str(&json)?;
-        Ok(todo_list)
+        migrations::from_json(&json)
     }
 }
 
@@ -198,7 +1574,7 @@ mod tests {
 
         assert!(list.remove_task(id).is_ok());
         assert!(list.get_task(id).is_none());
-        assert!(!list.remove_task(id).is_ok());
+        assert!(list.remove_task(id).is_err());
     }
 
     #[test]
@@ -211,14 +1587,16 @@ mod tests {
             priority: Priority::Low,
         });
 
-        let new_description = Some("Updated task".to_string());
-        let new_due_date = Some(NaiveDate::from_ymd(2023, 12, 31));
-        let new_category = Some("Updated".to_string());
+        let new_description = Patch::Set("Updated task".to_string());
+        let new_due_date = Patch::Set(NaiveDate::from_ymd_opt(2023, 12, 31).unwrap());
+        let new_category = Patch::Set("Updated".to_string());
 
         let new_task_update = TaskUpdate {
             description: new_description,
             due_date: new_due_date,
             category: new_category,
+            priority: Patch::Set(Priority::High),
+            ..TaskUpdate::default()
         };
 
         assert!(list.update_task(id, new_task_update).is_ok());
@@ -226,10 +1604,11 @@ mod tests {
         let task = list.get_task(id).unwrap();
         assert_eq!(task.description, "Updated task");
         match task.due_date {
-            DueDate::On(date) => assert_eq!(date, NaiveDate::from_ymd(2023, 12, 31)),
+            DueDate::On(date) => assert_eq!(date, NaiveDate::from_ymd_opt(2023, 12, 31).unwrap()),
             _ => panic!("Expected DueDate::On"),
         }
         assert_eq!(task.category, "Updated");
+        assert_eq!(task.priority, Priority::High);
     }
 
     #[test]
@@ -259,4 +1638,695 @@ mod tests {
         assert!(categories.contains(&"Work".to_string()));
         assert!(categories.contains(&"Personal".to_string()));
     }
+
+    #[test]
+    fn test_list_tasks_ordered_by_priority_then_due_date() {
+        let mut list = TodoList::new();
+        let date = |d| DueDate::On(NaiveDate::from_ymd_opt(2024, 5, d).unwrap());
+        for (description, due_date, priority) in [
+            ("low", date(1), Priority::Low),
+            ("high undated", DueDate::None, Priority::High),
+            ("high late", date(20), Priority::High),
+            ("high early", date(3), Priority::High),
+            ("medium", DueDate::None, Priority::Medium),
+        ] {
+            list.add_task(NewTask {
+                description: description.to_string(),
+                due_date,
+                category: "Test".to_string(),
+                priority,
+            });
+        }
+
+        let order: Vec<&str> = list
+            .list_tasks(None)
+            .iter()
+            .map(|task| task.description.as_str())
+            .collect();
+        assert_eq!(
+            order,
+            ["high early", "high late", "high undated", "medium", "low"]
+        );
+    }
+
+    #[test]
+    fn test_due_date_queries() {
+        let mut list = TodoList::new();
+        let today = NaiveDate::from_ymd_opt(2024, 5, 10).unwrap();
+        let day = |d| NaiveDate::from_ymd_opt(2024, 5, d).unwrap();
+        for (description, due_date) in [
+            ("missed", DueDate::On(day(9))),
+            ("deadline passed", DueDate::Before(day(9))),
+            ("today", DueDate::On(day(10))),
+            ("deadline today", DueDate::Before(day(10))),
+            ("this week", DueDate::Before(day(15))),
+            ("next month", DueDate::On(day(31))),
+            ("someday", DueDate::None),
+        ] {
+            list.add_task(NewTask {
+                description: description.to_string(),
+                due_date,
+                category: "Test".to_string(),
+                priority: Priority::Low,
+            });
+        }
+        list.complete_task(2).unwrap();
+
+        let descriptions = |tasks: Vec<&Task>| -> Vec<String> {
+            tasks.iter().map(|t| t.description.clone()).collect()
+        };
+        assert_eq!(descriptions(list.overdue_tasks(today)), ["missed"]);
+        assert_eq!(
+            descriptions(list.due_today_tasks(today)),
+            ["today", "deadline today"]
+        );
+        assert_eq!(
+            descriptions(list.due_within_days(today, 7)),
+            ["today", "deadline today", "this week"]
+        );
+        assert_eq!(
+            descriptions(list.due_within_days(today, 30)),
+            ["today", "deadline today", "this week", "next month"]
+        );
+    }
+
+    #[test]
+    fn test_due_on_counts_only_on_its_day() {
+        let day = |d| NaiveDate::from_ymd_opt(2024, 5, d).unwrap();
+        let due = DueDate::On(day(15));
+        assert!(!due.is_due_within(day(10), 0));
+        assert!(!due.is_due_within(day(10), 4));
+        assert!(due.is_due_within(day(10), 5));
+        assert!(due.is_due_within(day(15), 0));
+        assert!(!due.is_due_within(day(16), 30));
+        assert!(!due.is_overdue(day(15)));
+        assert!(due.is_overdue(day(16)));
+    }
+
+    #[test]
+    fn test_due_before_counts_once_its_deadline_is_near() {
+        let day = |d| NaiveDate::from_ymd_opt(2024, 5, d).unwrap();
+        let due = DueDate::Before(day(15));
+        assert!(!due.is_due_within(day(10), 0));
+        assert!(!due.is_due_within(day(10), 4));
+        assert!(due.is_due_within(day(10), 5));
+        assert!(due.is_due_within(day(15), 0));
+        let next_year = DueDate::Before(NaiveDate::from_ymd_opt(2025, 5, 10).unwrap());
+        assert!(!next_year.is_due_within(day(10), 30));
+        assert!(!due.is_due_within(day(16), 30));
+        assert!(!due.is_overdue(day(15)));
+        assert!(due.is_overdue(day(16)));
+        assert!(!DueDate::None.is_due_within(day(10), 30));
+        assert!(!DueDate::None.is_overdue(day(10)));
+    }
+
+    #[test]
+    fn test_update_task_due_date_mode() {
+        let mut list = TodoList::new();
+        let date = NaiveDate::from_ymd_opt(2024, 5, 10).unwrap();
+        let id = list.add_task(NewTask {
+            description: "Test task".to_string(),
+            due_date: DueDate::On(date),
+            category: "Test".to_string(),
+            priority: Priority::Low,
+        });
+
+        let update = |due_date| TaskUpdate::default().with_due_date(due_date);
+        list.update_task(id, update(DueDate::Before(date))).unwrap();
+        assert_eq!(list.get_task(id).unwrap().due_date, DueDate::Before(date));
+        list.update_task(id, update(DueDate::None)).unwrap();
+        assert_eq!(list.get_task(id).unwrap().due_date, DueDate::None);
+    }
+
+    #[test]
+    fn test_update_patches_keep_set_and_clear() {
+        let mut list = TodoList::new();
+        let date = NaiveDate::from_ymd_opt(2024, 5, 10).unwrap();
+        let id = list.add_task(NewTask {
+            description: "Test task".to_string(),
+            due_date: DueDate::Before(date),
+            category: "Work".to_string(),
+            priority: Priority::High,
+        });
+
+        let mode_only = TaskUpdate {
+            due_mode: Patch::Set(DueMode::On),
+            ..TaskUpdate::default()
+        };
+        list.update_task(id, mode_only).unwrap();
+        assert_eq!(list.get_task(id).unwrap().due_date, DueDate::On(date));
+
+        let clear_all = TaskUpdate {
+            due_date: Patch::Clear,
+            category: Patch::Clear,
+            priority: Patch::Clear,
+            ..TaskUpdate::default()
+        };
+        list.update_task(id, clear_all).unwrap();
+        let task = list.get_task(id).unwrap();
+        assert_eq!(task.due_date, DueDate::None);
+        assert_eq!(task.category, DEFAULT_CATEGORY);
+        assert_eq!(task.priority, Priority::Low);
+        assert_eq!(task.description, "Test task");
+
+        let clear_description = TaskUpdate {
+            description: Patch::Clear,
+            priority: Patch::Set(Priority::High),
+            ..TaskUpdate::default()
+        };
+        assert!(matches!(
+            list.update_task(id, clear_description),
+            Err(TodoError::InvalidPatch(_))
+        ));
+        assert_eq!(list.get_task(id).unwrap().priority, Priority::Low);
+    }
+
+    #[test]
+    fn test_merge_patch() {
+        let update = TaskUpdate::from_merge_patch(
+            r#"{"due_date": "2026-11-01", "due_mode": "by", "category": null, "priority": "high"}"#,
+        )
+        .unwrap();
+        assert_eq!(
+            update,
+            TaskUpdate {
+                due_date: Patch::Set(NaiveDate::from_ymd_opt(2026, 11, 1).unwrap()),
+                due_mode: Patch::Set(DueMode::Before),
+                category: Patch::Clear,
+                priority: Patch::Set(Priority::High),
+                ..TaskUpdate::default()
+            }
+        );
+        assert_eq!(
+            serde_json::to_string(&update).unwrap(),
+            r#"{"due_date":"2026-11-01","due_mode":"before","category":null,"priority":"High"}"#
+        );
+        assert_eq!(
+            TaskUpdate::from_merge_patch("{}").unwrap(),
+            TaskUpdate::default()
+        );
+        for invalid in [r#"{"prio": "high"}"#, r#"{"priority": "urgent"}"#, "[]"] {
+            assert!(matches!(
+                TaskUpdate::from_merge_patch(invalid),
+                Err(TodoError::InvalidPatch(_))
+            ));
+        }
+    }
+
+    #[test]
+    fn test_undo_redo() {
+        let mut list = TodoList::new();
+        let new_task = |description: &str| NewTask {
+            description: description.to_string(),
+            due_date: DueDate::On(NaiveDate::from_ymd_opt(2026, 11, 1).unwrap()),
+            category: "Work".to_string(),
+            priority: Priority::Low,
+        };
+        let parent = list.add_task(new_task("Release"));
+        let child = list.add_subtask(parent, new_task("Changelog")).unwrap();
+        let other = list.add_task(new_task("Announce"));
+        list.add_dependency(other, parent).unwrap();
+        list.update_task(
+            parent,
+            TaskUpdate {
+                description: Patch::Set("Release 1.0".to_string()),
+                due_date: Patch::Clear,
+                priority: Patch::Set(Priority::High),
+                ..TaskUpdate::default()
+            },
+        )
+        .unwrap();
+        list.remove_task(parent).unwrap();
+        assert_eq!(list.get_task(child).unwrap().parent, None);
+        assert!(list.get_task(other).unwrap().blocked_by.is_empty());
+
+        let undone = list.undo().unwrap();
+        assert_eq!((undone.action, undone.id), (Action::Remove, parent));
+        assert_eq!(list.get_task(child).unwrap().parent, Some(parent));
+        assert!(list.get_task(other).unwrap().blocked_by.contains(&parent));
+
+        assert_eq!(list.undo().unwrap().action, Action::Update);
+        let task = list.get_task(parent).unwrap();
+        assert_eq!(task.description, "Release");
+        assert_eq!(task.priority, Priority::Low);
+        assert!(task.due_date.date().is_some());
+
+        list.redo().unwrap();
+        assert_eq!(list.get_task(parent).unwrap().description, "Release 1.0");
+        assert_eq!(list.get_task(parent).unwrap().due_date, DueDate::None);
+
+        // Undoing an add removes the task, and redoing it brings the same task back
+        list.undo().unwrap();
+        list.undo().unwrap();
+        assert!(list.get_task(other).is_none());
+        list.redo().unwrap();
+        assert_eq!(list.get_task(other).unwrap().description, "Announce");
+
+        // A new change drops whatever could still be redone
+        list.add_task(new_task("Party"));
+        assert!(matches!(list.redo(), Err(TodoError::NothingToRedo)));
+        while list.undo().is_ok() {}
+        assert!(list.list_tasks(None).is_empty());
+        assert!(matches!(list.undo(), Err(TodoError::NothingToUndo)));
+    }
+
+    #[test]
+    fn test_export_and_import_tasks() {
+        let mut source = TodoList::new();
+        let new_task = |description: &str| NewTask {
+            description: description.to_string(),
+            due_date: DueDate::None,
+            category: "work".to_string(),
+            priority: Priority::Low,
+        };
+        let outside = source.add_task(new_task("Budget"));
+        let parent = source.add_task(new_task("Release"));
+        let child = source.add_subtask(parent, new_task("Changelog")).unwrap();
+        let grandchild = source.add_subtask(child, new_task("Collect PRs")).unwrap();
+        source.add_dependency(parent, child).unwrap();
+        source.add_dependency(parent, outside).unwrap();
+
+        let tasks = source.export_tasks(parent).unwrap();
+        let ids: Vec<u32> = tasks.iter().map(|task| task.id).collect();
+        assert_eq!(ids, [parent, child, grandchild]);
+
+        let mut target = TodoList::new();
+        target.add_task(NewTask {
+            category: "Work".to_string(),
+            ..new_task("Existing")
+        });
+        let new_ids = target.import_tasks(tasks);
+        assert_eq!(new_ids, [2, 3, 4]);
+        let release = target.get_task(2).unwrap();
+        assert_eq!(release.category, "Work");
+        assert_eq!(release.blocked_by, BTreeSet::from([3]));
+        assert_eq!(target.get_task(3).unwrap().parent, Some(2));
+        assert_eq!(target.get_task(4).unwrap().parent, Some(3));
+        assert_eq!(target.next_id(), 5);
+
+        target.undo().unwrap();
+        assert_eq!(target.list_tasks(None).len(), 1);
+    }
+
+    #[test]
+    fn test_changes_count_mutations() {
+        let mut list = TodoList::new();
+        let id = list.add_task(NewTask {
+            description: "Budget".to_string(),
+            due_date: DueDate::None,
+            category: "Work".to_string(),
+            priority: Priority::Low,
+        });
+        let changes = list.changes();
+        assert!(changes > 0);
+
+        assert!(list.get_task(id).is_some());
+        list.list_tasks(None);
+        assert!(list.set_notes(id + 1, "missing").is_err());
+        assert_eq!(list.changes(), changes);
+
+        list.add_tag(id, "finance").unwrap();
+        assert!(list.changes() > changes);
+        let changes = list.changes();
+        list.undo().unwrap();
+        assert!(list.changes() > changes);
+    }
+
+    #[test]
+    fn test_history_persists_only_when_asked() {
+        let mut list = TodoList::new();
+        let id = list.add_task(NewTask {
+            description: "Task".to_string(),
+            due_date: DueDate::None,
+            category: "Test".to_string(),
+            priority: Priority::Low,
+        });
+        list.update_task(id, TaskUpdate::default().with_due_date(DueDate::None))
+            .unwrap();
+
+        let json = serde_json::to_string(&list).unwrap();
+        assert!(!json.contains("history"));
+        let mut reloaded: TodoList = serde_json::from_str(&json).unwrap();
+        assert!(matches!(reloaded.undo(), Err(TodoError::NothingToUndo)));
+
+        list.history_mut().set_persisted(true);
+        let json = serde_json::to_string(&list).unwrap();
+        let mut reloaded: TodoList = serde_json::from_str(&json).unwrap();
+        assert!(reloaded.history().is_persisted());
+        assert_eq!(reloaded.undo().unwrap().action, Action::Update);
+        assert_eq!(reloaded.undo().unwrap().action, Action::Add);
+        assert!(reloaded.get_task(id).is_none());
+    }
+
+    #[test]
+    fn test_task_lifecycle() {
+        let mut list = TodoList::new();
+        let id = list.add_task(NewTask {
+            description: "Test task".to_string(),
+            due_date: DueDate::None,
+            category: "Test".to_string(),
+            priority: Priority::Low,
+        });
+        assert_eq!(list.get_task(id).unwrap().status, Status::NotStarted);
+
+        list.start_task(id).unwrap();
+        assert_eq!(list.get_task(id).unwrap().status, Status::InProgress);
+        assert_eq!(
+            list.list_tasks_with_status(None, Some(Status::InProgress))
+                .len(),
+            1
+        );
+
+        list.complete_task(id).unwrap();
+        let task = list.get_task(id).unwrap();
+        assert_eq!(task.status, Status::Completed);
+        assert!(task.completed_at.is_some());
+        assert!(list
+            .list_tasks_with_status(None, Some(Status::NotStarted))
+            .is_empty());
+
+        list.reopen_task(id).unwrap();
+        let task = list.get_task(id).unwrap();
+        assert_eq!(task.status, Status::NotStarted);
+        assert!(task.completed_at.is_none());
+
+        assert!(matches!(
+            list.complete_task(99),
+            Err(TodoError::TaskNotFound)
+        ));
+    }
+
+    #[test]
+    fn test_complete_recurring_task_spawns_next_occurrence() {
+        use crate::recurrence::{Frequency, RecurrenceEnd};
+
+        let mut list = TodoList::new();
+        let due = NaiveDate::from_ymd_opt(2024, 5, 10).unwrap();
+        let id = list.add_task(NewTask {
+            description: "Water plants".to_string(),
+            due_date: DueDate::Before(due),
+            category: "Home".to_string(),
+            priority: Priority::Medium,
+        });
+        list.set_recurrence(
+            id,
+            Some(Recurrence {
+                frequency: Frequency::Daily,
+                end: RecurrenceEnd::Count(2),
+            }),
+        )
+        .unwrap();
+
+        let next_id = list.complete_task(id).unwrap().unwrap();
+        let next = list.get_task(next_id).unwrap();
+        assert_eq!(next.description, "Water plants");
+        assert_eq!(next.status, Status::NotStarted);
+        assert_eq!(next.due_date, DueDate::Before(due.succ_opt().unwrap()));
+        assert_eq!(next.recurs_from, Some(id));
+        assert_eq!(list.get_task(id).unwrap().next_occurrence, Some(next_id));
+        assert_eq!(list.occurrence_history(next_id)[0].id, id);
+
+        // Completing again does not spawn twice, and the last occurrence ends the series
+        list.reopen_task(id).unwrap();
+        assert_eq!(list.complete_task(id).unwrap(), None);
+        assert_eq!(list.complete_task(next_id).unwrap(), None);
+        assert_eq!(list.list_tasks(None).len(), 2);
+    }
+
+    #[test]
+    fn test_subtasks() {
+        let mut list = TodoList::new();
+        let new_task = |description: &str| NewTask {
+            description: description.to_string(),
+            due_date: DueDate::None,
+            category: "Test".to_string(),
+            priority: Priority::Low,
+        };
+        let release = list.add_task(new_task("Release"));
+        let docs = list.add_subtask(release, new_task("Docs")).unwrap();
+        let changelog = list.add_subtask(docs, new_task("Changelog")).unwrap();
+        let tag = list.add_subtask(release, new_task("Tag")).unwrap();
+        list.complete_task(changelog).unwrap();
+
+        assert_eq!(
+            list.progress(release),
+            Progress {
+                completed: 1,
+                total: 3
+            }
+        );
+        let tree: Vec<(usize, u32)> = list.task_tree().iter().map(|(d, t)| (*d, t.id)).collect();
+        assert_eq!(tree, [(0, release), (1, docs), (2, changelog), (1, tag)]);
+
+        assert!(matches!(
+            list.move_task(release, Some(changelog)),
+            Err(TodoError::InvalidParent(_, _))
+        ));
+        list.move_task(docs, Some(tag)).unwrap();
+        assert_eq!(list.get_task(docs).unwrap().parent, Some(tag));
+        assert_eq!(list.get_task(changelog).unwrap().parent, Some(docs));
+
+        list.remove_task(docs).unwrap();
+        assert_eq!(list.get_task(changelog).unwrap().parent, Some(tag));
+
+        let mut removed = list
+            .remove_task_with(release, RemoveSubtasks::Cascade)
+            .unwrap();
+        removed.sort();
+        assert_eq!(removed, [release, changelog, tag]);
+        assert!(list.list_tasks(None).is_empty());
+    }
+
+    #[test]
+    fn test_dependencies() {
+        let mut list = TodoList::new();
+        let new_task = |description: &str| NewTask {
+            description: description.to_string(),
+            due_date: DueDate::None,
+            category: "Test".to_string(),
+            priority: Priority::Low,
+        };
+        let design = list.add_task(new_task("Design"));
+        let build = list.add_task(new_task("Build"));
+        let ship = list.add_task(new_task("Ship"));
+        list.add_dependency(build, design).unwrap();
+        list.add_dependency(ship, build).unwrap();
+
+        assert!(matches!(
+            list.add_dependency(design, ship),
+            Err(TodoError::DependencyCycle(_, _))
+        ));
+        assert!(matches!(
+            list.add_dependency(design, design),
+            Err(TodoError::DependencyCycle(_, _))
+        ));
+
+        let actionable = |list: &TodoList| -> Vec<u32> {
+            list.actionable_tasks().iter().map(|t| t.id).collect()
+        };
+        assert_eq!(actionable(&list), [design]);
+        list.complete_task(design).unwrap();
+        assert_eq!(actionable(&list), [build]);
+        assert_eq!(list.dependents(build)[0].id, ship);
+
+        let release = list.add_task(new_task("Release"));
+        let notes = list.add_subtask(release, new_task("Notes")).unwrap();
+        let announce = list.add_task(new_task("Announce"));
+        list.add_dependency(announce, notes).unwrap();
+        list.add_dependency(release, notes).unwrap();
+        let ids = |tasks: Vec<&Task>| -> Vec<u32> { tasks.iter().map(|t| t.id).collect() };
+        assert!(list
+            .removal_dependents(release, RemoveSubtasks::Promote)
+            .is_empty());
+        assert_eq!(
+            ids(list.removal_dependents(release, RemoveSubtasks::Cascade)),
+            [announce]
+        );
+        list.remove_task_with(release, RemoveSubtasks::Cascade)
+            .unwrap();
+        list.remove_task(announce).unwrap();
+
+        list.remove_task(build).unwrap();
+        assert!(list.get_task(ship).unwrap().blocked_by.is_empty());
+        assert_eq!(actionable(&list), [ship]);
+    }
+
+    #[test]
+    fn test_notes_and_checklist() {
+        let mut list = TodoList::new();
+        let id = list.add_task(NewTask {
+            description: "Pack for trip".to_string(),
+            due_date: DueDate::None,
+            category: "Home".to_string(),
+            priority: Priority::Low,
+        });
+        list.set_notes(id, "Flight at 9\nCheck in online\n\n")
+            .unwrap();
+        assert_eq!(
+            list.get_task(id).unwrap().notes,
+            "Flight at 9\nCheck in online"
+        );
+
+        assert_eq!(list.get_task(id).unwrap().checklist_percent(), None);
+        for item in ["Passport", "Charger", "Tickets"] {
+            list.add_checklist_item(id, item).unwrap();
+        }
+        assert!(list.toggle_checklist_item(id, 1).unwrap());
+        assert_eq!(list.get_task(id).unwrap().checklist_percent(), Some(33));
+        assert!(!list.toggle_checklist_item(id, 1).unwrap());
+        assert_eq!(list.remove_checklist_item(id, 2).unwrap().text, "Charger");
+        list.toggle_checklist_item(id, 2).unwrap();
+        assert_eq!(list.get_task(id).unwrap().checklist_percent(), Some(50));
+
+        for number in [0, 3] {
+            assert!(matches!(
+                list.toggle_checklist_item(id, number),
+                Err(TodoError::ChecklistItemNotFound(_, n)) if n == number
+            ));
+        }
+    }
+
+    #[test]
+    fn test_tags() {
+        let mut list = TodoList::new();
+        let new_task = |description: &str| NewTask {
+            description: description.to_string(),
+            due_date: DueDate::None,
+            category: "Work".to_string(),
+            priority: Priority::Low,
+        };
+        let call = list.add_task(new_task("Call client"));
+        let email = list.add_task(new_task("Email client"));
+        list.add_tag(call, "+Urgent-Client").unwrap();
+        list.add_tag(call, "phone").unwrap();
+        list.add_tag(email, "urgent-client").unwrap();
+        assert!(matches!(
+            list.add_tag(email, "two words"),
+            Err(TodoError::InvalidTag(_))
+        ));
+
+        assert_eq!(
+            list.tag_counts(),
+            [("phone".to_string(), 1), ("urgent-client".to_string(), 2)]
+        );
+
+        assert_eq!(list.rename_tag("urgent-client", "phone").unwrap(), 2);
+        assert_eq!(list.tag_counts(), [("phone".to_string(), 2)]);
+
+        list.remove_tag(call, "phone").unwrap();
+        assert!(list.get_task(call).unwrap().tags.is_empty());
+    }
+
+    #[test]
+    fn test_category_management() {
+        let mut list = TodoList::new();
+        let mut add = |category: &str| {
+            list.add_task(NewTask {
+                description: "Task".to_string(),
+                due_date: DueDate::None,
+                category: category.to_string(),
+                priority: Priority::Low,
+            })
+        };
+        add("Work");
+        add(" work ");
+        add("Hmoe");
+        add("Home");
+        add("Errands");
+        assert_eq!(list.get_categories(), ["Errands", "Hmoe", "Home", "Work"]);
+        assert_eq!(list.list_tasks(Some("WORK")).len(), 2);
+
+        assert!(matches!(
+            list.rename_category("Hmoe", "home"),
+            Err(TodoError::CategoryExists(_))
+        ));
+        assert!(matches!(
+            list.rename_category("work", "  "),
+            Err(TodoError::InvalidCategory(_))
+        ));
+        assert_eq!(list.rename_category("work", "Office").unwrap(), 2);
+        assert_eq!(list.merge_categories("hmoe", "HOME").unwrap(), 1);
+        assert_eq!(list.delete_category("errands").unwrap(), 1);
+        assert_eq!(list.get_categories(), ["General", "Home", "Office"]);
+        assert!(matches!(
+            list.delete_category("Errands"),
+            Err(TodoError::CategoryNotFound(_))
+        ));
+    }
+
+    #[test]
+    fn test_hierarchical_categories() {
+        let mut list = TodoList::new();
+        let mut add = |category: &str| {
+            list.add_task(NewTask {
+                description: "Task".to_string(),
+                due_date: DueDate::None,
+                category: category.to_string(),
+                priority: Priority::Low,
+            })
+        };
+        add("Work/ClientA/Bugs");
+        add(" work / clienta ");
+        add("Work/ClientB");
+        add("Workshop");
+        assert_eq!(
+            list.get_categories(),
+            [
+                "Work/ClientA",
+                "Work/ClientA/Bugs",
+                "Work/ClientB",
+                "Workshop"
+            ]
+        );
+        assert_eq!(list.list_tasks(Some("work")).len(), 3);
+        assert_eq!(list.list_tasks(Some("Work/ClientA")).len(), 2);
+
+        let tree = list.category_tree();
+        assert_eq!(tree.len(), 2);
+        assert_eq!(
+            (tree[0].path.as_str(), tree[0].tasks, tree[0].total),
+            ("Work", 0, 3)
+        );
+        let client_a = &tree[0].children[0];
+        assert_eq!(
+            (client_a.name.as_str(), client_a.tasks, client_a.total),
+            ("ClientA", 1, 2)
+        );
+        assert_eq!(client_a.children[0].path, "Work/ClientA/Bugs");
+
+        assert_eq!(
+            list.rename_category("Work/ClientA", "Work/Acme").unwrap(),
+            2
+        );
+        assert_eq!(
+            list.merge_categories("Work/ClientB", "work/acme").unwrap(),
+            1
+        );
+        assert_eq!(
+            list.get_categories(),
+            ["Work/Acme", "Work/Acme/Bugs", "Workshop"]
+        );
+        assert_eq!(list.delete_category("Work").unwrap(), 3);
+        assert_eq!(list.get_categories(), ["General", "Workshop"]);
+    }
+
+    #[test]
+    fn test_load_task_without_status() {
+        let json = r#"{"tasks":{"1":{"id":1,"description":"Old task","due_date":"None","category":"General","priority":"High"}},"next_id":2}"#;
+        let list: TodoList = serde_json::from_str(json).unwrap();
+        let task = list.get_task(1).unwrap();
+        assert_eq!(task.status, Status::NotStarted);
+        assert!(task.completed_at.is_none());
+    }
+
+    #[test]
+    fn test_parse_priority() {
+        assert_eq!("high".parse::<Priority>().unwrap(), Priority::High);
+        assert_eq!(" Medium ".parse::<Priority>().unwrap(), Priority::Medium);
+        assert_eq!("l".parse::<Priority>().unwrap(), Priority::Low);
+        assert!(matches!(
+            "urgent".parse::<Priority>(),
+            Err(TodoError::InvalidPriority(_))
+        ));
+    }
 }
@@ -0,0 +1,358 @@
+use crate::fileio::{self, ListLock};
+use crate::sqlite::DATABASE_EXTENSIONS;
+use crate::storage::{self, Storage};
+use crate::todo::{RemoveSubtasks, TodoError, TodoList};
+use std::collections::BTreeSet;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+type Result<T> = std::result::Result<T, TodoError>;
+
+// DEFAULT_LIST is the list a new workspace starts with
+pub const DEFAULT_LIST: &str = "default";
+
+// CURRENT_FILE is the file in the workspace directory naming the current list
+const CURRENT_FILE: &str = ".current";
+
+// Workspace is a directory of named lists, each kept in its own list file named after the
+// list, such as `work.json` or `archive.db`. Every list has its own task IDs. One list is the
+// current one, which the caller keeps open; the workspace opens the others as needed
+#[derive(Debug)]
+pub struct Workspace {
+    dir: PathBuf,
+    backups: usize,
+    current: String,
+}
+
+impl Workspace {
+    // open opens the workspace in `dir`, creating the directory and a DEFAULT_LIST when there
+    // are no lists yet. The current list is the one last switched to if it still exists, then
+    // DEFAULT_LIST if there is one, and the first list by name otherwise
+    pub fn open(dir: impl AsRef<Path>, backups: usize) -> Result<Workspace> {
+        let dir = dir.as_ref().to_path_buf();
+        fs::create_dir_all(&dir)?;
+        let mut workspace = Workspace {
+            dir,
+            backups,
+            current: DEFAULT_LIST.to_string(),
+        };
+        let names = workspace.names()?;
+        let saved = fs::read_to_string(workspace.dir.join(CURRENT_FILE)).unwrap_or_default();
+        let saved = saved.trim_end_matches('\n');
+        match names.first() {
+            None => workspace.create(DEFAULT_LIST)?,
+            Some(_) if names.iter().any(|name| name == saved) => {
+                workspace.current = saved.to_string()
+            }
+            Some(first) if !names.iter().any(|name| name == DEFAULT_LIST) => {
+                workspace.current = first.clone()
+            }
+            Some(_) => {}
+        }
+        Ok(workspace)
+    }
+
+    pub fn dir(&self) -> &Path {
+        &self.dir
+    }
+
+    // current is the name of the current list
+    pub fn current(&self) -> &str {
+        &self.current
+    }
+
+    // names lists the names of the lists in the workspace, in order
+    pub fn names(&self) -> Result<Vec<String>> {
+        let mut names = BTreeSet::new();
+        for entry in fs::read_dir(&self.dir)? {
+            let path = entry?.path();
+            let is_list = path
+                .extension()
+                .and_then(|extension| extension.to_str())
+                .is_some_and(is_list_extension);
+            let Some(name) = path.file_stem().and_then(|stem| stem.to_str()) else {
+                continue;
+            };
+            if is_list && path.is_file() && validate_name(name).is_ok() {
+                names.insert(name.to_string());
+            }
+        }
+        Ok(names.into_iter().collect())
+    }
+
+    pub fn contains(&self, name: &str) -> bool {
+        validate_name(name).is_ok() && self.existing_path(name).is_some()
+    }
+
+    // path is the list file of the list `name`. Lists that don't exist yet get a JSON file
+    pub fn path(&self, name: &str) -> PathBuf {
+        self.existing_path(name)
+            .unwrap_or_else(|| self.dir.join(format!("{}.json", name)))
+    }
+
+    fn existing_path(&self, name: &str) -> Option<PathBuf> {
+        std::iter::once("json")
+            .chain(DATABASE_EXTENSIONS)
+            .map(|extension| self.dir.join(format!("{}.{}", name, extension)))
+            .find(|path| path.is_file())
+    }
+
+    // open_list opens and loads the list `name`, locking its file
+    pub fn open_list(&self, name: &str) -> Result<(Box<dyn Storage>, TodoList)> {
+        if !self.contains(name) {
+            return Err(TodoError::ListNotFound(name.to_string()));
+        }
+        let mut storage = storage::open_file(self.path(name), false, self.backups)?;
+        let todo_list = storage.load()?;
+        Ok((storage, todo_list))
+    }
+
+    // create adds an empty list named `name`
+    pub fn create(&mut self, name: &str) -> Result<()> {
+        validate_name(name)?;
+        if self.contains(name) {
+            return Err(TodoError::ListExists(name.to_string()));
+        }
+        let mut storage = storage::open_file(self.path(name), false, self.backups)?;
+        storage.save(&TodoList::new())
+    }
+
+    // switch makes `name` the current list, here and the next time the workspace is opened,
+    // and opens it. The caller closes the list it had open first, since its file stays
+    // locked until then
+    pub fn switch(&mut self, name: &str) -> Result<(Box<dyn Storage>, TodoList)> {
+        let opened = self.open_list(name)?;
+        self.set_current(name)?;
+        Ok(opened)
+    }
+
+    fn set_current(&mut self, name: &str) -> Result<()> {
+        let line = format!("{}\n", name);
+        fileio::write_atomic(&self.dir.join(CURRENT_FILE), line.as_bytes(), 0)?;
+        self.current = name.to_string();
+        Ok(())
+    }
+
+    // rename renames a list and its file. A list open elsewhere, including the current list
+    // while the caller has it open, is locked and cannot be renamed
+    pub fn rename(&mut self, name: &str, new_name: &str) -> Result<()> {
+        validate_name(new_name)?;
+        let path = self
+            .existing_path(name)
+            .ok_or_else(|| TodoError::ListNotFound(name.to_string()))?;
+        if self.contains(new_name) {
+            return Err(TodoError::ListExists(new_name.to_string()));
+        }
+        let extension = path.extension().unwrap_or_default().to_string_lossy();
+        let new_path = self.dir.join(format!("{}.{}", new_name, extension));
+        let _lock = ListLock::acquire(&path)?;
+        fs::rename(&path, &new_path)?;
+        if self.current == name {
+            self.set_current(new_name)?;
+        }
+        Ok(())
+    }
+
+    // delete deletes a list and its file, keeping any backups of it. The current list cannot
+    // be deleted
+    pub fn delete(&mut self, name: &str) -> Result<()> {
+        if self.current == name {
+            return Err(TodoError::CurrentList(name.to_string()));
+        }
+        let path = self
+            .existing_path(name)
+            .ok_or_else(|| TodoError::ListNotFound(name.to_string()))?;
+        let _lock = ListLock::acquire(&path)?;
+        fs::remove_file(&path)?;
+        Ok(())
+    }
+
+    // copy_task copies a task and its subtasks from `from`, the current list, to the list
+    // `to`, where they get new IDs, and saves that list. It returns the new IDs
+    pub fn copy_task(&self, from: &TodoList, id: u32, to: &str) -> Result<Vec<u32>> {
+        if self.current == to {
+            return Err(TodoError::CurrentList(to.to_string()));
+        }
+        let tasks = from.export_tasks(id)?;
+        let (mut storage, mut todo_list) = self.open_list(to)?;
+        let ids = todo_list.import_tasks(tasks);
+        storage.save(&todo_list)?;
+        Ok(ids)
+    }
+
+    // move_task copies a task and its subtasks to the list `to` as copy_task does, then
+    // removes them from `from` and saves it to `storage`. When that save fails, the tasks are
+    // put back in `from` and the copies removed from `to`, so the task is never in both lists
+    // or in neither
+    pub fn move_task(
+        &self,
+        from: &mut TodoList,
+        storage: &mut dyn Storage,
+        id: u32,
+        to: &str,
+    ) -> Result<Vec<u32>> {
+        let ids = self.copy_task(from, id, to)?;
+        let before = from.clone();
+        let saved = from
+            .remove_task_with(id, RemoveSubtasks::Cascade)
+            .and_then(|_| storage.save(from));
+        if let Err(e) = saved {
+            *from = before;
+            // The move failing is what gets reported; failing to take the copies back as
+            // well would leave the task in both lists, as after a copy
+            let _ = self.discard_tasks(to, &ids);
+            return Err(e);
+        }
+        Ok(ids)
+    }
+
+    // discard_tasks drops tasks from the list `to` without recording it in its history
+    fn discard_tasks(&self, to: &str, ids: &[u32]) -> Result<()> {
+        let (mut storage, mut todo_list) = self.open_list(to)?;
+        for &id in ids {
+            todo_list.discard_task(id);
+        }
+        storage.save(&todo_list)
+    }
+}
+
+fn is_list_extension(extension: &str) -> bool {
+    extension == "json" || DATABASE_EXTENSIONS.contains(&extension)
+}
+
+// validate_name checks that a list name makes a file name in the workspace directory
+fn validate_name(name: &str) -> Result<()> {
+    if name.trim().is_empty()
+        || name != name.trim()
+        || name.starts_with('.')
+        || name.contains(['/', '\\'])
+    {
+        return Err(TodoError::InvalidListName(name.to_string()));
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::todo::{DueDate, NewTask, Priority};
+
+    fn temp_dir(name: &str) -> PathBuf {
+        let dir =
+            std::env::temp_dir().join(format!("todo_workspace_{}_{}", name, std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        dir
+    }
+
+    fn new_task(description: &str) -> NewTask {
+        NewTask {
+            description: description.to_string(),
+            due_date: DueDate::None,
+            category: "Work".to_string(),
+            priority: Priority::Low,
+        }
+    }
+
+    #[test]
+    fn test_create_rename_and_delete_lists() {
+        let dir = temp_dir("lists");
+        let mut workspace = Workspace::open(&dir, 0).unwrap();
+        assert_eq!(workspace.names().unwrap(), [DEFAULT_LIST]);
+        assert_eq!(workspace.current(), DEFAULT_LIST);
+
+        workspace.create("work").unwrap();
+        assert!(matches!(
+            workspace.create("work"),
+            Err(TodoError::ListExists(_))
+        ));
+        for invalid in ["", " work", ".hidden", "a/b"] {
+            assert!(matches!(
+                workspace.create(invalid),
+                Err(TodoError::InvalidListName(_))
+            ));
+        }
+
+        workspace.rename("work", "office.v2").unwrap();
+        workspace.rename("office.v2", "office").unwrap();
+        assert_eq!(workspace.names().unwrap(), [DEFAULT_LIST, "office"]);
+        assert!(matches!(
+            workspace.rename("work", "other"),
+            Err(TodoError::ListNotFound(_))
+        ));
+        assert!(matches!(
+            workspace.delete(DEFAULT_LIST),
+            Err(TodoError::CurrentList(_))
+        ));
+
+        let (storage, _) = workspace.switch("office").unwrap();
+        assert_eq!(workspace.current(), "office");
+        assert!(matches!(
+            workspace.rename("office", "busy"),
+            Err(TodoError::ListLocked { .. })
+        ));
+        drop(storage);
+        workspace.delete(DEFAULT_LIST).unwrap();
+        assert_eq!(workspace.names().unwrap(), ["office"]);
+
+        // Reopening starts on the list last switched to
+        workspace.create("home").unwrap();
+        let reopened = Workspace::open(&dir, 0).unwrap();
+        assert_eq!(reopened.current(), "office");
+        workspace.rename("office", "work").unwrap();
+        let reopened = Workspace::open(&dir, 0).unwrap();
+        assert_eq!(reopened.current(), "work");
+
+        // and on the first list when that one is gone and there is no default one
+        fs::remove_file(workspace.path("work")).unwrap();
+        let reopened = Workspace::open(&dir, 0).unwrap();
+        assert_eq!(reopened.current(), "home");
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_move_and_copy_tasks_between_lists() {
+        let dir = temp_dir("transfer");
+        let mut workspace = Workspace::open(&dir, 0).unwrap();
+        workspace.create("home").unwrap();
+        let (mut storage, mut todo_list) = workspace.switch(DEFAULT_LIST).unwrap();
+        let parent = todo_list.add_task(new_task("Move house"));
+        todo_list
+            .add_subtask(parent, new_task("Pack boxes"))
+            .unwrap();
+        let other = todo_list.add_task(new_task("Report"));
+
+        assert_eq!(workspace.copy_task(&todo_list, other, "home").unwrap(), [1]);
+        assert_eq!(
+            workspace
+                .move_task(&mut todo_list, storage.as_mut(), parent, "home")
+                .unwrap(),
+            [2, 3]
+        );
+        assert!(matches!(
+            workspace.copy_task(&todo_list, other, DEFAULT_LIST),
+            Err(TodoError::CurrentList(_))
+        ));
+        assert_eq!(todo_list.list_tasks(None).len(), 1);
+        drop(storage);
+        let (mut storage, saved) = workspace.open_list(DEFAULT_LIST).unwrap();
+        assert_eq!(saved.list_tasks(None).len(), 1);
+
+        // When the source list can't be saved, the move is taken back on both sides, even
+        // without a history to undo it with
+        todo_list.history_mut().set_limit(0);
+        let mut unsaved = storage::JsonFile::new(dir.join("missing").join("list.json"), 0);
+        assert!(workspace
+            .move_task(&mut todo_list, &mut unsaved, other, "home")
+            .is_err());
+        assert!(todo_list.get_task(other).is_some());
+        assert!(todo_list.history().redo_changes().next().is_none());
+        storage.save(&todo_list).unwrap();
+        drop(storage);
+
+        let (_, home) = workspace.switch("home").unwrap();
+        assert_eq!(home.get_task(1).unwrap().description, "Report");
+        assert_eq!(home.get_task(3).unwrap().parent, Some(2));
+        assert_eq!(home.list_tasks(None).len(), 3);
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,538 @@
+use clap::Parser;
+use std::fs;
+use std::path::{Path, PathBuf};
+use todo::{
+    cli,
+    commands::{run, Args},
+    query::TaskQuery,
+    todo::{DueDate, Priority, TodoError, TodoList},
+};
+
+fn temp_list_file(name: &str) -> PathBuf {
+    let path = std::env::temp_dir().join(format!("todo_{}_{}.json", name, std::process::id()));
+    let _ = fs::remove_file(&path);
+    path
+}
+
+fn run_args(file: &Path, args: &[&str]) -> Result<(), TodoError> {
+    let mut argv = vec!["todo", "--file", file.to_str().unwrap()];
+    argv.extend_from_slice(args);
+    run(Args::try_parse_from(argv).unwrap())
+}
+
+#[test]
+fn test_add_and_update_persist_to_file() {
+    let file = temp_list_file("add_update");
+
+    run_args(
+        &file,
+        &[
+            "add",
+            "Ship release",
+            "--due",
+            "2026-11-01",
+            "--category",
+            "Work",
+            "--priority",
+            "high",
+        ],
+    )
+    .unwrap();
+    run_args(&file, &["update", "1", "--description", "Ship 1.0"]).unwrap();
+
+    let list = TodoList::load_from_file(file.to_str().unwrap()).unwrap();
+    let task = list.get_task(1).unwrap();
+    assert_eq!(task.description, "Ship 1.0");
+    assert_eq!(task.category, "Work");
+    assert_eq!(task.priority, Priority::High);
+    assert_eq!(
+        task.due_date,
+        DueDate::On(chrono::NaiveDate::from_ymd_opt(2026, 11, 1).unwrap())
+    );
+
+    fs::remove_file(&file).unwrap();
+}
+
+#[test]
+fn test_remove_missing_task_fails() {
+    let file = temp_list_file("rm_missing");
+
+    run_args(&file, &["add", "Only task"]).unwrap();
+    assert!(matches!(
+        run_args(&file, &["rm", "12"]),
+        Err(TodoError::TaskNotFound)
+    ));
+    run_args(&file, &["rm", "1"]).unwrap();
+
+    let list = TodoList::load_from_file(file.to_str().unwrap()).unwrap();
+    assert!(list.list_tasks(None).is_empty());
+
+    fs::remove_file(&file).unwrap();
+}
+
+#[test]
+fn test_update_due_date_mode_and_clear() {
+    let file = temp_list_file("due_mode");
+    let date = chrono::NaiveDate::from_ymd_opt(2026, 11, 1).unwrap();
+
+    run_args(&file, &["add", "Invoice", "--due-by", "2026-11-01"]).unwrap();
+    let list = TodoList::load_from_file(file.to_str().unwrap()).unwrap();
+    assert_eq!(list.get_task(1).unwrap().due_date, DueDate::Before(date));
+
+    run_args(&file, &["update", "1", "--due-mode", "on"]).unwrap();
+    let list = TodoList::load_from_file(file.to_str().unwrap()).unwrap();
+    assert_eq!(list.get_task(1).unwrap().due_date, DueDate::On(date));
+
+    run_args(&file, &["update", "1", "--no-due"]).unwrap();
+    let list = TodoList::load_from_file(file.to_str().unwrap()).unwrap();
+    assert_eq!(list.get_task(1).unwrap().due_date, DueDate::None);
+
+    fs::remove_file(&file).unwrap();
+}
+
+#[test]
+fn test_update_with_merge_patch() {
+    let file = temp_list_file("patch");
+    let date = chrono::NaiveDate::from_ymd_opt(2026, 11, 1).unwrap();
+
+    run_args(
+        &file,
+        &["add", "Invoice", "--category", "Work", "--priority", "high"],
+    )
+    .unwrap();
+    run_args(
+        &file,
+        &[
+            "update",
+            "1",
+            "--patch",
+            r#"{"due_date": "2026-11-01", "due_mode": "by", "category": null, "priority": null}"#,
+        ],
+    )
+    .unwrap();
+    assert!(matches!(
+        run_args(
+            &file,
+            &["update", "1", "--patch", r#"{"description": null}"#]
+        ),
+        Err(TodoError::InvalidPatch(_))
+    ));
+    assert!(
+        Args::try_parse_from(["todo", "update", "1", "--patch", "{}", "--priority", "low"])
+            .is_err()
+    );
+
+    let list = TodoList::load_from_file(file.to_str().unwrap()).unwrap();
+    let task = list.get_task(1).unwrap();
+    assert_eq!(task.due_date, DueDate::Before(date));
+    assert_eq!(task.category, "General");
+    assert_eq!(task.priority, Priority::Low);
+    assert_eq!(task.description, "Invoice");
+
+    fs::remove_file(&file).unwrap();
+}
+
+#[test]
+fn test_subtasks_move_and_cascade() {
+    let file = temp_list_file("subtasks");
+
+    run_args(&file, &["add", "Release"]).unwrap();
+    run_args(&file, &["add", "Docs", "--parent", "1"]).unwrap();
+    run_args(&file, &["add", "Tag", "--parent", "2"]).unwrap();
+    assert!(matches!(
+        run_args(&file, &["move", "1", "--parent", "3"]),
+        Err(TodoError::InvalidParent(1, 3))
+    ));
+    run_args(&file, &["move", "3", "--parent", "1"]).unwrap();
+    run_args(&file, &["rm", "1", "--cascade"]).unwrap();
+
+    let list = TodoList::load_from_file(file.to_str().unwrap()).unwrap();
+    assert!(list.list_tasks(None).is_empty());
+
+    fs::remove_file(&file).unwrap();
+}
+
+#[test]
+fn test_block_rejects_cycles() {
+    let file = temp_list_file("block");
+
+    run_args(&file, &["add", "Design"]).unwrap();
+    run_args(&file, &["add", "Build"]).unwrap();
+    run_args(&file, &["block", "2", "--by", "1"]).unwrap();
+    assert!(matches!(
+        run_args(&file, &["block", "1", "--by", "2"]),
+        Err(TodoError::DependencyCycle(1, 2))
+    ));
+
+    let list = TodoList::load_from_file(file.to_str().unwrap()).unwrap();
+    let actionable: Vec<u32> = list.actionable_tasks().iter().map(|t| t.id).collect();
+    assert_eq!(actionable, [1]);
+
+    fs::remove_file(&file).unwrap();
+}
+
+#[test]
+fn test_tags_add_update_and_rename() {
+    let file = temp_list_file("tags");
+
+    run_args(
+        &file,
+        &["add", "Call client", "--tag", "urgent", "--tag", "+Phone"],
+    )
+    .unwrap();
+    run_args(&file, &["add", "Email client", "--tag", "email"]).unwrap();
+    run_args(
+        &file,
+        &["update", "2", "--tag", "urgent", "--untag", "email"],
+    )
+    .unwrap();
+    run_args(&file, &["rename-tag", "phone", "call"]).unwrap();
+    assert!(matches!(
+        run_args(&file, &["update", "1", "--tag", "two words"]),
+        Err(TodoError::InvalidTag(_))
+    ));
+
+    let list = TodoList::load_from_file(file.to_str().unwrap()).unwrap();
+    assert_eq!(
+        list.tag_counts(),
+        [("call".to_string(), 1), ("urgent".to_string(), 2)]
+    );
+    let tagged = |query: &str| -> Vec<u32> {
+        let query = TaskQuery::parse(query).unwrap();
+        list.query(&query).iter().map(|task| task.id).collect()
+    };
+    assert_eq!(tagged("+urgent +call"), [1]);
+    assert_eq!(tagged("anytag:call,urgent"), [1, 2]);
+
+    fs::remove_file(&file).unwrap();
+}
+
+#[test]
+fn test_category_rename_merge_and_delete() {
+    let file = temp_list_file("categories");
+
+    run_args(&file, &["add", "Fix bug", "--category", "Wrok"]).unwrap();
+    run_args(&file, &["add", "Review PR", "--category", "work "]).unwrap();
+    run_args(&file, &["add", "Buy milk", "--category", "Errands"]).unwrap();
+    run_args(&file, &["add", "Call mum", "--category", "Family"]).unwrap();
+    run_args(&file, &["rename-category", "work", "Work"]).unwrap();
+    run_args(&file, &["merge-categories", "wrok", "WORK"]).unwrap();
+    run_args(&file, &["delete-category", "errands"]).unwrap();
+    assert!(matches!(
+        run_args(&file, &["rename-category", "Family", "work"]),
+        Err(TodoError::CategoryExists(_))
+    ));
+    assert!(matches!(
+        run_args(&file, &["delete-category", "Errands"]),
+        Err(TodoError::CategoryNotFound(_))
+    ));
+
+    let list = TodoList::load_from_file(file.to_str().unwrap()).unwrap();
+    assert_eq!(list.get_categories(), ["Family", "General", "Work"]);
+    assert_eq!(list.list_tasks(Some("work")).len(), 2);
+
+    fs::remove_file(&file).unwrap();
+}
+
+#[test]
+fn test_notes_and_checklist() {
+    let file = temp_list_file("checklist");
+
+    run_args(&file, &["add", "Pack for trip"]).unwrap();
+    run_args(&file, &["notes", "1", "Flight at 9\nCheck in online"]).unwrap();
+    run_args(&file, &["add-item", "1", "Passport"]).unwrap();
+    run_args(&file, &["add-item", "1", "Tickets"]).unwrap();
+    run_args(&file, &["toggle", "1", "2"]).unwrap();
+    assert!(matches!(
+        run_args(&file, &["rm-item", "1", "3"]),
+        Err(TodoError::ChecklistItemNotFound(1, 3))
+    ));
+
+    let list = TodoList::load_from_file(file.to_str().unwrap()).unwrap();
+    let task = list.get_task(1).unwrap();
+    assert_eq!(task.notes.lines().count(), 2);
+    assert_eq!(task.checklist_percent(), Some(50));
+    assert!(task.checklist[1].checked);
+
+    fs::remove_file(&file).unwrap();
+}
+
+#[test]
+fn test_timer_persists_between_commands() {
+    let file = temp_list_file("timer");
+
+    run_args(&file, &["add", "Fix login", "--category", "Work"]).unwrap();
+    run_args(&file, &["add", "Taxes"]).unwrap();
+    run_args(&file, &["track", "1"]).unwrap();
+    assert!(matches!(
+        run_args(&file, &["track", "2"]),
+        Err(TodoError::TimerRunning(1))
+    ));
+
+    let list = TodoList::load_from_file(file.to_str().unwrap()).unwrap();
+    assert_eq!(list.running_timer().map(|(id, _)| id), Some(1));
+
+    run_args(&file, &["stop"]).unwrap();
+    assert!(matches!(
+        run_args(&file, &["stop"]),
+        Err(TodoError::NoTimerRunning)
+    ));
+    run_args(&file, &["report", "--by", "category", "--csv"]).unwrap();
+
+    let list = TodoList::load_from_file(file.to_str().unwrap()).unwrap();
+    let task = list.get_task(1).unwrap();
+    assert!(list.running_timer().is_none());
+    assert_eq!(task.time_entries.len(), 1);
+    assert!(task.time_entries[0].stopped_at.is_some());
+
+    fs::remove_file(&file).unwrap();
+}
+
+#[test]
+fn test_undo_across_commands_needs_persisted_history() {
+    let file = temp_list_file("undo");
+
+    run_args(&file, &["add", "Ship release"]).unwrap();
+    assert!(matches!(
+        run_args(&file, &["undo"]),
+        Err(TodoError::NothingToUndo)
+    ));
+
+    assert!(matches!(
+        run_args(&file, &["history", "--limit", "10"]),
+        Err(TodoError::HistoryNotPersisted)
+    ));
+    run_args(&file, &["history", "--persist", "--limit", "10"]).unwrap();
+    run_args(&file, &["history", "--limit", "20"]).unwrap();
+    run_args(&file, &["rm", "1"]).unwrap();
+    run_args(&file, &["undo"]).unwrap();
+    let list = TodoList::load_from_file(file.to_str().unwrap()).unwrap();
+    assert_eq!(list.get_task(1).unwrap().description, "Ship release");
+    assert_eq!(list.history().limit(), 20);
+
+    run_args(&file, &["redo"]).unwrap();
+    let list = TodoList::load_from_file(file.to_str().unwrap()).unwrap();
+    assert!(list.get_task(1).is_none());
+
+    fs::remove_file(&file).unwrap();
+}
+
+#[test]
+fn test_journal_converts_snapshot_and_reads_as_of() {
+    let file = temp_list_file("journal");
+
+    run_args(&file, &["add", "Saved as a snapshot"]).unwrap();
+    run_args(&file, &["--journal", "add", "Journaled"]).unwrap();
+    run_args(&file, &["update", "2", "--priority", "high"]).unwrap();
+    let lines = fs::read_to_string(&file).unwrap().lines().count();
+    assert_eq!(lines, 3);
+
+    let (_, list) = todo::journal::Journal::open(&file).unwrap();
+    assert_eq!(list.get_task(2).unwrap().priority, Priority::High);
+
+    assert!(matches!(
+        run_args(&file, &["--as-of", "2000-01-01", "list"]),
+        Err(TodoError::JournalCompacted(_))
+    ));
+    run_args(&file, &["--as-of", "2999-01-01T00:00:00Z", "list"]).unwrap();
+    assert!(matches!(
+        run_args(&file, &["--as-of", "2999-01-01", "done", "1"]),
+        Err(TodoError::ReadOnly)
+    ));
+
+    run_args(&file, &["compact"]).unwrap();
+    assert_eq!(fs::read_to_string(&file).unwrap().lines().count(), 1);
+    let (_, list) = todo::journal::Journal::open(&file).unwrap();
+    assert_eq!(list.list_tasks(None).len(), 2);
+
+    fs::remove_file(&file).unwrap();
+}
+
+#[test]
+fn test_saves_keep_backups_and_respect_the_lock() {
+    let file = temp_list_file("backups");
+
+    for description in ["First", "Second", "Third"] {
+        run_args(&file, &["--backups", "1", "add", description]).unwrap();
+    }
+    let backup = todo::fileio::backup_path(&file, 1);
+    let previous = TodoList::load_from_file(backup.to_str().unwrap()).unwrap();
+    assert_eq!(previous.list_tasks(None).len(), 2);
+    assert!(!todo::fileio::backup_path(&file, 2).exists());
+
+    let lock = todo::fileio::ListLock::acquire(&file).unwrap();
+    assert!(matches!(
+        run_args(&file, &["add", "Blocked"]),
+        Err(TodoError::ListLocked { .. })
+    ));
+    drop(lock);
+    run_args(&file, &["add", "Fourth"]).unwrap();
+
+    fs::remove_file(&file).unwrap();
+    fs::remove_file(&backup).unwrap();
+}
+
+#[test]
+fn test_journal_compaction_respects_backups() {
+    let file = temp_list_file("journal_backups");
+    for number in 1..=2 {
+        let _ = fs::remove_file(todo::fileio::backup_path(&file, number));
+    }
+
+    run_args(&file, &["add", "Saved as a snapshot"]).unwrap();
+    run_args(&file, &["--journal", "--backups", "0", "add", "Journaled"]).unwrap();
+    run_args(&file, &["--backups", "0", "compact"]).unwrap();
+    assert!(!todo::fileio::backup_path(&file, 1).exists());
+
+    run_args(&file, &["--backups", "1", "compact"]).unwrap();
+    run_args(&file, &["--backups", "1", "compact"]).unwrap();
+    assert!(todo::fileio::backup_path(&file, 1).exists());
+    assert!(!todo::fileio::backup_path(&file, 2).exists());
+
+    fs::remove_file(&file).unwrap();
+    fs::remove_file(todo::fileio::backup_path(&file, 1)).unwrap();
+}
+
+#[test]
+fn test_migrate_to_database() {
+    let file = temp_list_file("migrate");
+    let database = file.with_extension("db");
+    let _ = fs::remove_file(&database);
+
+    run_args(
+        &file,
+        &[
+            "add",
+            "Send invoice",
+            "--category",
+            "Work",
+            "--tag",
+            "billing",
+        ],
+    )
+    .unwrap();
+    run_args(&file, &["add", "Water plants", "--category", "Home"]).unwrap();
+    run_args(&file, &["migrate", database.to_str().unwrap()]).unwrap();
+    assert!(matches!(
+        run_args(&file, &["migrate", database.to_str().unwrap()]),
+        Err(TodoError::FileExists(_))
+    ));
+    assert!(todo::sqlite::is_database(&database).unwrap());
+
+    run_args(&database, &["done", "2"]).unwrap();
+    run_args(&database, &["list", "cat:work", "+billing"]).unwrap();
+    let mut storage = todo::storage::open_file(&database, false, 0).unwrap();
+    let open = storage.find_tasks(&TaskQuery::open()).unwrap();
+    assert_eq!(open.len(), 1);
+    assert_eq!(open[0].description, "Send invoice");
+    drop(storage);
+
+    fs::remove_file(&file).unwrap();
+    fs::remove_file(&database).unwrap();
+}
+
+#[test]
+fn test_workspace_lists_have_their_own_ids() {
+    let dir = std::env::temp_dir().join(format!("todo_workspace_{}", std::process::id()));
+    let _ = fs::remove_dir_all(&dir);
+    let file = temp_list_file("unused");
+    let workspace = dir.to_str().unwrap();
+
+    run_args(&file, &["--workspace", workspace, "add", "Default task"]).unwrap();
+    todo::workspace::Workspace::open(&dir, 0)
+        .unwrap()
+        .create("work")
+        .unwrap();
+    run_args(
+        &file,
+        &[
+            "--workspace",
+            workspace,
+            "--list",
+            "work",
+            "add",
+            "Work task",
+        ],
+    )
+    .unwrap();
+    assert!(matches!(
+        run_args(&file, &["--workspace", workspace, "--list", "home", "list"]),
+        Err(TodoError::ListNotFound(_))
+    ));
+    assert!(!file.exists());
+
+    let work = TodoList::load_from_file(dir.join("work.json").to_str().unwrap()).unwrap();
+    assert_eq!(work.get_task(1).unwrap().description, "Work task");
+    let default = TodoList::load_from_file(dir.join("default.json").to_str().unwrap()).unwrap();
+    assert_eq!(default.get_task(1).unwrap().description, "Default task");
+
+    fs::remove_dir_all(&dir).unwrap();
+}
+
+// The scripted editor first breaks the priority field and adds a note that looks like an
+// error annotation, then fixes the field once the document comes back annotated, keeping a
+// copy of the annotated document next to itself
+#[cfg(unix)]
+#[test]
+fn test_edit_reopens_editor_on_errors() {
+    use std::os::unix::fs::PermissionsExt;
+
+    let file = temp_list_file("edit");
+    let dir = std::env::temp_dir().join(format!("todo_editor_{}", std::process::id()));
+    fs::create_dir_all(&dir).unwrap();
+    let editor = dir.join("editor.sh");
+    fs::write(
+        &editor,
+        r#"#!/bin/sh
+dir=$(dirname "$0")
+if grep -q '^# ERROR: unknown field' "$1"; then
+    cp "$1" "$dir/annotated.txt"
+    sed -e 's/^prio: high/priority: high/' "$1" > "$dir/edited.txt"
+else
+    sed -e 's/^priority: .*/prio: high/' -e 's/^tags:.*/tags: urgent/' -e 's/^due:.*/due:/' \
+        "$1" > "$dir/edited.txt"
+    echo '# ERROR: not really' >> "$dir/edited.txt"
+fi
+cat "$dir/edited.txt" > "$1"
+"#,
+    )
+    .unwrap();
+    fs::set_permissions(&editor, fs::Permissions::from_mode(0o755)).unwrap();
+
+    run_args(&file, &["add", "Call client", "--due", "2026-11-01"]).unwrap();
+    let mut list = TodoList::load_from_file(file.to_str().unwrap()).unwrap();
+    assert!(cli::edit_task_in_editor(&mut list, 1, editor.to_str().unwrap()).unwrap());
+
+    let annotated = fs::read_to_string(dir.join("annotated.txt")).unwrap();
+    let error = annotated
+        .lines()
+        .position(|line| line.starts_with("# ERROR: unknown field 'prio'"))
+        .unwrap();
+    assert_eq!(annotated.lines().nth(error + 1), Some("prio: high"));
+    let task = list.get_task(1).unwrap();
+    assert_eq!(task.priority, Priority::High);
+    assert_eq!(task.due_date, DueDate::None);
+    assert!(task.tags.contains("urgent"));
+    assert_eq!(task.notes, "# ERROR: not really");
+
+    fs::remove_dir_all(&dir).unwrap();
+    fs::remove_file(&file).unwrap();
+}
+
+#[test]
+fn test_invalid_arguments_are_rejected() {
+    assert!(Args::try_parse_from(["todo", "add", "Task", "--priority", "urgent"]).is_err());
+    assert!(Args::try_parse_from(["todo", "add", "Task", "--due", "soon"]).is_err());
+    assert!(Args::try_parse_from(["todo", "list", "--sort", "size"]).is_err());
+    assert!(Args::try_parse_from(["todo", "list", "--sort", "-due,category:asc"]).is_ok());
+    assert!(Args::try_parse_from([
+        "todo",
+        "add",
+        "Task",
+        "--due",
+        "2026-11-01",
+        "--due-by",
+        "2026-11-02"
+    ])
+    .is_err());
+}
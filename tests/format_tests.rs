@@ -0,0 +1,93 @@
+use chrono::NaiveDate;
+use std::fs;
+use std::path::PathBuf;
+use todo::{
+    migrations::FORMAT_VERSION,
+    todo::{DueDate, Priority, Status, TodoError, TodoList},
+};
+
+// Every file in tests/data was saved by an earlier version of todo, named after its format
+// version, and must keep loading
+
+fn corpus_file(name: &str) -> String {
+    PathBuf::from(env!("CARGO_MANIFEST_DIR"))
+        .join("tests/data")
+        .join(name)
+        .to_str()
+        .unwrap()
+        .to_string()
+}
+
+#[test]
+fn test_load_baseline_file() {
+    let list = TodoList::load_from_file(&corpus_file("v1_baseline.json")).unwrap();
+    let task = list.get_task(1).unwrap();
+    assert_eq!(task.status, Status::NotStarted);
+    assert_eq!(task.priority, Priority::High);
+    assert_eq!(
+        task.due_date,
+        DueDate::On(NaiveDate::from_ymd_opt(2024, 5, 1).unwrap())
+    );
+    // ` work ` and `Work` are one category now, and a blank one is General
+    assert_eq!(list.get_categories(), ["General", "Work"]);
+    assert_eq!(list.get_task(1).unwrap().category, "Work");
+}
+
+#[test]
+fn test_load_recurring_file() {
+    let list = TodoList::load_from_file(&corpus_file("v1_recurring.json")).unwrap();
+    assert_eq!(list.get_task(1).unwrap().next_occurrence, Some(2));
+    assert_eq!(list.get_task(2).unwrap().recurs_from, Some(1));
+    assert_eq!(list.get_task(3).unwrap().status, Status::InProgress);
+    assert_eq!(list.get_categories(), ["Home"]);
+}
+
+#[test]
+fn test_load_subtasks_and_tags_file() {
+    let list = TodoList::load_from_file(&corpus_file("v1_subtasks_tags.json")).unwrap();
+    let task = list.get_task(1).unwrap();
+    assert_eq!(task.category, "Work/ClientA");
+    assert_eq!(task.checklist_percent(), Some(50));
+    assert_eq!(task.time_entries.len(), 1);
+    assert!(task.tags.contains("urgent"));
+    assert_eq!(list.get_task(2).unwrap().category, "Work/ClientA");
+    assert_eq!(list.open_blockers(2).len(), 1);
+    assert_eq!(list.subtasks(1).len(), 2);
+}
+
+#[test]
+fn test_load_current_file_with_history() {
+    let mut list = TodoList::load_from_file(&corpus_file("v2_history.json")).unwrap();
+    assert!(list.history().is_persisted());
+    list.undo().unwrap();
+    assert_eq!(list.get_task(1).unwrap().priority, Priority::Low);
+}
+
+#[test]
+fn test_newer_file_is_rejected() {
+    let result = TodoList::load_from_file(&corpus_file("v99_future.json"));
+    assert!(matches!(
+        result,
+        Err(TodoError::UnsupportedVersion {
+            found: 99,
+            supported: FORMAT_VERSION
+        })
+    ));
+}
+
+#[test]
+fn test_migrated_file_is_saved_in_the_current_format() {
+    let list = TodoList::load_from_file(&corpus_file("v1_baseline.json")).unwrap();
+    let file = std::env::temp_dir().join(format!("todo_migrated_{}.json", std::process::id()));
+    let file = file.to_str().unwrap();
+    list.save_to_file(file).unwrap();
+
+    let saved: serde_json::Value =
+        serde_json::from_str(&fs::read_to_string(file).unwrap()).unwrap();
+    assert_eq!(saved["version"], FORMAT_VERSION);
+    assert_eq!(saved["list"]["tasks"]["1"]["category"], "Work");
+    let reloaded = TodoList::load_from_file(file).unwrap();
+    assert_eq!(reloaded.list_tasks(None).len(), 3);
+
+    fs::remove_file(file).unwrap();
+}
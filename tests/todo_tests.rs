@@ -1,6 +1,8 @@
 use chrono::NaiveDate;
 use todo::{
     cli::DEFAULT_CATEGORY,
+    patch::Patch,
+    query::TaskQuery,
     todo::{DueDate, NewTask, Priority, TaskUpdate, TodoList},
 };
 
@@ -36,7 +38,7 @@ fn test_remove_task() {
     let id = todo_list.add_task(task_update);
     assert!(todo_list.remove_task(id).is_ok());
     assert_eq!(todo_list.list_tasks(None).len(), 0);
-    assert!(!todo_list.remove_task(id).is_ok());
+    assert!(todo_list.remove_task(id).is_err());
 }
 
 #[test]
@@ -77,13 +79,13 @@ fn test_update_task_preserve_due_date() {
     });
 
     let task_update = TaskUpdate {
-        description: Some("Updated task".to_string()),
-        due_date: Some(original_date),
-        category: None,
-    };
+        description: Patch::Set("Updated task".to_string()),
+        ..TaskUpdate::default()
+    }
+    .with_due_date(DueDate::On(original_date));
 
     // Update only the description
-    todo_list.update_task(id, task_update);
+    todo_list.update_task(id, task_update).unwrap();
 
     let updated_task = todo_list.get_task(id).unwrap();
     assert_eq!(updated_task.description, "Updated task");
@@ -95,12 +97,8 @@ fn test_update_task_preserve_due_date() {
 
     // Update only the due date
     let new_date = NaiveDate::from_ymd_opt(2024, 7, 15).unwrap();
-    let task_update = TaskUpdate {
-        description: None,
-        due_date: Some(new_date),
-        category: None,
-    };
-    todo_list.update_task(id, task_update);
+    let task_update = TaskUpdate::default().with_due_date(DueDate::On(new_date));
+    todo_list.update_task(id, task_update).unwrap();
 
     let updated_task = todo_list.get_task(id).unwrap();
     assert_eq!(updated_task.description, "Updated task");
@@ -110,12 +108,8 @@ fn test_update_task_preserve_due_date() {
         panic!("Expected DueDate::On");
     }
     // Update neither description nor due date
-    let task_update = TaskUpdate {
-        description: None,
-        due_date: None,
-        category: None,
-    };
-    todo_list.update_task(id, task_update);
+    let task_update = TaskUpdate::default();
+    todo_list.update_task(id, task_update).unwrap();
 
     let updated_task = todo_list.get_task(id).unwrap();
     assert_eq!(updated_task.description, "Updated task");
@@ -126,11 +120,10 @@ fn test_update_task_preserve_due_date() {
     }
     // Update only the category
     let task_update = TaskUpdate {
-        description: None,
-        due_date: None,
-        category: Some("Programming".to_string()),
+        category: Patch::Set("Programming".to_string()),
+        ..TaskUpdate::default()
     };
-    todo_list.update_task(id, task_update);
+    todo_list.update_task(id, task_update).unwrap();
 
     let updated_task = todo_list.get_task(id).unwrap();
     assert_eq!(updated_task.description, "Updated task");
@@ -171,3 +164,60 @@ fn test_list_tasks_by_category() {
     let personal_tasks = todo_list.list_tasks(Some("Personal"));
     assert_eq!(personal_tasks.len(), 2);
 }
+
+#[test]
+fn test_update_task_priority() {
+    let mut todo_list = TodoList::new();
+    let id = todo_list.add_task(NewTask {
+        description: "Fix bug".to_string(),
+        due_date: DueDate::None,
+        category: DEFAULT_CATEGORY.to_string(),
+        priority: Priority::Low,
+    });
+
+    let task_update = TaskUpdate {
+        priority: Patch::Set(Priority::High),
+        ..TaskUpdate::default()
+    };
+    todo_list.update_task(id, task_update).unwrap();
+
+    let updated_task = todo_list.get_task(id).unwrap();
+    assert_eq!(updated_task.priority, Priority::High);
+    assert_eq!(updated_task.description, "Fix bug");
+}
+
+#[test]
+fn test_query_tasks() {
+    let mut todo_list = TodoList::new();
+    todo_list.add_task(NewTask {
+        description: "Send invoice".to_string(),
+        due_date: DueDate::On(NaiveDate::from_ymd_opt(2026, 10, 20).unwrap()),
+        category: "Work".to_string(),
+        priority: Priority::High,
+    });
+    todo_list.add_task(NewTask {
+        description: "Plan offsite".to_string(),
+        due_date: DueDate::None,
+        category: "Work".to_string(),
+        priority: Priority::Low,
+    });
+    let done = todo_list.add_task(NewTask {
+        description: "Pay invoice".to_string(),
+        due_date: DueDate::None,
+        category: "Personal".to_string(),
+        priority: Priority::Medium,
+    });
+    todo_list.complete_task(done).unwrap();
+
+    let query =
+        TaskQuery::parse(r#"cat:Work prio>=medium due<2026-11-01 status:open "invoice""#).unwrap();
+    let tasks = todo_list.query(&query);
+    assert_eq!(tasks.len(), 1);
+    assert_eq!(tasks[0].description, "Send invoice");
+
+    let query = TaskQuery::parse("invoice OR NOT cat:Work").unwrap();
+    assert_eq!(todo_list.query(&query).len(), 2);
+
+    let query = TaskQuery::Category("Work".to_string()).and(TaskQuery::open());
+    assert_eq!(todo_list.query(&query).len(), 2);
+}